@@ -1,6 +1,7 @@
 use anyhow::{Result, anyhow};
 use libc::size_t;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 use std::ptr;
@@ -37,6 +38,7 @@ pub struct Message {
 
 impl Message {
     /// Create a new message from Rust data
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         role: &str,
         label: &str,
@@ -139,6 +141,7 @@ impl Message {
                             .map(|s| s.to_string())
                     })
                     .collect::<Result<Vec<String>, _>>()?,
+                fields: BTreeMap::new(),
             })
         }
     }
@@ -190,6 +193,10 @@ pub struct MessageData {
     pub line: i32,
     pub level: i32,
     pub messages: Vec<String>,
+    /// Extra key/value pairs from sources such as syslog structured data.
+    /// Not part of the binary encoding.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
 }
 
 impl MessageData {
@@ -199,7 +206,7 @@ impl MessageData {
     }
     /// Convert to FFI message
     pub fn to_ffi(&self) -> Result<Message> {
-        let msg = Message::new(
+        Message::new(
             &self.role,
             &self.label,
             &self.file,
@@ -210,9 +217,7 @@ impl MessageData {
             self.line,
             self.level,
             self.messages.clone(),
-        );
-
-        msg
+        )
     }
     pub fn to_json_string(&self) -> Result<String> {
         serde_json::to_string(self).map_err(|e| anyhow!("Failed to serialize message: {}", e))
@@ -245,6 +250,7 @@ mod tests {
                 "Calling C++ encoding function".to_string(),
                 "Success!".to_string(),
             ],
+            fields: BTreeMap::new(),
         };
 
         println!("Original message: {:?}", test_message);
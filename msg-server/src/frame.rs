@@ -0,0 +1,92 @@
+//! Length-prefixed framing for encoded XCLOG messages.
+//!
+//! An encoded message is not self-delimiting (the message list runs to the end
+//! of the buffer), so streams and capture files prefix every frame with its
+//! size as a little-endian `u64`, mirroring the `size_t` prefixes used inside
+//! the encoding itself.
+use anyhow::{Result, anyhow};
+use std::io::{ErrorKind, Read, Write};
+
+/// Frames larger than this are treated as corrupt input.
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+pub fn encode_frame(data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(8 + data.len());
+    frame.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    frame.extend_from_slice(data);
+    frame
+}
+
+pub fn write_frame<W: Write>(writer: &mut W, data: &[u8]) -> Result<()> {
+    writer.write_all(&(data.len() as u64).to_le_bytes())?;
+    writer.write_all(data)?;
+    Ok(())
+}
+
+/// Read one frame, returning `None` on a clean end of stream.
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut size = [0u8; 8];
+    match reader.read_exact(&mut size) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let size = u64::from_le_bytes(size) as usize;
+    if size > MAX_FRAME_SIZE {
+        return Err(anyhow!("frame too large: {} bytes", size));
+    }
+    let mut data = vec![0u8; size];
+    reader.read_exact(&mut data)?;
+    Ok(Some(data))
+}
+
+/// Split a buffer holding several consecutive frames.
+pub fn split_frames(mut data: &[u8]) -> Result<Vec<&[u8]>> {
+    let mut frames = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 8 {
+            return Err(anyhow!("truncated frame header"));
+        }
+        let size = u64::from_le_bytes(data[..8].try_into().unwrap()) as usize;
+        if size > data.len() - 8 {
+            return Err(anyhow!("truncated frame body"));
+        }
+        frames.push(&data[8..8 + size]);
+        data = &data[8 + size..];
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn frames_round_trip() {
+        let mut stream = Vec::new();
+        write_frame(&mut stream, b"first").unwrap();
+        stream.extend(encode_frame(b""));
+        write_frame(&mut stream, b"third").unwrap();
+
+        let frames = split_frames(&stream).unwrap();
+        assert_eq!(frames, [&b"first"[..], b"", b"third"]);
+
+        let mut reader = Cursor::new(&stream);
+        assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"first");
+        assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"");
+        assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"third");
+        assert!(read_frame(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_and_oversized_frames_are_errors() {
+        let frame = encode_frame(b"payload");
+        assert!(split_frames(&frame[..4]).is_err());
+        assert!(split_frames(&frame[..frame.len() - 1]).is_err());
+        assert!(read_frame(&mut Cursor::new(&frame[..frame.len() - 1])).is_err());
+
+        let oversized = ((MAX_FRAME_SIZE + 1) as u64).to_le_bytes();
+        assert!(read_frame(&mut Cursor::new(&oversized)).is_err());
+    }
+}
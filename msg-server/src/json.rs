@@ -0,0 +1,167 @@
+//! Lenient JSON form of [`MessageData`] for producers that cannot link the
+//! C++ client, such as shell scripts or web services posting over HTTP.
+//!
+//! Only `messages` is required; it may be a single string or a list, and
+//! non-string items are kept as their JSON text. `message` is accepted as an
+//! alias. `level` takes a number or a name understood by
+//! [`parse_level`](crate::level::parse_level). Missing fields get defaults:
+//! the current time, `INFO`, and empty strings or zero for the rest. An
+//! optional `fields` object is kept as key/value text.
+use crate::MessageData;
+use crate::level::{INFO, parse_level};
+use anyhow::{Result, anyhow, bail};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn now_micros() -> usize {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as usize)
+        .unwrap_or_default()
+}
+
+fn string_field(object: &Map<String, Value>, key: &str) -> Result<String> {
+    match object.get(key) {
+        None | Some(Value::Null) => Ok(String::new()),
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(_) => Err(anyhow!("{key}: expected a string")),
+    }
+}
+
+fn unsigned_field(object: &Map<String, Value>, key: &str) -> Result<Option<usize>> {
+    match object.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(|v| Some(v as usize))
+            .ok_or_else(|| anyhow!("{key}: expected a non-negative integer")),
+    }
+}
+
+fn level_field(object: &Map<String, Value>) -> Result<i32> {
+    match object.get("level") {
+        None | Some(Value::Null) => Ok(INFO),
+        Some(Value::Number(level)) => level
+            .as_i64()
+            .and_then(|l| i32::try_from(l).ok())
+            .ok_or_else(|| anyhow!("level: expected an integer")),
+        Some(Value::String(name)) => {
+            parse_level(name).ok_or_else(|| anyhow!("level: unknown level {name:?}"))
+        }
+        Some(_) => Err(anyhow!("level: expected a number or a level name")),
+    }
+}
+
+fn text(item: &Value) -> String {
+    match item {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn fields_field(object: &Map<String, Value>) -> Result<BTreeMap<String, String>> {
+    match object.get("fields") {
+        None | Some(Value::Null) => Ok(BTreeMap::new()),
+        Some(Value::Object(fields)) => {
+            Ok(fields.iter().map(|(k, v)| (k.clone(), text(v))).collect())
+        }
+        Some(_) => Err(anyhow!("fields: expected an object")),
+    }
+}
+
+fn messages_field(object: &Map<String, Value>) -> Result<Vec<String>> {
+    let value = object
+        .get("messages")
+        .or_else(|| object.get("message"))
+        .ok_or_else(|| anyhow!("messages: missing"))?;
+    match value {
+        Value::Array(items) => Ok(items.iter().map(text).collect()),
+        Value::Null => Err(anyhow!("messages: missing")),
+        other => Ok(vec![text(other)]),
+    }
+}
+
+impl MessageData {
+    /// Builds a message from one JSON object, see the module docs for defaults.
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let Value::Object(object) = value else {
+            bail!("expected a JSON object");
+        };
+        let line = match object.get("line") {
+            None | Some(Value::Null) => 0,
+            Some(value) => value
+                .as_i64()
+                .and_then(|l| i32::try_from(l).ok())
+                .ok_or_else(|| anyhow!("line: expected an integer"))?,
+        };
+        Ok(MessageData {
+            role: string_field(object, "role")?,
+            label: string_field(object, "label")?,
+            file: string_field(object, "file")?,
+            function: string_field(object, "function")?,
+            time: unsigned_field(object, "time")?.unwrap_or_else(now_micros),
+            process_id: unsigned_field(object, "process_id")?.unwrap_or_default(),
+            thread_id: unsigned_field(object, "thread_id")?.unwrap_or_default(),
+            line,
+            level: level_field(object)?,
+            messages: messages_field(object)?,
+            fields: fields_field(object)?,
+        })
+    }
+}
+
+/// Parses a single object or an array of objects. Each item is parsed on its
+/// own so one bad record does not reject the rest of a batch.
+pub fn parse_batch(body: &[u8]) -> Result<Vec<Result<MessageData>>> {
+    let value: Value = serde_json::from_slice(body)?;
+    Ok(match &value {
+        Value::Array(items) => items.iter().map(MessageData::from_json).collect(),
+        single => vec![MessageData::from_json(single)],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::ERROR;
+    use serde_json::json;
+
+    #[test]
+    fn defaults_and_aliases() {
+        let data = MessageData::from_json(&json!({"message": "hello", "level": "err"})).unwrap();
+        assert_eq!(data.messages, ["hello"]);
+        assert_eq!(data.level, ERROR);
+        assert_eq!(data.process_id, 0);
+        assert!(data.time > 0);
+
+        let data = MessageData::from_json(&json!({
+            "role": "sh", "time": 5, "line": 3, "messages": ["a", 1, {"k": true}],
+            "fields": {"host": "b1", "n": 2}
+        }))
+        .unwrap();
+        assert_eq!(data.role, "sh");
+        assert_eq!(data.time, 5);
+        assert_eq!(data.messages, ["a", "1", r#"{"k":true}"#]);
+        assert_eq!(data.fields["host"], "b1");
+        assert_eq!(data.fields["n"], "2");
+    }
+
+    #[test]
+    fn invalid_records() {
+        assert!(MessageData::from_json(&json!({"role": "sh"})).is_err());
+        assert!(MessageData::from_json(&json!({"messages": "a", "role": 1})).is_err());
+        assert!(MessageData::from_json(&json!({"messages": "a", "time": -1})).is_err());
+        assert!(MessageData::from_json(&json!({"messages": "a", "level": "loud"})).is_err());
+        assert!(MessageData::from_json(&json!({"messages": "a", "fields": [1]})).is_err());
+        assert!(MessageData::from_json(&json!("text")).is_err());
+    }
+
+    #[test]
+    fn batches() {
+        let batch = parse_batch(br#"[{"messages": "a"}, {"messages": null}]"#).unwrap();
+        assert!(batch[0].is_ok() && batch[1].is_err());
+        assert_eq!(parse_batch(br#"{"messages": "a"}"#).unwrap().len(), 1);
+        assert!(parse_batch(b"not json").is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Conventional level numbers used when a source only provides a level name.
+//! Producers linking the C++ client are free to use any integer.
+
+pub const TRACE: i32 = 0;
+pub const DEBUG: i32 = 1;
+pub const INFO: i32 = 2;
+pub const WARN: i32 = 3;
+pub const ERROR: i32 = 4;
+pub const FATAL: i32 = 5;
+
+/// Parse a level given either as a number or as a common level name.
+pub fn parse_level(value: &str) -> Option<i32> {
+    let value = value.trim();
+    if let Ok(level) = value.parse::<i32>() {
+        return Some(level);
+    }
+    match value.to_ascii_lowercase().as_str() {
+        "trace" | "verbose" => Some(TRACE),
+        "debug" | "dbg" | "d" => Some(DEBUG),
+        "info" | "information" | "notice" | "i" => Some(INFO),
+        "warn" | "warning" | "w" => Some(WARN),
+        "error" | "err" | "e" => Some(ERROR),
+        "fatal" | "critical" | "crit" | "panic" | "f" => Some(FATAL),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_numbers() {
+        assert_eq!(parse_level(" 7 "), Some(7));
+        assert_eq!(parse_level("-1"), Some(-1));
+        assert_eq!(parse_level("WARNING"), Some(WARN));
+        assert_eq!(parse_level("Err"), Some(ERROR));
+        assert_eq!(parse_level("notice"), Some(INFO));
+        assert_eq!(parse_level("crit"), Some(FATAL));
+        assert_eq!(parse_level("loud"), None);
+        assert_eq!(parse_level(""), None);
+    }
+}
@@ -1,4 +1,12 @@
 mod ffi_wrapper;
+pub mod frame;
+pub mod json;
+pub mod level;
+pub mod stream_support;
+pub mod syslog_support;
+#[cfg(test)]
+mod testing;
+pub mod udp_support;
 pub mod zmq_support;
 pub use ffi_wrapper::{Message, MessageData};
 
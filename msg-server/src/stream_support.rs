@@ -0,0 +1,401 @@
+//! Plain stream listeners for clients that cannot link ZeroMQ, e.g. `nc` or
+//! `socat`. Addresses are `tcp://host:port` or, on Unix, `unix:///path`.
+//! A connection carries newline-delimited JSON records (see
+//! [`json`](crate::json)), length-prefixed XCLOG frames (see
+//! [`frame`](crate::frame)) or syslog records (see
+//! [`syslog_support`](crate::syslog_support)).
+use crate::ffi_wrapper::MessageData;
+use crate::frame::MAX_FRAME_SIZE;
+use crate::json::parse_batch;
+use crate::syslog_support::next_record;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often idle accept and read loops check whether the server was closed.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamFormat {
+    /// One JSON object, or an array of them, per line.
+    #[default]
+    Ndjson,
+    /// Encoded messages, each prefixed with its size as a little-endian `u64`.
+    Frames,
+    /// Syslog records, octet counted or newline terminated.
+    Syslog,
+}
+
+type Handler = Arc<RwLock<Box<dyn Fn(MessageData) + Send + Sync>>>;
+
+enum Listener {
+    Tcp(TcpListener),
+    /// The socket file with its device and inode, see [`socket_file_id`].
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener, String, Option<(u64, u64)>),
+}
+
+#[cfg(unix)]
+fn socket_file_id(path: &str) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| (m.dev(), m.ino()))
+}
+
+#[cfg(unix)]
+fn is_stale_socket(path: &str) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket())
+        && std::os::unix::net::UnixStream::connect(path).is_err()
+}
+
+trait Connection: Read + Send {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+}
+
+impl Connection for std::net::TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        std::net::TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for std::os::unix::net::UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        std::os::unix::net::UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl Listener {
+    fn bind(address: &str) -> Result<Self> {
+        if let Some(host) = address.strip_prefix("tcp://") {
+            let listener = TcpListener::bind(host)?;
+            listener.set_nonblocking(true)?;
+            return Ok(Listener::Tcp(listener));
+        }
+        #[cfg(unix)]
+        if let Some(path) = address.strip_prefix("unix://") {
+            // A socket file left behind by an earlier run would make bind fail.
+            // Anything else at the path, or a socket that still accepts
+            // connections, is left alone and bind reports the conflict.
+            if is_stale_socket(path) {
+                let _ = std::fs::remove_file(path);
+            }
+            let listener = std::os::unix::net::UnixListener::bind(path)?;
+            listener.set_nonblocking(true)?;
+            return Ok(Listener::Unix(
+                listener,
+                path.to_string(),
+                socket_file_id(path),
+            ));
+        }
+        Err(anyhow!("unsupported stream address: {address}"))
+    }
+
+    fn accept(&self) -> std::io::Result<Box<dyn Connection>> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                Ok(Box::new(stream))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener, _, _) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                Ok(Box::new(stream))
+            }
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        // A listener started on the same path since then owns the file now.
+        #[cfg(unix)]
+        if let Listener::Unix(_, path, id) = self
+            && id.is_some()
+            && socket_file_id(path) == *id
+        {
+            let _ = std::fs::remove_file(path.as_str());
+        }
+    }
+}
+
+// Takes every complete record off the front of `buffer`, keeping a partial tail.
+fn drain_records(buffer: &mut Vec<u8>, format: StreamFormat, handler: &Handler) -> Result<()> {
+    let mut consumed = 0;
+    match format {
+        StreamFormat::Ndjson => {
+            while let Some(end) = buffer[consumed..].iter().position(|b| *b == b'\n') {
+                let line = &buffer[consumed..consumed + end];
+                consumed += end + 1;
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                // Invalid records are dropped like undecodable ZeroMQ messages.
+                for data in parse_batch(line).into_iter().flatten().flatten() {
+                    handler.read().unwrap()(data);
+                }
+            }
+        }
+        StreamFormat::Frames => {
+            while buffer.len() - consumed >= 8 {
+                let header: [u8; 8] = buffer[consumed..consumed + 8].try_into().unwrap();
+                let size = u64::from_le_bytes(header) as usize;
+                if size > MAX_FRAME_SIZE {
+                    return Err(anyhow!("frame too large: {size} bytes"));
+                }
+                if buffer.len() - consumed - 8 < size {
+                    break;
+                }
+                let data = &buffer[consumed + 8..consumed + 8 + size];
+                consumed += 8 + size;
+                if let Ok(data) = MessageData::from_bytes(data) {
+                    handler.read().unwrap()(data);
+                }
+            }
+        }
+        StreamFormat::Syslog => {
+            while let Some((record, size)) = next_record(&buffer[consumed..])? {
+                consumed += size;
+                if let Ok(data) = MessageData::from_syslog(record) {
+                    handler.read().unwrap()(data);
+                }
+            }
+        }
+    }
+    buffer.drain(..consumed);
+    Ok(())
+}
+
+fn serve_connection(
+    mut connection: Box<dyn Connection>,
+    format: StreamFormat,
+    handler: Handler,
+    closed: Arc<RwLock<bool>>,
+) {
+    if connection.set_read_timeout(Some(POLL_INTERVAL)).is_err() {
+        return;
+    }
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 64 * 1024];
+    while !*closed.read().unwrap() {
+        match connection.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => {
+                buffer.extend_from_slice(&chunk[..n]);
+                if drain_records(&mut buffer, format, &handler).is_err() {
+                    break;
+                }
+                if format != StreamFormat::Frames && buffer.len() > MAX_FRAME_SIZE {
+                    // A line this long is not a log record.
+                    break;
+                }
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(_) => break,
+        }
+    }
+    // A last record without a trailing newline.
+    if format != StreamFormat::Frames && !buffer.is_empty() {
+        buffer.push(b'\n');
+        let _ = drain_records(&mut buffer, format, &handler);
+    }
+}
+
+/// TCP or Unix socket counterpart of
+/// [`ServerHandler`](crate::zmq_support::ServerHandler) with the same lifecycle.
+pub struct StreamServerHandler {
+    address_: Arc<Mutex<String>>,
+    format_: Arc<Mutex<StreamFormat>>,
+    handler_: Handler,
+    closed_: Arc<RwLock<bool>>,
+    thread_: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl StreamServerHandler {
+    pub fn new<F>(address: &str, format: StreamFormat, handler: F) -> Self
+    where
+        F: 'static + Fn(MessageData) + Send + Sync,
+    {
+        Self {
+            address_: Arc::new(Mutex::new(address.to_string())),
+            format_: Arc::new(Mutex::new(format)),
+            handler_: Arc::new(RwLock::new(Box::new(handler))),
+            closed_: Arc::new(RwLock::new(true)),
+            thread_: Mutex::new(None),
+        }
+    }
+
+    /// Binds right away so a taken address is reported, then accepts on a
+    /// background thread until [`close`](Self::close).
+    pub fn run(&self) -> Result<()> {
+        if !self.is_closed() {
+            return Ok(());
+        }
+        let address = self.address();
+        let listener = Listener::bind(&address)?;
+        let format = self.format();
+        let handler = self.handler_.clone();
+        let closed = self.closed_.clone();
+        *closed.write().unwrap() = false;
+        let thread = thread::spawn(move || {
+            println!("Stream server listening on: {address} ({format:?})");
+            let mut connections: Vec<JoinHandle<()>> = Vec::new();
+            while !*closed.read().unwrap() {
+                connections.retain(|connection| !connection.is_finished());
+                match listener.accept() {
+                    Ok(connection) => {
+                        let handler = handler.clone();
+                        let closed = closed.clone();
+                        connections.push(thread::spawn(move || {
+                            serve_connection(connection, format, handler, closed)
+                        }));
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                    Err(e) => {
+                        eprintln!("Stream server on {address} failed to accept: {e}");
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                }
+            }
+            for connection in connections {
+                let _ = connection.join();
+            }
+        });
+        *self.thread_.lock().unwrap() = Some(thread);
+        Ok(())
+    }
+
+    /// Stops accepting and waits for the open connections to finish, so no
+    /// record is handled after this returns and the address can be bound again.
+    pub fn close(&self) {
+        *self.closed_.write().unwrap() = true;
+        if let Some(thread) = self.thread_.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.closed_.read().unwrap()
+    }
+
+    pub fn set_handler<F>(&self, handler: F)
+    where
+        F: 'static + Fn(MessageData) + Send + Sync,
+    {
+        *self.handler_.write().unwrap() = Box::new(handler);
+    }
+
+    pub fn address(&self) -> String {
+        self.address_.lock().unwrap().clone()
+    }
+
+    pub fn set_address(&self, address: &str) {
+        if self.is_closed() {
+            *self.address_.lock().unwrap() = address.to_string();
+        }
+    }
+
+    pub fn format(&self) -> StreamFormat {
+        *self.format_.lock().unwrap()
+    }
+
+    pub fn set_format(&self, format: StreamFormat) {
+        if self.is_closed() {
+            *self.format_.lock().unwrap() = format;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::encode_frame;
+    use crate::testing::{free_port, message, temp_path};
+
+    fn collect() -> (Handler, Arc<Mutex<Vec<MessageData>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let handler: Handler = Arc::new(RwLock::new(Box::new(move |data| {
+            sink.lock().unwrap().push(data)
+        })));
+        (handler, received)
+    }
+
+    #[test]
+    fn ndjson_keeps_partial_lines() {
+        let (handler, received) = collect();
+        let mut buffer = b"{\"messages\":\"a\"}\n\n{\"role\":1}\n{\"messages\":\"b".to_vec();
+        drain_records(&mut buffer, StreamFormat::Ndjson, &handler).unwrap();
+        assert_eq!(received.lock().unwrap().len(), 1);
+        buffer.extend_from_slice(b"\"}\n");
+        drain_records(&mut buffer, StreamFormat::Ndjson, &handler).unwrap();
+        assert!(buffer.is_empty());
+        assert_eq!(received.lock().unwrap()[1].messages, ["b"]);
+    }
+
+    #[test]
+    fn frames_wait_for_the_whole_body() {
+        let (handler, received) = collect();
+        let data = message("m");
+        let frame = encode_frame(&data.to_ffi().unwrap().encode().unwrap());
+        let mut buffer = frame[..frame.len() - 1].to_vec();
+        drain_records(&mut buffer, StreamFormat::Frames, &handler).unwrap();
+        assert!(received.lock().unwrap().is_empty());
+        buffer.push(*frame.last().unwrap());
+        drain_records(&mut buffer, StreamFormat::Frames, &handler).unwrap();
+        assert_eq!(received.lock().unwrap()[0].messages, ["m"]);
+
+        let mut oversized = (MAX_FRAME_SIZE as u64 + 1).to_le_bytes().to_vec();
+        assert!(drain_records(&mut oversized, StreamFormat::Frames, &handler).is_err());
+    }
+
+    #[test]
+    fn close_frees_the_address() {
+        let address = format!("tcp://127.0.0.1:{}", free_port());
+        let server = StreamServerHandler::new(&address, StreamFormat::Ndjson, |_| {});
+        server.run().unwrap();
+        server.close();
+        server.run().unwrap();
+        server.close();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stale_listener_keeps_the_new_socket_file() {
+        let path = temp_path("stream.sock");
+        let address = format!("unix://{}", path.display());
+        let old = Listener::bind(&address).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let new = Listener::bind(&address).unwrap();
+        drop(old);
+        assert!(path.exists());
+        drop(new);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bind_only_replaces_stale_sockets() {
+        let path = temp_path("replace.sock");
+        let address = format!("unix://{}", path.display());
+        // std's listener leaves its socket file behind when dropped.
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        let live = Listener::bind(&address).unwrap();
+        assert!(Listener::bind(&address).is_err());
+        drop(live);
+
+        std::fs::write(&path, "not a socket").unwrap();
+        assert!(Listener::bind(&address).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,430 @@
+//! Syslog receiver for devices and daemons that cannot link the C++ client.
+//! Addresses are `udp://host:port` or `tcp://host:port`.
+//!
+//! Both RFC 5424 and the older BSD format of RFC 3164 are understood. The
+//! hostname and app-name (or tag) form the `role`, the msgid becomes the
+//! `label`, the severity is mapped onto the levels in [`level`](crate::level)
+//! and a numeric procid fills `process_id`. Structured data is kept in
+//! `fields` as `sd-id.param-name`, next to the facility. TCP streams may use
+//! octet counting (RFC 6587) or newline-terminated records.
+use crate::ffi_wrapper::MessageData;
+use crate::frame::MAX_FRAME_SIZE;
+use crate::level::{DEBUG, ERROR, FATAL, INFO, WARN};
+use crate::stream_support::{StreamFormat, StreamServerHandler};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the receive loop checks whether the server was closed.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+type Handler = Arc<RwLock<Box<dyn Fn(MessageData) + Send + Sync>>>;
+
+fn severity_level(severity: u8) -> i32 {
+    match severity {
+        0..=2 => FATAL,
+        3 => ERROR,
+        4 => WARN,
+        5 | 6 => INFO,
+        _ => DEBUG,
+    }
+}
+
+fn split_priority(text: &str) -> Result<(u8, &str)> {
+    let invalid = || anyhow!("missing syslog priority");
+    let rest = text.strip_prefix('<').ok_or_else(invalid)?;
+    let end = rest
+        .find('>')
+        .filter(|end| (1..=3).contains(end))
+        .ok_or_else(invalid)?;
+    let priority = rest[..end]
+        .parse::<u8>()
+        .ok()
+        .filter(|p| *p <= 191)
+        .ok_or_else(invalid)?;
+    Ok((priority, &rest[end + 1..]))
+}
+
+fn next_token(text: &str) -> (&str, &str) {
+    text.split_once(' ').unwrap_or((text, ""))
+}
+
+// RFC 5424 writes absent values as a single dash.
+fn nil(value: &str) -> &str {
+    if value == "-" { "" } else { value }
+}
+
+#[derive(Default)]
+struct Header<'a> {
+    time: Option<usize>,
+    hostname: &'a str,
+    app_name: &'a str,
+    procid: &'a str,
+    msgid: &'a str,
+}
+
+/// Reads `[id name="value" ...]...` into `fields`, returning the message after it.
+fn parse_structured_data<'a>(
+    mut text: &'a str,
+    fields: &mut BTreeMap<String, String>,
+) -> Result<&'a str> {
+    if let Some(rest) = text.strip_prefix('-') {
+        return Ok(rest.strip_prefix(' ').unwrap_or(rest));
+    }
+    let invalid = || anyhow!("invalid structured data");
+    while let Some(rest) = text.strip_prefix('[') {
+        let end = rest.find([' ', ']']).ok_or_else(invalid)?;
+        let id = &rest[..end];
+        text = &rest[end..];
+        loop {
+            if let Some(rest) = text.strip_prefix(']') {
+                text = rest;
+                break;
+            }
+            let rest = text.strip_prefix(' ').ok_or_else(invalid)?;
+            let (name, rest) = rest.split_once("=\"").ok_or_else(invalid)?;
+            let mut value = String::new();
+            let mut chars = rest.char_indices();
+            let end = loop {
+                match chars.next().ok_or_else(invalid)? {
+                    (i, '"') => break i,
+                    (_, '\\') => match chars.next().ok_or_else(invalid)? {
+                        (_, c @ ('"' | '\\' | ']')) => value.push(c),
+                        (_, c) => {
+                            value.push('\\');
+                            value.push(c);
+                        }
+                    },
+                    (_, c) => value.push(c),
+                }
+            };
+            fields.insert(format!("{id}.{name}"), value);
+            text = &rest[end + 1..];
+        }
+    }
+    Ok(text.strip_prefix(' ').unwrap_or(text))
+}
+
+fn parse_rfc5424<'a>(
+    text: &'a str,
+    fields: &mut BTreeMap<String, String>,
+) -> Result<(Header<'a>, &'a str)> {
+    let (timestamp, rest) = next_token(text);
+    let (hostname, rest) = next_token(rest);
+    let (app_name, rest) = next_token(rest);
+    let (procid, rest) = next_token(rest);
+    let (msgid, rest) = next_token(rest);
+    let time = match nil(timestamp) {
+        "" => None,
+        timestamp => Some(
+            DateTime::parse_from_rfc3339(timestamp)
+                .map_err(|_| anyhow!("invalid syslog timestamp: {timestamp}"))?
+                .timestamp_micros()
+                .max(0) as usize,
+        ),
+    };
+    let message = parse_structured_data(rest, fields)?;
+    let header = Header {
+        time,
+        hostname: nil(hostname),
+        app_name: nil(app_name),
+        procid: nil(procid),
+        msgid: nil(msgid),
+    };
+    Ok((header, message.strip_prefix('\u{feff}').unwrap_or(message)))
+}
+
+// `Mmm dd hh:mm:ss` carries no year, the most recent one not in the future is used.
+fn parse_bsd_time(text: &str) -> Option<usize> {
+    let now = Local::now();
+    [now.year(), now.year() - 1].into_iter().find_map(|year| {
+        let naive =
+            NaiveDateTime::parse_from_str(&format!("{year} {text}"), "%Y %b %e %H:%M:%S").ok()?;
+        let time = Local.from_local_datetime(&naive).earliest()?;
+        (time <= now + chrono::Duration::days(1)).then(|| time.timestamp_micros().max(0) as usize)
+    })
+}
+
+fn parse_rfc3164(text: &str) -> (Header<'_>, &str) {
+    let mut header = Header::default();
+    let mut rest = text;
+    if let Some(time) = text.get(..15).and_then(parse_bsd_time) {
+        header.time = Some(time);
+        rest = text[15..].trim_start_matches(' ');
+        // Local senders often leave out the hostname and go straight to the tag.
+        let (hostname, after) = next_token(rest);
+        if !after.is_empty() && !hostname.ends_with(':') && !hostname.contains('[') {
+            header.hostname = hostname;
+            rest = after;
+        }
+    }
+    let tag_end = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "-_./".contains(c)))
+        .unwrap_or(rest.len());
+    if tag_end > 0 {
+        let (tag, after) = rest.split_at(tag_end);
+        let (procid, after) = match after.strip_prefix('[').and_then(|a| a.split_once(']')) {
+            Some((procid, after)) => (procid, after),
+            None => ("", after),
+        };
+        if let Some(message) = after.strip_prefix(':') {
+            header.app_name = tag;
+            header.procid = procid;
+            return (header, message.strip_prefix(' ').unwrap_or(message));
+        }
+    }
+    (header, rest)
+}
+
+impl MessageData {
+    /// Builds a message from one syslog record, see the module docs for the mapping.
+    pub fn from_syslog(record: &[u8]) -> Result<Self> {
+        let text = String::from_utf8_lossy(record);
+        let text = text.trim_end_matches(['\r', '\n', '\0']);
+        let (priority, rest) = split_priority(text)?;
+        let mut fields = BTreeMap::new();
+        let (version, body) = next_token(rest);
+        let (header, message) = if !version.is_empty()
+            && version.len() <= 2
+            && version.bytes().all(|b| b.is_ascii_digit())
+        {
+            parse_rfc5424(body, &mut fields)?
+        } else {
+            parse_rfc3164(rest)
+        };
+        fields.insert("facility".to_string(), (priority / 8).to_string());
+        let process_id = match header.procid.parse::<usize>() {
+            Ok(pid) => pid,
+            Err(_) => {
+                if !header.procid.is_empty() {
+                    fields.insert("procid".to_string(), header.procid.to_string());
+                }
+                0
+            }
+        };
+        let role = match (header.hostname, header.app_name) {
+            ("", app_name) => app_name.to_string(),
+            (hostname, "") => hostname.to_string(),
+            (hostname, app_name) => format!("{hostname}/{app_name}"),
+        };
+        Ok(MessageData {
+            role,
+            label: header.msgid.to_string(),
+            file: String::new(),
+            function: String::new(),
+            time: header
+                .time
+                .unwrap_or_else(|| Local::now().timestamp_micros().max(0) as usize),
+            process_id,
+            thread_id: 0,
+            line: 0,
+            level: severity_level(priority % 8),
+            messages: vec![message.to_string()],
+            fields,
+        })
+    }
+}
+
+/// Splits the next record off a TCP stream, returning it with the number of
+/// bytes it used, or `None` until the record is complete.
+pub(crate) fn next_record(buffer: &[u8]) -> Result<Option<(&[u8], usize)>> {
+    let digits = buffer.iter().take_while(|b| b.is_ascii_digit()).count();
+    if digits > 0 {
+        // Octet counting, `MSG-LEN SP SYSLOG-MSG`.
+        if digits > 10 {
+            return Err(anyhow!("invalid syslog frame length"));
+        }
+        if digits == buffer.len() {
+            return Ok(None);
+        }
+        if buffer[digits] == b' ' {
+            let size: usize = std::str::from_utf8(&buffer[..digits])?.parse()?;
+            if size > MAX_FRAME_SIZE {
+                return Err(anyhow!("syslog frame too large: {size} bytes"));
+            }
+            let end = digits + 1 + size;
+            return Ok((buffer.len() >= end).then(|| (&buffer[digits + 1..end], end)));
+        }
+    }
+    Ok(buffer
+        .iter()
+        .position(|b| *b == b'\n')
+        .map(|end| (&buffer[..end], end + 1)))
+}
+
+/// Syslog counterpart of [`ServerHandler`](crate::zmq_support::ServerHandler)
+/// with the same lifecycle. TCP connections are served by a
+/// [`StreamServerHandler`] in [`StreamFormat::Syslog`].
+pub struct SyslogServerHandler {
+    address_: Arc<Mutex<String>>,
+    handler_: Handler,
+    closed_: Arc<RwLock<bool>>,
+    stream_: Mutex<Option<StreamServerHandler>>,
+    thread_: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl SyslogServerHandler {
+    pub fn new<F>(address: &str, handler: F) -> Self
+    where
+        F: 'static + Fn(MessageData) + Send + Sync,
+    {
+        Self {
+            address_: Arc::new(Mutex::new(address.to_string())),
+            handler_: Arc::new(RwLock::new(Box::new(handler))),
+            closed_: Arc::new(RwLock::new(true)),
+            stream_: Mutex::new(None),
+            thread_: Mutex::new(None),
+        }
+    }
+
+    /// Binds right away so a taken address is reported, then receives on a
+    /// background thread until [`close`](Self::close).
+    pub fn run(&self) -> Result<()> {
+        if !self.is_closed() {
+            return Ok(());
+        }
+        let address = self.address();
+        if address.starts_with("tcp://") {
+            let handler = self.handler_.clone();
+            let stream = StreamServerHandler::new(&address, StreamFormat::Syslog, move |data| {
+                handler.read().unwrap()(data)
+            });
+            stream.run()?;
+            *self.stream_.lock().unwrap() = Some(stream);
+            *self.closed_.write().unwrap() = false;
+            return Ok(());
+        }
+        let host = address
+            .strip_prefix("udp://")
+            .ok_or_else(|| anyhow!("unsupported syslog address: {address}"))?;
+        let socket = UdpSocket::bind(host)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        let handler = self.handler_.clone();
+        let closed = self.closed_.clone();
+        *closed.write().unwrap() = false;
+        let thread = thread::spawn(move || {
+            println!("Syslog server listening on: {address}");
+            let mut buffer = vec![0u8; 64 * 1024];
+            while !*closed.read().unwrap() {
+                match socket.recv_from(&mut buffer) {
+                    // Records that are not syslog are dropped like undecodable ZeroMQ messages.
+                    Ok((size, _)) => {
+                        if let Ok(data) = MessageData::from_syslog(&buffer[..size]) {
+                            handler.read().unwrap()(data);
+                        }
+                    }
+                    Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        eprintln!("Syslog server on {address} failed to receive: {e}");
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                }
+            }
+        });
+        *self.thread_.lock().unwrap() = Some(thread);
+        Ok(())
+    }
+
+    /// Stops receiving and waits for the receive loop, so the address can be
+    /// bound again.
+    pub fn close(&self) {
+        *self.closed_.write().unwrap() = true;
+        if let Some(stream) = self.stream_.lock().unwrap().take() {
+            stream.close();
+        }
+        if let Some(thread) = self.thread_.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.closed_.read().unwrap()
+    }
+
+    pub fn set_handler<F>(&self, handler: F)
+    where
+        F: 'static + Fn(MessageData) + Send + Sync,
+    {
+        *self.handler_.write().unwrap() = Box::new(handler);
+    }
+
+    pub fn address(&self) -> String {
+        self.address_.lock().unwrap().clone()
+    }
+
+    pub fn set_address(&self, address: &str) {
+        if self.is_closed() {
+            *self.address_.lock().unwrap() = address.to_string();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc5424() {
+        let data = MessageData::from_syslog(
+            b"<165>1 2003-10-11T22:14:15.003Z board1 evntslog 42 ID47 \
+              [exampleSDID@32473 iut=\"3\" eventSource=\"App\\\"x\\]\"][meta seq=\"7\"] \xEF\xBB\xBFAn event\n",
+        )
+        .unwrap();
+        assert_eq!(data.role, "board1/evntslog");
+        assert_eq!(data.label, "ID47");
+        assert_eq!(data.level, INFO);
+        assert_eq!(data.process_id, 42);
+        assert_eq!(data.time, 1_065_910_455_003_000);
+        assert_eq!(data.messages, ["An event"]);
+        assert_eq!(data.fields["exampleSDID@32473.iut"], "3");
+        assert_eq!(data.fields["exampleSDID@32473.eventSource"], "App\"x]");
+        assert_eq!(data.fields["meta.seq"], "7");
+        assert_eq!(data.fields["facility"], "20");
+
+        let data = MessageData::from_syslog(b"<11>1 - - - worker-a - -").unwrap();
+        assert_eq!((data.role.as_str(), data.level), ("", ERROR));
+        assert_eq!(data.fields["procid"], "worker-a");
+        assert_eq!(data.messages, [""]);
+    }
+
+    #[test]
+    fn rfc3164() {
+        let data =
+            MessageData::from_syslog(b"<34>Oct  1 22:14:15 mymachine su[230]: 'su root' failed")
+                .unwrap();
+        assert_eq!(data.role, "mymachine/su");
+        assert_eq!(data.level, FATAL);
+        assert_eq!(data.process_id, 230);
+        assert_eq!(data.messages, ["'su root' failed"]);
+        assert!(data.time > 0);
+
+        let data = MessageData::from_syslog(b"<12>Oct 11 22:14:15 cron: started").unwrap();
+        assert_eq!((data.role.as_str(), data.level), ("cron", WARN));
+        assert_eq!(data.messages, ["started"]);
+
+        let data = MessageData::from_syslog(b"<15>plain text").unwrap();
+        assert_eq!((data.role.as_str(), data.level), ("", DEBUG));
+        assert_eq!(data.messages, ["plain text"]);
+
+        assert!(MessageData::from_syslog(b"no priority").is_err());
+        assert!(MessageData::from_syslog(b"<200>too high").is_err());
+    }
+
+    #[test]
+    fn tcp_framing() {
+        let buffer = b"11 <13>1 - - -<13>line\n12 <13";
+        let (record, used) = next_record(buffer).unwrap().unwrap();
+        assert_eq!((record, used), (&b"<13>1 - - -"[..], 14));
+        let (record, used) = next_record(&buffer[14..]).unwrap().unwrap();
+        assert_eq!((record, used), (&b"<13>line"[..], 9));
+        assert!(next_record(&buffer[23..]).unwrap().is_none());
+        assert!(next_record(b"123").unwrap().is_none());
+        assert!(next_record(b"99999999999 x").is_err());
+    }
+}
@@ -0,0 +1,44 @@
+//! Fixtures shared by the unit tests.
+use crate::MessageData;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_PATH: AtomicUsize = AtomicUsize::new(0);
+
+/// A message with fixed metadata and `text` as its only line.
+pub fn message(text: &str) -> MessageData {
+    MessageData {
+        role: "r".to_string(),
+        label: "l".to_string(),
+        file: "f".to_string(),
+        function: "main".to_string(),
+        time: 1,
+        process_id: 2,
+        thread_id: 3,
+        line: 4,
+        level: 2,
+        messages: vec![text.to_string()],
+        fields: Default::default(),
+    }
+}
+
+/// A loopback port the OS just handed out, for tests that bind an address
+/// twice and so cannot ask for port 0 each time.
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// A path in the temp directory that no other test or test run uses.
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "xclogger-{}-{}-{}",
+        std::process::id(),
+        NEXT_PATH.fetch_add(1, Ordering::Relaxed),
+        name
+    ))
+}
@@ -0,0 +1,338 @@
+//! Fire-and-forget UDP ingest for hot loops that cannot wait for a REP reply.
+//! Addresses are `udp://host:port`.
+//!
+//! A datagram carries one or more length-prefixed XCLOG frames (see
+//! [`frame`](crate::frame)). It may start with [`SEQUENCE_MAGIC`] and a
+//! little-endian `u64` counter that the sender increments per datagram; the
+//! server then counts the gaps as dropped datagrams. The magic read as a frame
+//! size is far above [`MAX_FRAME_SIZE`](crate::frame::MAX_FRAME_SIZE), so the
+//! two layouts cannot be confused.
+use crate::ffi_wrapper::MessageData;
+use crate::frame::{encode_frame, split_frames};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Marks a datagram that starts with a sequence counter.
+pub const SEQUENCE_MAGIC: [u8; 4] = *b"XCSQ";
+
+/// Largest payload a UDP datagram can carry over IPv4.
+pub const MAX_DATAGRAM_SIZE: usize = 65507;
+
+/// A sequence jump larger than this is treated as a sender restart rather
+/// than as dropped datagrams.
+pub const SEQUENCE_WINDOW: u64 = 1 << 20;
+
+/// Senders whose sequence is tracked at once; the least recently seen one is
+/// forgotten when a new sender arrives.
+pub const MAX_TRACKED_SENDERS: usize = 4096;
+
+/// How often the receive loop checks whether the server was closed.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+type Handler = Arc<RwLock<Box<dyn Fn(MessageData) + Send + Sync>>>;
+
+/// Counters since the server was created.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct UdpStats {
+    pub datagrams: u64,
+    pub messages: u64,
+    /// Datagrams or frames that could not be decoded.
+    pub malformed: u64,
+    /// Datagrams missing from a sender's sequence.
+    pub dropped: u64,
+    /// Datagrams that arrived after a later one from the same sender.
+    pub late: u64,
+    /// Senders that included a sequence counter.
+    pub senders: u64,
+}
+
+#[derive(Default)]
+struct UdpState {
+    stats: UdpStats,
+    // Next expected sequence number and last use per sender.
+    expected: HashMap<SocketAddr, (u64, u64)>,
+    tick: u64,
+}
+
+impl UdpState {
+    fn track(&mut self, sender: SocketAddr, sequence: u64) {
+        match self.expected.get(&sender).map(|&(expected, _)| expected) {
+            None => {
+                self.stats.senders += 1;
+                self.evict();
+            }
+            // Counting again from zero, or jumping far in either direction,
+            // means the sender restarted.
+            Some(_) if sequence == 0 => {}
+            Some(expected) if sequence.abs_diff(expected) > SEQUENCE_WINDOW => {}
+            Some(expected) if sequence < expected => {
+                self.stats.late += 1;
+                return;
+            }
+            Some(expected) => {
+                self.stats.dropped = self.stats.dropped.saturating_add(sequence - expected)
+            }
+        }
+        self.tick += 1;
+        self.expected
+            .insert(sender, (sequence.wrapping_add(1), self.tick));
+    }
+
+    fn evict(&mut self) {
+        if self.expected.len() < MAX_TRACKED_SENDERS {
+            return;
+        }
+        let oldest = self
+            .expected
+            .iter()
+            .min_by_key(|(_, (_, used))| *used)
+            .map(|(sender, _)| *sender);
+        if let Some(oldest) = oldest {
+            self.expected.remove(&oldest);
+        }
+    }
+}
+
+/// Builds a datagram from encoded messages, optionally with a sequence counter.
+pub fn encode_datagram(sequence: Option<u64>, messages: &[&[u8]]) -> Vec<u8> {
+    let mut datagram = Vec::new();
+    if let Some(sequence) = sequence {
+        datagram.extend_from_slice(&SEQUENCE_MAGIC);
+        datagram.extend_from_slice(&sequence.to_le_bytes());
+    }
+    for message in messages {
+        datagram.extend_from_slice(&encode_frame(message));
+    }
+    datagram
+}
+
+fn split_sequence(datagram: &[u8]) -> Result<(Option<u64>, &[u8])> {
+    match datagram.strip_prefix(&SEQUENCE_MAGIC) {
+        Some(rest) if rest.len() >= 8 => {
+            let sequence = u64::from_le_bytes(rest[..8].try_into().unwrap());
+            Ok((Some(sequence), &rest[8..]))
+        }
+        Some(_) => Err(anyhow!("truncated sequence header")),
+        None => Ok((None, datagram)),
+    }
+}
+
+fn handle_datagram(
+    datagram: &[u8],
+    sender: SocketAddr,
+    state: &Mutex<UdpState>,
+    handler: &Handler,
+) {
+    let mut decoded = Vec::new();
+    let mut malformed = 0;
+    let sequence = match split_sequence(datagram) {
+        Ok((sequence, body)) => match split_frames(body) {
+            Ok(frames) => {
+                for frame in frames {
+                    match MessageData::from_bytes(frame) {
+                        Ok(data) => decoded.push(data),
+                        Err(_) => malformed += 1,
+                    }
+                }
+                sequence
+            }
+            Err(_) => {
+                malformed += 1;
+                sequence
+            }
+        },
+        Err(_) => {
+            malformed += 1;
+            None
+        }
+    };
+    {
+        let mut state = state.lock().unwrap();
+        state.stats.datagrams += 1;
+        state.stats.messages += decoded.len() as u64;
+        state.stats.malformed += malformed;
+        if let Some(sequence) = sequence {
+            state.track(sender, sequence);
+        }
+    }
+    let handler = handler.read().unwrap();
+    for data in decoded {
+        handler(data);
+    }
+}
+
+/// UDP counterpart of [`ServerHandler`](crate::zmq_support::ServerHandler)
+/// with the same lifecycle.
+pub struct UdpServerHandler {
+    address_: Arc<Mutex<String>>,
+    handler_: Handler,
+    closed_: Arc<RwLock<bool>>,
+    state_: Arc<Mutex<UdpState>>,
+    thread_: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl UdpServerHandler {
+    pub fn new<F>(address: &str, handler: F) -> Self
+    where
+        F: 'static + Fn(MessageData) + Send + Sync,
+    {
+        Self {
+            address_: Arc::new(Mutex::new(address.to_string())),
+            handler_: Arc::new(RwLock::new(Box::new(handler))),
+            closed_: Arc::new(RwLock::new(true)),
+            state_: Arc::new(Mutex::new(UdpState::default())),
+            thread_: Mutex::new(None),
+        }
+    }
+
+    /// Binds right away so a taken address is reported, then receives on a
+    /// background thread until [`close`](Self::close).
+    pub fn run(&self) -> Result<()> {
+        if !self.is_closed() {
+            return Ok(());
+        }
+        let address = self.address();
+        let host = address
+            .strip_prefix("udp://")
+            .ok_or_else(|| anyhow!("unsupported UDP address: {address}"))?;
+        let socket = UdpSocket::bind(host)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        let handler = self.handler_.clone();
+        let closed = self.closed_.clone();
+        let state = self.state_.clone();
+        *closed.write().unwrap() = false;
+        let thread = thread::spawn(move || {
+            println!("UDP server listening on: {address}");
+            let mut buffer = vec![0u8; MAX_DATAGRAM_SIZE];
+            while !*closed.read().unwrap() {
+                match socket.recv_from(&mut buffer) {
+                    Ok((size, sender)) => {
+                        handle_datagram(&buffer[..size], sender, &state, &handler)
+                    }
+                    Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        eprintln!("UDP server on {address} failed to receive: {e}");
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                }
+            }
+        });
+        *self.thread_.lock().unwrap() = Some(thread);
+        Ok(())
+    }
+
+    /// Stops the receive loop and waits for it, so the address can be bound again.
+    pub fn close(&self) {
+        *self.closed_.write().unwrap() = true;
+        if let Some(thread) = self.thread_.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.closed_.read().unwrap()
+    }
+
+    pub fn set_handler<F>(&self, handler: F)
+    where
+        F: 'static + Fn(MessageData) + Send + Sync,
+    {
+        *self.handler_.write().unwrap() = Box::new(handler);
+    }
+
+    pub fn address(&self) -> String {
+        self.address_.lock().unwrap().clone()
+    }
+
+    pub fn set_address(&self, address: &str) {
+        if self.is_closed() {
+            *self.address_.lock().unwrap() = address.to_string();
+        }
+    }
+
+    pub fn stats(&self) -> UdpStats {
+        self.state_.lock().unwrap().stats.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::MAX_FRAME_SIZE;
+
+    fn message(text: &str) -> Vec<u8> {
+        crate::testing::message(text)
+            .to_ffi()
+            .unwrap()
+            .encode()
+            .unwrap()
+    }
+
+    #[test]
+    fn magic_is_not_a_frame_size() {
+        let size = u64::from_le_bytes([b'X', b'C', b'S', b'Q', 0, 0, 0, 0]);
+        assert!(size as usize > MAX_FRAME_SIZE);
+    }
+
+    #[test]
+    fn datagrams_and_sequence_gaps() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let handler: Handler = Arc::new(RwLock::new(Box::new(move |data: MessageData| {
+            sink.lock().unwrap().push(data.messages[0].clone())
+        })));
+        let state = Mutex::new(UdpState::default());
+        let a: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let b: SocketAddr = "127.0.0.1:1001".parse().unwrap();
+        let (one, two) = (message("one"), message("two"));
+
+        handle_datagram(&encode_datagram(None, &[&one, &two]), a, &state, &handler);
+        handle_datagram(&encode_datagram(Some(0), &[&one]), a, &state, &handler);
+        handle_datagram(&encode_datagram(Some(3), &[&two]), a, &state, &handler);
+        handle_datagram(&encode_datagram(Some(1), &[&one]), a, &state, &handler);
+        handle_datagram(&encode_datagram(Some(7), &[&one]), b, &state, &handler);
+        handle_datagram(&encode_datagram(Some(0), &[&one]), a, &state, &handler);
+        handle_datagram(b"XCSQ\x01", a, &state, &handler);
+        handle_datagram(&one, a, &state, &handler);
+
+        assert_eq!(received.lock().unwrap()[..3], ["one", "two", "one"]);
+        let stats = state.lock().unwrap().stats.clone();
+        assert_eq!(
+            stats,
+            UdpStats {
+                datagrams: 8,
+                messages: 7,
+                malformed: 2,
+                dropped: 2,
+                late: 1,
+                senders: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn restarts_and_sender_limit() {
+        let mut state = UdpState::default();
+        let a: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+
+        state.track(a, 5);
+        state.track(a, u64::MAX / 2);
+        state.track(a, 10);
+        state.track(a, 10 + SEQUENCE_WINDOW);
+        assert_eq!(state.stats.dropped, SEQUENCE_WINDOW - 1);
+        assert_eq!(state.stats.late, 0);
+
+        for port in 0..MAX_TRACKED_SENDERS as u16 + 10 {
+            state.track(SocketAddr::from(([127, 0, 0, 2], port)), 1);
+        }
+        assert_eq!(state.expected.len(), MAX_TRACKED_SENDERS);
+        assert!(!state.expected.contains_key(&a));
+    }
+}
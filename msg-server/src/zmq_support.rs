@@ -1,41 +1,63 @@
 // use ffi_wrapper::MessageData;
 use crate::ffi_wrapper::MessageData;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex, RwLock};
-use std::thread;
 use std::thread::sleep;
+use std::thread::{self, JoinHandle};
 use zmq::{Context, SocketType};
 
+/// REP replies to every message with its payload, PULL receives without replying
+/// so producers can use fire-and-forget PUSH sockets.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SocketMode {
+    #[default]
+    Rep,
+    Pull,
+}
+
+type Handler = Arc<RwLock<Box<dyn Fn(MessageData) + Send + Sync>>>;
+
 pub struct ServerHandler {
     address_: Arc<Mutex<String>>,
-    handler_: Arc<RwLock<Box<dyn Fn(MessageData) -> () + Send + Sync>>>,
+    mode_: Arc<Mutex<SocketMode>>,
+    handler_: Handler,
     closed_: Arc<RwLock<bool>>,
+    thread_: Mutex<Option<JoinHandle<()>>>,
 }
 impl ServerHandler {
     pub fn new<F>(address: &str, handler: F) -> Self
     where
-        F: 'static + Fn(MessageData) -> () + Send + Sync,
+        F: 'static + Fn(MessageData) + Send + Sync,
     {
         Self {
             address_: Arc::new(Mutex::new(address.to_string())),
+            mode_: Arc::new(Mutex::new(SocketMode::default())),
             handler_: Arc::new(RwLock::new(Box::new(handler))),
             closed_: Arc::<RwLock<bool>>::new(true.into()),
+            thread_: Mutex::new(None),
         }
     }
-    pub fn run(&self) -> () {
-        *self.closed_.as_ref().write().unwrap() = false;
+    /// Binds right away so a taken address is reported, then receives on a
+    /// background thread until [`close`](Self::close).
+    pub fn run(&self) -> Result<()> {
         // Create communication context (like TCP connection pool)
         let addr = self.address_.lock().unwrap().clone();
+        let mode = *self.mode_.lock().unwrap();
+        let ctx = Context::new();
+        let socket_type = match mode {
+            SocketMode::Rep => SocketType::REP,
+            SocketMode::Pull => SocketType::PULL,
+        };
+        let rep = ctx.socket(socket_type)?;
+        rep.bind(addr.as_str())?;
+        *self.closed_.as_ref().write().unwrap() = false;
         let closed = self.closed_.clone();
         let handler = self.handler_.clone();
-        thread::spawn(move || {
-            let ctx = Context::new();
-            let rep = ctx
-                .socket(SocketType::REP)
-                .expect("Failed to create socket");
-            rep.bind(addr.as_str()).expect("Failed to bind socket");
-            if *closed.as_ref().read().unwrap() {
-                return;
-            }
+        let thread = thread::spawn(move || {
+            // The context has to outlive the socket.
+            let _ctx = ctx;
             println!("Server listening on: {}", addr);
             while !*closed.as_ref().read().unwrap() {
                 match rep.recv_bytes(zmq::DONTWAIT) {
@@ -43,7 +65,9 @@ impl ServerHandler {
                         if let Ok(decoded_msg) = MessageData::from_bytes(&data) {
                             handler.as_ref().read().unwrap().as_ref()(decoded_msg);
                         }
-                        rep.send(data, 0).expect("Failed to send message back");
+                        if mode == SocketMode::Rep {
+                            rep.send(data, 0).expect("Failed to send message back");
+                        }
                     }
                     Err(_) => {
                         continue;
@@ -52,17 +76,23 @@ impl ServerHandler {
                 sleep(std::time::Duration::from_millis(0));
             }
         });
-        // Send messages (0 means non-blocking mode)
+        *self.thread_.lock().unwrap() = Some(thread);
+        Ok(())
     }
+    /// Stops the receive loop and waits for it, so no message is handled after
+    /// this returns and the address can be bound again.
     pub fn close(&self) {
         *self.closed_.as_ref().write().unwrap() = true;
+        if let Some(thread) = self.thread_.lock().unwrap().take() {
+            let _ = thread.join();
+        }
     }
     pub fn is_closed(&self) -> bool {
         *self.closed_.as_ref().read().unwrap()
     }
     pub fn set_handler<F>(self, handler: F)
     where
-        F: 'static + Fn(MessageData) -> () + Send + Sync,
+        F: 'static + Fn(MessageData) + Send + Sync,
     {
         *self.handler_.as_ref().write().unwrap() = Box::new(handler);
     }
@@ -74,4 +104,32 @@ impl ServerHandler {
             *self.address_.lock().unwrap() = address.to_string();
         }
     }
+    pub fn mode(&self) -> SocketMode {
+        *self.mode_.lock().unwrap()
+    }
+    pub fn set_mode(&self, mode: SocketMode) {
+        if *self.closed_.as_ref().read().unwrap() {
+            *self.mode_.lock().unwrap() = mode;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::free_port;
+
+    #[test]
+    fn bind_errors_are_returned() {
+        let address = format!("tcp://127.0.0.1:{}", free_port());
+        let first = ServerHandler::new(&address, |_| {});
+        first.run().unwrap();
+        let second = ServerHandler::new(&address, |_| {});
+        assert!(second.run().is_err());
+        assert!(second.is_closed());
+        // close() waits for the receive loop, so the address is free again.
+        first.close();
+        second.run().unwrap();
+        second.close();
+    }
 }
@@ -0,0 +1,332 @@
+use clap::Parser;
+use msg_server::stream_support::{StreamFormat, StreamServerHandler};
+use msg_server::syslog_support::SyslogServerHandler;
+use msg_server::udp_support::UdpServerHandler;
+use msg_server::zmq_support::{ServerHandler, SocketMode};
+use msg_server::MessageData;
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+use xclogger_server_lib::db::DB;
+use xclogger_server_lib::http::HttpServer;
+use xclogger_server_lib::otlp;
+use xclogger_server_lib::pipeline::MessagePipeline;
+use xclogger_server_lib::settings::{
+    apply_retention, default_db_path, validate_address, validate_stream_address,
+    validate_syslog_address, validate_udp_address, Retention, Settings, StreamListener,
+};
+use xclogger_server_lib::tail::{FileTailer, TailSource};
+use xclogger_server_lib::template::LineTemplate;
+
+// How often retention limits are enforced while running.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Headless xclogger ingest server.
+///
+/// Values are taken from the flags first, then the config file, then the
+/// settings stored in the database.
+#[derive(Parser)]
+#[command(name = "xclogger-daemon", version)]
+struct Args {
+    /// TOML config file with `db`, `address`, `socket_mode`, `http_address`,
+    /// `http_token`, `listeners`, `udp_address`, `syslog_listeners`, `otlp_address`,
+    /// `tail_sources` and `retention`
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// SQLite database, defaults to the desktop app's default project
+    #[arg(long)]
+    db: Option<PathBuf>,
+    /// Address to bind, e.g. tcp://0.0.0.0:5555
+    #[arg(short, long)]
+    address: Option<String>,
+    /// `rep` or `pull`
+    #[arg(long, value_parser = parse_socket_mode)]
+    socket_mode: Option<SocketMode>,
+    /// Serve the HTTP API on this address, e.g. 127.0.0.1:7878
+    #[arg(long)]
+    http: Option<String>,
+    /// Bearer token for the HTTP API, required for addresses other than loopback
+    #[arg(long)]
+    http_token: Option<String>,
+    /// Accept newline-delimited JSON on tcp://host:port or unix:///path, repeatable
+    #[arg(long)]
+    ndjson: Vec<String>,
+    /// Accept length-prefixed XCLOG frames on tcp://host:port or unix:///path, repeatable
+    #[arg(long)]
+    frames: Vec<String>,
+    /// Accept XCLOG frames in UDP datagrams on udp://host:port
+    #[arg(long)]
+    udp: Option<String>,
+    /// Receive syslog on udp://host:port or tcp://host:port, repeatable
+    #[arg(long)]
+    syslog: Vec<String>,
+    /// Receive OpenTelemetry logs over gRPC and HTTP on this address, e.g. 0.0.0.0:4317
+    #[arg(long)]
+    otlp: Option<String>,
+    /// Follow a log file and ingest each new line as is, repeatable. Use
+    /// `tail_sources` in the config file for a template
+    #[arg(long)]
+    tail: Vec<PathBuf>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct DaemonConfig {
+    db: Option<PathBuf>,
+    address: Option<String>,
+    socket_mode: Option<SocketMode>,
+    http_address: Option<String>,
+    http_token: Option<String>,
+    listeners: Option<Vec<StreamListener>>,
+    udp_address: Option<String>,
+    syslog_listeners: Option<Vec<String>>,
+    otlp_address: Option<String>,
+    tail_sources: Option<Vec<TailSource>>,
+    retention: Option<Retention>,
+}
+
+fn parse_socket_mode(value: &str) -> Result<SocketMode, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_ascii_lowercase()))
+        .map_err(|_| format!("unknown socket mode: {}", value))
+}
+
+// Whole lines as messages, with the file name as role.
+fn plain_tail_source(path: &Path) -> TailSource {
+    TailSource {
+        path: path.to_string_lossy().to_string(),
+        template: LineTemplate {
+            pattern: String::new(),
+            format: Some("{message}".to_string()),
+            time_format: None,
+            role: path.file_name().map(|n| n.to_string_lossy().to_string()),
+            label: None,
+            level: None,
+        },
+        from_start: false,
+    }
+}
+
+fn load_config(path: &PathBuf) -> Result<DaemonConfig, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    toml::from_str(&text).map_err(|e| format!("invalid config {}: {}", path.display(), e))
+}
+
+fn run() -> Result<(), String> {
+    let args = Args::parse();
+    let config = match &args.config {
+        Some(path) => load_config(path)?,
+        None => DaemonConfig::default(),
+    };
+    let db_path = args
+        .db
+        .or(config.db)
+        .or_else(default_db_path)
+        .ok_or("cannot determine the database path, pass --db")?;
+
+    let db = Arc::new(Mutex::new(Option::<Connection>::None));
+    db.connect(&db_path)?;
+    let settings = Settings::load(&db)?;
+    let address = args
+        .address
+        .or(config.address)
+        .unwrap_or(settings.address.clone());
+    validate_address(&address)?;
+    let socket_mode = args
+        .socket_mode
+        .or(config.socket_mode)
+        .unwrap_or(settings.socket_mode);
+    let listeners: Vec<StreamListener> = {
+        let flags: Vec<_> = (args.ndjson.iter().map(|a| (a, StreamFormat::Ndjson)))
+            .chain(args.frames.iter().map(|a| (a, StreamFormat::Frames)))
+            .map(|(address, format)| StreamListener {
+                address: address.clone(),
+                format,
+            })
+            .collect();
+        match flags.is_empty() {
+            false => flags,
+            true => config
+                .listeners
+                .unwrap_or(settings.stream_listeners.clone()),
+        }
+    };
+    for listener in &listeners {
+        validate_stream_address(&listener.address)?;
+    }
+    let udp_address = args
+        .udp
+        .or(config.udp_address)
+        .or(settings.udp_address.clone());
+    if let Some(address) = &udp_address {
+        validate_udp_address(address)?;
+    }
+    let syslog_listeners = match args.syslog.is_empty() {
+        false => args.syslog.clone(),
+        true => config
+            .syslog_listeners
+            .unwrap_or(settings.syslog_listeners.clone()),
+    };
+    for address in &syslog_listeners {
+        validate_syslog_address(address)?;
+    }
+    let tail_sources = match args.tail.is_empty() {
+        false => args
+            .tail
+            .iter()
+            .map(|path| plain_tail_source(path))
+            .collect(),
+        true => config.tail_sources.unwrap_or(settings.tail_sources.clone()),
+    };
+    for source in &tail_sources {
+        source.validate()?;
+    }
+    let retention = config.retention.unwrap_or(settings.retention.clone());
+    apply_retention(&db, &retention)?;
+
+    let pipeline = Arc::new(MessagePipeline::new(db.clone()));
+    pipeline.reload_alerts()?;
+    pipeline.load_rules();
+    let http_token = args
+        .http_token
+        .or(config.http_token)
+        .or(settings.http_token.clone());
+    let http = match args.http.or(config.http_address).or(settings.http_address) {
+        Some(address) => Some(HttpServer::start(
+            &address,
+            http_token.as_deref(),
+            pipeline.clone(),
+        )?),
+        None => None,
+    };
+    let otlp = match args.otlp.or(config.otlp_address).or(settings.otlp_address) {
+        Some(address) => Some(otlp::start(&address, pipeline.clone())?),
+        None => None,
+    };
+    let received = Arc::new(AtomicUsize::new(0));
+    let ingest = {
+        let pipeline = pipeline.clone();
+        let received = received.clone();
+        Arc::new(move |data: MessageData, source: Option<&str>| {
+            match pipeline.ingest_into(&data, None, source) {
+                Ok(_) => {
+                    received.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => eprintln!("failed to ingest message: {}", e),
+            }
+        })
+    };
+    let server = {
+        let ingest = ingest.clone();
+        ServerHandler::new(&address, move |data| ingest(data, None))
+    };
+    server.set_mode(socket_mode);
+    server
+        .run()
+        .map_err(|e| format!("failed to listen on {}: {}", address, e))?;
+    let streams = listeners
+        .iter()
+        .map(|listener| {
+            let ingest = ingest.clone();
+            let stream =
+                StreamServerHandler::new(&listener.address, listener.format, move |data| {
+                    ingest(data, None)
+                });
+            stream
+                .run()
+                .map(|_| stream)
+                .map_err(|e| format!("failed to listen on {}: {}", listener.address, e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let udp = match &udp_address {
+        Some(address) => {
+            let ingest = ingest.clone();
+            let udp = UdpServerHandler::new(address, move |data| ingest(data, None));
+            udp.run()
+                .map_err(|e| format!("failed to listen on {}: {}", address, e))?;
+            Some(udp)
+        }
+        None => None,
+    };
+    let syslogs = syslog_listeners
+        .iter()
+        .map(|address| {
+            let ingest = ingest.clone();
+            let syslog = SyslogServerHandler::new(address, move |data| ingest(data, None));
+            syslog
+                .run()
+                .map(|_| syslog)
+                .map_err(|e| format!("failed to listen on {}: {}", address, e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let tailers = tail_sources
+        .into_iter()
+        .map(|source| {
+            let ingest = ingest.clone();
+            let path = source.path.clone();
+            let tailer = FileTailer::new(source, db.clone(), move |data| ingest(data, Some(&path)));
+            tailer.run().map(|_| tailer)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    println!(
+        "xclogger-daemon: {:?} on {}, storing to {}",
+        socket_mode,
+        address,
+        db_path.display()
+    );
+    if let Some(http) = &http {
+        println!("xclogger-daemon: HTTP API on http://{}", http.address());
+    }
+    if let Some(otlp) = &otlp {
+        println!("xclogger-daemon: OTLP logs on {}", otlp.address());
+    }
+
+    // SIGINT / SIGTERM end the wait below, the receive loop is then closed cleanly.
+    let (stop_tx, stop_rx) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = stop_tx.send(());
+    })
+    .map_err(|e| format!("failed to install signal handler: {}", e))?;
+    while let Err(mpsc::RecvTimeoutError::Timeout) = stop_rx.recv_timeout(RETENTION_INTERVAL) {
+        if let Err(e) = apply_retention(&db, &retention) {
+            eprintln!("failed to apply retention: {}", e);
+        }
+    }
+    server.close();
+    for stream in &streams {
+        stream.close();
+    }
+    if let Some(udp) = &udp {
+        udp.close();
+    }
+    for syslog in &syslogs {
+        syslog.close();
+    }
+    for tailer in &tailers {
+        tailer.close();
+    }
+    drop(http);
+    drop(otlp);
+    println!(
+        "xclogger-daemon: stopped, {} messages received",
+        received.load(Ordering::Relaxed)
+    );
+    if let Some(udp) = &udp {
+        let stats = udp.stats();
+        println!(
+            "xclogger-daemon: UDP {} datagrams, {} dropped, {} late, {} malformed",
+            stats.datagrams, stats.dropped, stats.late, stats.malformed
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("xclogger-daemon: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,462 @@
+use chrono::{Local, TimeZone};
+use clap::{Parser, Subcommand, ValueEnum};
+use rusqlite::Connection;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use xclogger_server_lib::db::{
+    AggregateDB, DBMessage, DistinctQuery, ExportDB, ExportFormat, FieldCount, FilterConfig,
+    MessageDB, MessageField, NumberRange, Project, ProjectDB, DB,
+};
+use xclogger_server_lib::query::{parse_field, parse_filter};
+use xclogger_server_lib::rules::{ChipStyle, ChipStyleKind, RoleRuleSet, RuleMatcher, StyleId};
+use xclogger_server_lib::settings::{default_db_path, default_registry_path, Settings};
+
+// How often `tail -f` checks for new messages.
+const TAIL_INTERVAL: Duration = Duration::from_millis(500);
+const TAIL_BATCH: i32 = 1000;
+
+/// Query the xclogger message database.
+///
+/// FILTER is a list of conditions and words, e.g.
+/// `level>=warn role=net label~conn time>=-15m "connection reset"`.
+///
+/// role, label, file, function (func) and msg take `=` (equals), `~` (contains),
+/// `^` (starts with) or `$` (ends with). id, time, process_id (pid),
+/// thread_id (tid), line and level take `=`, `>`, `>=`, `<`, `<=` or
+/// `=min..max`; several conditions on one field are combined. Levels may be
+/// names such as `warn`. Times are microseconds, RFC 3339, local
+/// `YYYY-MM-DD[ HH:MM:SS]` or relative to now (`-30s`, `-15m`, `-2h`, `-1d`).
+/// Other words are searched for in the message text; use double quotes to keep
+/// spaces.
+#[derive(Parser)]
+#[command(name = "xclogger", version)]
+struct Args {
+    /// SQLite database, defaults to the desktop app's default project
+    #[arg(long, global = true)]
+    db: Option<PathBuf>,
+    /// Open the database of a project created in the desktop app
+    #[arg(long, global = true, conflicts_with = "db")]
+    project: Option<String>,
+    /// Disable colors, also disabled when NO_COLOR is set or stdout is not a terminal
+    #[arg(long, global = true)]
+    no_color: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print matching messages
+    Query {
+        filter: Vec<String>,
+        #[arg(long, default_value = "id", value_parser = parse_field_arg)]
+        order_by: MessageField,
+        #[arg(long)]
+        desc: bool,
+        #[arg(short = 'n', long)]
+        limit: Option<i32>,
+        #[arg(long, default_value_t = 0)]
+        offset: i32,
+        /// Print one JSON object per line
+        #[arg(long)]
+        json: bool,
+    },
+    /// Count matching messages
+    Count {
+        filter: Vec<String>,
+        /// Count per value of a field instead
+        #[arg(long, value_parser = parse_field_arg)]
+        by: Option<MessageField>,
+    },
+    /// List the values of a field with their counts
+    Distinct {
+        #[arg(value_parser = parse_field_arg)]
+        field: MessageField,
+        filter: Vec<String>,
+        #[arg(long)]
+        prefix: Option<String>,
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        /// Most frequent first instead of ordered by value
+        #[arg(long)]
+        by_frequency: bool,
+    },
+    /// Delete matching messages
+    Delete {
+        filter: Vec<String>,
+        /// Required to actually delete, otherwise only the count is shown
+        #[arg(long)]
+        yes: bool,
+        /// Allow an empty filter, which deletes every message
+        #[arg(long)]
+        all: bool,
+    },
+    /// Write matching messages to a file, `-` for stdout
+    Export {
+        path: PathBuf,
+        filter: Vec<String>,
+        /// Defaults to the file extension, or text
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+    /// Print the last messages and optionally keep following new ones
+    Tail {
+        filter: Vec<String>,
+        #[arg(short = 'n', long, default_value_t = 10)]
+        lines: i32,
+        #[arg(short, long)]
+        follow: bool,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Jsonl,
+    Csv,
+    Text,
+}
+
+impl From<Format> for ExportFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Jsonl => ExportFormat::Jsonl,
+            Format::Csv => ExportFormat::Csv,
+            Format::Text => ExportFormat::Text,
+        }
+    }
+}
+
+fn parse_field_arg(value: &str) -> Result<MessageField, String> {
+    parse_field(value).ok_or_else(|| format!("unknown field: {}", value))
+}
+
+fn filter(words: &[String]) -> Result<FilterConfig, String> {
+    parse_filter(&words.join(" "))
+}
+
+/// Turns rule set styles into ANSI escapes, following the chips of the log view.
+struct Painter {
+    enabled: bool,
+    settings: Settings,
+    matcher: RuleMatcher,
+}
+
+fn hex_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+    match hex.len() {
+        6 => Some((
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        )),
+        3 => {
+            let short = |i: usize| channel(&hex[i..i + 1]).map(|v| v * 17);
+            Some((short(0)?, short(1)?, short(2)?))
+        }
+        _ => None,
+    }
+}
+
+fn pattern_style(sets: &[RoleRuleSet], id: Option<StyleId>) -> Option<&ChipStyle> {
+    let id = id?;
+    sets.get(id.set)?.rules.get(id.rule).map(|rule| &rule.style)
+}
+
+impl Painter {
+    fn new(db: &Arc<Mutex<Option<Connection>>>, enabled: bool) -> Self {
+        let settings = Settings::load(db).unwrap_or_default();
+        let matcher = RuleMatcher::new(
+            &settings.level_rule_sets,
+            &settings.role_rule_sets,
+            &settings.label_rule_sets,
+        )
+        .unwrap_or_default();
+        Self {
+            enabled,
+            settings,
+            matcher,
+        }
+    }
+
+    fn chip(&self, style: Option<&ChipStyle>, text: &str) -> String {
+        let chip = format!("[{}]", text);
+        let Some((r, g, b)) = style
+            .filter(|_| self.enabled)
+            .and_then(|s| hex_color(&s.color))
+        else {
+            return chip;
+        };
+        match style.map(|s| s.style) {
+            Some(ChipStyleKind::Fill) => {
+                format!("\x1b[48;2;{};{};{}m\x1b[97m{}\x1b[0m", r, g, b, chip)
+            }
+            _ => format!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, chip),
+        }
+    }
+
+    fn dim(&self, text: &str) -> String {
+        if self.enabled {
+            format!("\x1b[2m{}\x1b[0m", text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn line(&self, message: &DBMessage) -> String {
+        let level = self.matcher.resolve_level(message.level).and_then(|id| {
+            let set = self.settings.level_rule_sets.get(id.set)?;
+            set.rules.get(id.rule).map(|rule| &rule.style)
+        });
+        let role = pattern_style(
+            &self.settings.role_rule_sets,
+            self.matcher.resolve_role(&message.role),
+        );
+        let label = pattern_style(
+            &self.settings.label_rule_sets,
+            self.matcher.resolve_label(&message.label),
+        );
+        let time = Local
+            .timestamp_micros(message.time as i64)
+            .single()
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S%.6f").to_string())
+            .unwrap_or_else(|| message.time.to_string());
+        let level_text = level
+            .and_then(|s| s.text.clone())
+            .unwrap_or_else(|| message.level.to_string());
+        format!(
+            "{} {} {} {} {} {}",
+            self.dim(&time),
+            self.chip(level, &level_text),
+            self.chip(role, &message.role),
+            self.chip(label, &message.label),
+            message.messages.join(" "),
+            self.dim(&format!(
+                "({}:{} {} {}/{})",
+                message.file, message.line, message.function, message.process_id, message.thread_id
+            )),
+        )
+    }
+
+    fn print(&self, messages: &[DBMessage], json: bool) -> Result<(), String> {
+        let mut out = std::io::stdout().lock();
+        for message in messages {
+            let line = if json {
+                serde_json::to_string(message).map_err(|e| e.to_string())?
+            } else {
+                self.line(message)
+            };
+            if writeln!(out, "{}", line).is_err() {
+                // stdout closed, e.g. piped into `head`
+                std::process::exit(0);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn tail(
+    db: &Arc<Mutex<Option<Connection>>>,
+    painter: &Painter,
+    config: &FilterConfig,
+    lines: i32,
+    follow: bool,
+    json: bool,
+) -> Result<(), String> {
+    let mut seen = db.last_message_id()?;
+    // Only messages up to `seen` here, later ones are picked up by the loop below.
+    let mut window = config.clone();
+    window.id = Some(clamp(&config.id, None, Some(seen as i64)));
+    let mut last = db.query_messages(&window, &MessageField::Id, &lines, &0, true)?;
+    last.reverse();
+    painter.print(&last, json)?;
+    if !follow {
+        return Ok(());
+    }
+    loop {
+        std::thread::sleep(TAIL_INTERVAL);
+        let newest = db.last_message_id()?;
+        if newest < seen {
+            // Messages were deleted or the database was replaced.
+            seen = newest;
+        }
+        if newest == seen {
+            continue;
+        }
+        let mut window = config.clone();
+        window.id = Some(clamp(
+            &config.id,
+            Some(seen as i64 + 1),
+            Some(newest as i64),
+        ));
+        let mut offset = 0;
+        loop {
+            let batch =
+                db.query_messages(&window, &MessageField::Id, &TAIL_BATCH, &offset, false)?;
+            painter.print(&batch, json)?;
+            if batch.len() < TAIL_BATCH as usize {
+                break;
+            }
+            offset += TAIL_BATCH;
+        }
+        seen = newest;
+    }
+}
+
+fn print_counts(counts: Vec<FieldCount>) {
+    for count in counts {
+        match count.value {
+            serde_json::Value::String(value) => println!("{}\t{}", count.count, value),
+            value => println!("{}\t{}", count.count, value),
+        }
+    }
+}
+
+fn clamp(range: &Option<NumberRange>, min: Option<i64>, max: Option<i64>) -> NumberRange {
+    let (lower, upper) = range.as_ref().map_or((None, None), |r| (r.min, r.max));
+    NumberRange {
+        min: match (lower, min) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        },
+        max: match (upper, max) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        },
+    }
+}
+
+fn project_db_path(name: &str) -> Result<PathBuf, String> {
+    let registry_path = default_registry_path()
+        .filter(|path| path.exists())
+        .ok_or("no projects found, create one in the desktop app first")?;
+    let registry = Arc::new(Mutex::new(Option::<Connection>::None));
+    // The default project is only registered when the registry is empty.
+    let default_project = Project {
+        name: "default".to_string(),
+        db_path: default_db_path()
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        active: true,
+    };
+    registry.connect_registry(&registry_path, &default_project)?;
+    registry
+        .get_project(name)?
+        .map(|project| PathBuf::from(project.db_path))
+        .ok_or_else(|| format!("project not found: {}", name))
+}
+
+fn run() -> Result<(), String> {
+    let args = Args::parse();
+    let db_path = match &args.project {
+        Some(name) => project_db_path(name)?,
+        None => args
+            .db
+            .or_else(default_db_path)
+            .ok_or("cannot determine the database path, pass --db")?,
+    };
+    if !db_path.exists() {
+        return Err(format!("database not found: {}", db_path.display()));
+    }
+    let db = Arc::new(Mutex::new(Option::<Connection>::None));
+    db.connect(&db_path)?;
+    let color =
+        !args.no_color && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal();
+    let painter = Painter::new(&db, color);
+
+    match args.command {
+        Command::Query {
+            filter: words,
+            order_by,
+            desc,
+            limit,
+            offset,
+            json,
+        } => {
+            let config = filter(&words)?;
+            // A negative limit means no limit in SQLite.
+            let messages =
+                db.query_messages(&config, &order_by, &limit.unwrap_or(-1), &offset, desc)?;
+            painter.print(&messages, json)?;
+        }
+        Command::Count { filter: words, by } => {
+            let config = filter(&words)?;
+            match by {
+                Some(field) => print_counts(db.count_by(&config, &field)?),
+                None => println!("{}", db.filter_messages_count(&config)?),
+            }
+        }
+        Command::Distinct {
+            field,
+            filter: words,
+            prefix,
+            limit,
+            by_frequency,
+        } => {
+            let query = DistinctQuery {
+                config: Some(filter(&words)?),
+                prefix,
+                limit,
+                by_frequency,
+            };
+            print_counts(db.get_distinct(&field, &query)?);
+        }
+        Command::Delete {
+            filter: words,
+            yes,
+            all,
+        } => {
+            let config = filter(&words)?;
+            if config.is_empty() && !all {
+                return Err("refusing to delete every message without --all".to_string());
+            }
+            if !yes {
+                println!(
+                    "{} messages match, pass --yes to delete them",
+                    db.filter_messages_count(&config)?
+                );
+                return Ok(());
+            }
+            println!("{} messages deleted", db.delete_messages(&config)?);
+        }
+        Command::Export {
+            path,
+            filter: words,
+            format,
+        } => {
+            let config = filter(&words)?;
+            let format =
+                format.unwrap_or_else(|| match path.extension().and_then(|e| e.to_str()) {
+                    Some("jsonl") | Some("json") => Format::Jsonl,
+                    Some("csv") => Format::Csv,
+                    _ => Format::Text,
+                });
+            if path.as_os_str() == "-" {
+                let mut out = std::io::stdout().lock();
+                db.export_messages(&config, format.into(), &mut out, |_, _| true)?;
+            } else {
+                let exported =
+                    db.export_messages_to_file(&config, format.into(), &path, |_, _| true)?;
+                eprintln!("{} messages exported to {}", exported, path.display());
+            }
+        }
+        Command::Tail {
+            filter: words,
+            lines,
+            follow,
+            json,
+        } => tail(&db, &painter, &filter(&words)?, lines, follow, json)?,
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("xclogger: {}", e);
+        std::process::exit(1);
+    }
+}
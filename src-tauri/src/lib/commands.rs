@@ -0,0 +1,540 @@
+use crate::db::*;
+use crate::loghandler::*;
+use crate::process::ProcessSpec;
+use crate::profile::{ProfileFormat, ProfileStrategy};
+use crate::settings::Settings;
+use std::path::PathBuf;
+use tauri::{ipc::Channel, AppHandle, Emitter, State};
+#[tauri::command]
+async fn stop_server(handler: State<'_, LogHandler>) -> Result<String, String> {
+    handler.stop_server().map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn start_server(app: AppHandle, handler: State<'_, LogHandler>) -> Result<String, String> {
+    handler.start_server(&app).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn get_messages(
+    app_handle: AppHandle,
+    handler: State<'_, LogHandler>,
+    limit: i32,
+    offset: i32,
+    desc: bool,
+) -> Result<String, String> {
+    if !handler.db.is_connected() {
+        handler.connect_db(&app_handle)?;
+    }
+    handler.db.get_messages(limit, offset, desc)
+}
+#[tauri::command]
+async fn get_message_count(app: AppHandle, handler: State<'_, LogHandler>) -> Result<i32, String> {
+    handler.connect_db(&app)?;
+    handler.db.get_message_count()
+}
+#[tauri::command]
+async fn config_set(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    key: String,
+    value: String,
+) -> Result<(), String> {
+    // 设置项与 update_settings 走同一流程，其余键原样保存
+    let mut settings = handler.get_settings(&app)?;
+    if settings.set(&key, &value)? {
+        handler.update_settings(&app, settings)?;
+        return Ok(());
+    }
+    handler.db.set_config(key.as_str(), value.as_str())
+}
+#[tauri::command]
+async fn config_get(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    key: String,
+) -> Result<Option<String>, String> {
+    handler.connect_db(&app)?;
+    handler.db.get_config(key.as_str())
+}
+#[tauri::command]
+async fn get_all_configs(app: AppHandle, handler: State<'_, LogHandler>) -> Result<String, String> {
+    serde_json::to_string(&handler.get_settings(&app)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn update_settings(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    settings: Settings,
+) -> Result<String, String> {
+    serde_json::to_string(&handler.update_settings(&app, settings)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn export_profile(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    path: String,
+    format: Option<ProfileFormat>,
+) -> Result<String, String> {
+    serde_json::to_string(&handler.export_profile(&app, &path, format)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn import_profile(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    path: String,
+    strategy: ProfileStrategy,
+) -> Result<String, String> {
+    serde_json::to_string(&handler.import_profile(&app, &path, strategy)?)
+        .map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn get_server_address(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    handler.get_address()
+}
+#[tauri::command]
+async fn set_server_address(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    address: String,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    handler.set_server_address(address)
+}
+#[tauri::command]
+async fn get_server_state(handler: State<'_, LogHandler>) -> Result<String, String> {
+    serde_json::to_string(&handler.get_server_state()?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn filter_messages(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    config: FilterConfig,
+    order_by: MessageField,
+    limit: i32,
+    offset: i32,
+    desc: bool,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    let mut messages = handler
+        .db
+        .query_messages(&config, &order_by, &limit, &offset, desc)?;
+    handler.pipeline.annotate(&mut messages);
+    serde_json::to_string(&messages).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn filter_messages_count(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    config: FilterConfig,
+) -> Result<i32, String> {
+    handler.connect_db(&app)?;
+    handler.db.filter_messages_count(&config)
+}
+#[tauri::command]
+async fn delete_messages(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    config: FilterConfig,
+) -> Result<usize, String> {
+    handler.connect_db(&app)?;
+    handler.db.delete_messages(&config)
+}
+#[tauri::command]
+async fn get_distinct(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    field: MessageField,
+    query: Option<DistinctQuery>,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(
+        &handler
+            .db
+            .get_distinct(&field, &query.unwrap_or_default())?,
+    )
+    .map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn count_by(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    config: FilterConfig,
+    field: MessageField,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(&handler.db.count_by(&config, &field)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn time_histogram(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    config: FilterConfig,
+    bucket: i64,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(&handler.db.time_histogram(&config, bucket)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn level_by_role(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    config: FilterConfig,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(&handler.db.level_by_role(&config)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn get_context(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    id: i64,
+    before: usize,
+    after: usize,
+    scope: Option<ContextScope>,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(
+        &handler
+            .db
+            .get_context(id, before, after, scope.unwrap_or_default())?,
+    )
+    .map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn list_projects(app: AppHandle, handler: State<'_, LogHandler>) -> Result<String, String> {
+    serde_json::to_string(&handler.list_projects(&app)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn get_current_project(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+) -> Result<String, String> {
+    serde_json::to_string(&handler.current_project(&app)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn create_project(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    name: String,
+    address: Option<String>,
+    db_path: Option<String>,
+) -> Result<String, String> {
+    serde_json::to_string(&handler.create_project(&app, &name, address, db_path)?)
+        .map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn switch_project(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    name: String,
+) -> Result<String, String> {
+    serde_json::to_string(&handler.switch_project(&app, &name)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn rename_project(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    name: String,
+    new_name: String,
+) -> Result<(), String> {
+    handler.rename_project(&app, &name, &new_name)
+}
+#[tauri::command]
+async fn delete_project(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    name: String,
+    remove_file: bool,
+) -> Result<(), String> {
+    handler.delete_project(&app, &name, remove_file)
+}
+#[tauri::command]
+async fn export_messages(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    config: FilterConfig,
+    format: ExportFormat,
+    path: String,
+) -> Result<usize, String> {
+    handler.export_messages(&app, &config, format, &path)
+}
+#[tauri::command]
+async fn cancel_export(handler: State<'_, LogHandler>, path: String) -> Result<(), String> {
+    handler.cancel_export(&path)
+}
+#[tauri::command]
+async fn import_messages(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    path: String,
+    format: ImportFormat,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(&handler.db.import_messages(&PathBuf::from(path), &format)?)
+        .map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn list_sessions(app: AppHandle, handler: State<'_, LogHandler>) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(&handler.db.list_sessions()?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn export_archive(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    config: FilterConfig,
+    order_by: Option<MessageField>,
+    desc: Option<bool>,
+    path: String,
+) -> Result<String, String> {
+    let order = order_by.map(|field| (field, desc.unwrap_or(false)));
+    serde_json::to_string(&handler.export_archive(&app, &config, order, &path)?)
+        .map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn open_archive(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    path: String,
+) -> Result<String, String> {
+    serde_json::to_string(&handler.open_archive(&app, &path)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn close_archive(app: AppHandle, handler: State<'_, LogHandler>) -> Result<(), String> {
+    handler.close_archive(&app)
+}
+#[tauri::command]
+async fn compact_messages(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    before_time: i64,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(&handler.db.move_to_cold(before_time, COLD_BLOCK_SIZE)?)
+        .map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn get_storage_stats(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(&handler.db.storage_stats()?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn subscribe(
+    handler: State<'_, LogHandler>,
+    config: FilterConfig,
+    throttle_ms: Option<u64>,
+    on_messages: Channel<Vec<DBMessage>>,
+) -> Result<u64, String> {
+    Ok(handler
+        .pipeline
+        .subscriptions
+        .subscribe(config, throttle_ms.unwrap_or(100), move |batch| {
+            on_messages.send(batch).is_ok()
+        }))
+}
+#[tauri::command]
+async fn unsubscribe(handler: State<'_, LogHandler>, id: u64) -> Result<bool, String> {
+    Ok(handler.pipeline.subscriptions.unsubscribe(id))
+}
+#[tauri::command]
+async fn pause_stream(app: AppHandle, handler: State<'_, LogHandler>) -> Result<(), String> {
+    handler.pause(&app)
+}
+#[tauri::command]
+async fn resume_stream(app: AppHandle, handler: State<'_, LogHandler>) -> Result<usize, String> {
+    handler.resume(&app)
+}
+#[tauri::command]
+async fn add_bookmark(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    name: String,
+    message_id: Option<i64>,
+    time: Option<i64>,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(&handler.db.add_bookmark(&name, message_id, time)?)
+        .map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn list_bookmarks(app: AppHandle, handler: State<'_, LogHandler>) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(&handler.db.list_bookmarks()?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn rename_bookmark(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    id: i64,
+    name: String,
+) -> Result<(), String> {
+    handler.connect_db(&app)?;
+    handler.db.rename_bookmark(id, &name)
+}
+#[tauri::command]
+async fn delete_bookmark(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    id: i64,
+) -> Result<(), String> {
+    handler.connect_db(&app)?;
+    handler.db.delete_bookmark(id)
+}
+#[tauri::command]
+async fn locate_bookmark(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    id: i64,
+    config: FilterConfig,
+    desc: bool,
+) -> Result<i32, String> {
+    handler.connect_db(&app)?;
+    handler.db.locate_bookmark(id, &config, desc)
+}
+#[tauri::command]
+async fn apply_bookmark_range(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    config: FilterConfig,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    let config = handler.db.bookmark_range(&config, from, to)?;
+    serde_json::to_string(&config).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn create_search(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    search: SavedSearch,
+) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    let search = handler.db.create_search(&search)?;
+    handler.pipeline.reload_alerts()?;
+    serde_json::to_string(&search).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn list_searches(app: AppHandle, handler: State<'_, LogHandler>) -> Result<String, String> {
+    handler.connect_db(&app)?;
+    serde_json::to_string(&handler.db.list_searches()?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn update_search(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    search: SavedSearch,
+) -> Result<(), String> {
+    handler.connect_db(&app)?;
+    handler.db.update_search(&search)?;
+    handler.pipeline.reload_alerts()
+}
+#[tauri::command]
+async fn delete_search(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    id: i64,
+) -> Result<(), String> {
+    handler.connect_db(&app)?;
+    handler.db.delete_search(id)?;
+    handler.pipeline.reload_alerts()
+}
+#[tauri::command]
+async fn launch_process(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    spec: ProcessSpec,
+) -> Result<String, String> {
+    serde_json::to_string(&handler.launch_process(&app, spec)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn list_processes(handler: State<'_, LogHandler>) -> Result<String, String> {
+    serde_json::to_string(&handler.processes.list()?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn stop_process(handler: State<'_, LogHandler>, id: u64) -> Result<String, String> {
+    serde_json::to_string(&handler.processes.stop(id)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn restart_process(
+    app: AppHandle,
+    handler: State<'_, LogHandler>,
+    id: u64,
+) -> Result<String, String> {
+    serde_json::to_string(&handler.restart_process(&app, id)?).map_err(|e| e.to_string())
+}
+#[tauri::command]
+async fn remove_process(handler: State<'_, LogHandler>, id: u64) -> Result<(), String> {
+    handler.processes.remove(id)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .manage(LogHandler::new())
+        .invoke_handler(tauri::generate_handler![
+            start_server,
+            stop_server,
+            get_server_address,
+            set_server_address,
+            get_server_state,
+            get_messages,
+            filter_messages_count,
+            filter_messages,
+            get_message_count,
+            delete_messages,
+            get_distinct,
+            count_by,
+            time_histogram,
+            level_by_role,
+            get_context,
+            config_set,
+            config_get,
+            get_all_configs,
+            update_settings,
+            export_profile,
+            import_profile,
+            list_projects,
+            get_current_project,
+            create_project,
+            switch_project,
+            rename_project,
+            delete_project,
+            export_messages,
+            cancel_export,
+            import_messages,
+            list_sessions,
+            export_archive,
+            open_archive,
+            close_archive,
+            compact_messages,
+            get_storage_stats,
+            subscribe,
+            unsubscribe,
+            pause_stream,
+            resume_stream,
+            add_bookmark,
+            list_bookmarks,
+            rename_bookmark,
+            delete_bookmark,
+            locate_bookmark,
+            apply_bookmark_range,
+            create_search,
+            list_searches,
+            update_search,
+            delete_search,
+            launch_process,
+            list_processes,
+            stop_process,
+            restart_process,
+            remove_process
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
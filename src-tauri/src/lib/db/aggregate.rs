@@ -0,0 +1,380 @@
+use super::messagedb::{escape_like, get_params};
+use super::{FilterConfig, MessageField};
+use rusqlite::{types::ValueRef, Connection};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldCount {
+    pub value: serde_json::Value,
+    pub count: i64,
+}
+
+/// 去重取值的查询条件，用于字段自动补全
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DistinctQuery {
+    /// 只统计满足过滤条件的消息，为空时统计全部消息
+    #[serde(default)]
+    pub config: Option<FilterConfig>,
+    /// 取值前缀，不区分 ASCII 大小写
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// 为 true 时按出现次数从多到少排序，否则按取值排序
+    #[serde(default)]
+    pub by_frequency: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct HistogramBucket {
+    /// 桶的起始时间，单位与 time 列相同（微秒）
+    pub start: i64,
+    pub count: i64,
+    pub levels: BTreeMap<i32, i64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RoleLevels {
+    pub role: String,
+    pub count: i64,
+    pub levels: BTreeMap<i32, i64>,
+}
+
+pub trait AggregateDB {
+    /// 按字段分组计数，结果按数量从多到少排列
+    fn count_by(
+        &self,
+        config: &FilterConfig,
+        field: &MessageField,
+    ) -> Result<Vec<FieldCount>, String>;
+    /// 字段的去重取值及其出现次数，可限定范围、前缀与数量，用于自动补全
+    fn get_distinct(
+        &self,
+        field: &MessageField,
+        query: &DistinctQuery,
+    ) -> Result<Vec<FieldCount>, String>;
+    fn time_histogram(
+        &self,
+        config: &FilterConfig,
+        bucket: i64,
+    ) -> Result<Vec<HistogramBucket>, String>;
+    fn level_by_role(&self, config: &FilterConfig) -> Result<Vec<RoleLevels>, String>;
+}
+
+// 辅助函数：将 SQLite 的值转换为 JSON，分组字段既可能是文本也可能是整数
+pub(crate) fn value_to_json(value: ValueRef) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => serde_json::Value::from(i),
+        ValueRef::Real(f) => serde_json::Value::from(f),
+        ValueRef::Text(t) => serde_json::Value::from(String::from_utf8_lossy(t).to_string()),
+        ValueRef::Blob(_) => serde_json::Value::Null,
+    }
+}
+
+impl AggregateDB for Arc<Mutex<Option<Connection>>> {
+    fn count_by(
+        &self,
+        config: &FilterConfig,
+        field: &MessageField,
+    ) -> Result<Vec<FieldCount>, String> {
+        self.get_distinct(
+            field,
+            &DistinctQuery {
+                config: Some(config.clone()),
+                by_frequency: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn get_distinct(
+        &self,
+        field: &MessageField,
+        query: &DistinctQuery,
+    ) -> Result<Vec<FieldCount>, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let column = field.column();
+        let (mut where_clause, mut params) = match &query.config {
+            Some(config) => get_params(config),
+            None => (String::new(), Vec::new()),
+        };
+        if let Some(prefix) = query.prefix.as_ref().filter(|p| !p.is_empty()) {
+            // 转义 LIKE 通配符，前缀按字面匹配
+            let escaped = escape_like(prefix);
+            let condition = format!("{} LIKE ? ESCAPE '\\'", column);
+            where_clause = if where_clause.is_empty() {
+                format!("WHERE {}", condition)
+            } else {
+                format!("{} AND {}", where_clause, condition)
+            };
+            params.push(Box::new(format!("{}%", escaped)));
+        }
+        let order = if query.by_frequency {
+            format!("count DESC, {}", column)
+        } else {
+            column.to_string()
+        };
+        let limit = match query.limit {
+            Some(limit) => format!("LIMIT {}", limit),
+            None => String::new(),
+        };
+        let sql = format!(
+            "SELECT {column}, COUNT(*) AS count FROM all_messages {where_clause}
+             GROUP BY {column} ORDER BY {order} {limit}",
+            column = column,
+            where_clause = where_clause,
+            order = order,
+            limit = limit
+        );
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let counts: Result<Vec<_>, _> = stmt
+            .query_map(
+                rusqlite::params_from_iter(params.iter().map(|p| &**p)),
+                |row| {
+                    Ok(FieldCount {
+                        value: value_to_json(row.get_ref(0)?),
+                        count: row.get(1)?,
+                    })
+                },
+            )
+            .map_err(|e| e.to_string())?
+            .collect();
+        counts.map_err(|e| e.to_string())
+    }
+
+    fn time_histogram(
+        &self,
+        config: &FilterConfig,
+        bucket: i64,
+    ) -> Result<Vec<HistogramBucket>, String> {
+        if bucket <= 0 {
+            return Err("桶宽度必须大于0".to_string());
+        }
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let (where_clause, mut params) = get_params(config);
+        params.insert(0, Box::new(bucket));
+        params.insert(0, Box::new(bucket));
+        let query = format!(
+            "SELECT (time / ?) * ? AS bucket, level, COUNT(*) FROM all_messages {}
+             GROUP BY bucket, level ORDER BY bucket",
+            where_clause
+        );
+        let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+        let mut rows = stmt
+            .query(rusqlite::params_from_iter(params.iter().map(|p| &**p)))
+            .map_err(|e| e.to_string())?;
+
+        let mut buckets: Vec<HistogramBucket> = Vec::new();
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            let start: i64 = row.get(0).map_err(|e| e.to_string())?;
+            let level: i32 = row.get(1).map_err(|e| e.to_string())?;
+            let count: i64 = row.get(2).map_err(|e| e.to_string())?;
+            match buckets.last_mut() {
+                Some(last) if last.start == start => {
+                    last.count += count;
+                    last.levels.insert(level, count);
+                }
+                _ => buckets.push(HistogramBucket {
+                    start,
+                    count,
+                    levels: BTreeMap::from([(level, count)]),
+                }),
+            }
+        }
+        Ok(buckets)
+    }
+
+    fn level_by_role(&self, config: &FilterConfig) -> Result<Vec<RoleLevels>, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let (where_clause, params) = get_params(config);
+        let query = format!(
+            "SELECT role, level, COUNT(*) FROM all_messages {}
+             GROUP BY role, level ORDER BY role, level",
+            where_clause
+        );
+        let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+        let mut rows = stmt
+            .query(rusqlite::params_from_iter(params.iter().map(|p| &**p)))
+            .map_err(|e| e.to_string())?;
+
+        let mut roles: Vec<RoleLevels> = Vec::new();
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            let role: String = row.get(0).map_err(|e| e.to_string())?;
+            let level: i32 = row.get(1).map_err(|e| e.to_string())?;
+            let count: i64 = row.get(2).map_err(|e| e.to_string())?;
+            match roles.last_mut() {
+                Some(last) if last.role == role => {
+                    last.count += count;
+                    last.levels.insert(level, count);
+                }
+                _ => roles.push(RoleLevels {
+                    role,
+                    count,
+                    levels: BTreeMap::from([(level, count)]),
+                }),
+            }
+        }
+        Ok(roles)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{message, TestDb};
+    use msg_server::MessageData;
+
+    // 12 条消息：3 个角色轮流，级别 0..4 循环，每 10 微秒一条
+    fn open() -> TestDb {
+        TestDb::with((0..12).map(|i| MessageData {
+            role: ["net", "db", "ui"][i % 3].to_string(),
+            label: "aggregate".to_string(),
+            time: 1000 + i * 10,
+            level: (i % 4) as i32,
+            ..message(&format!("message {}", i))
+        }))
+    }
+
+    fn config(json: &str) -> FilterConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn count_by_field() {
+        let db = open();
+        let counts = db
+            .count_by(
+                &config(r#"{"level":{"min":1,"max":null}}"#),
+                &MessageField::Level,
+            )
+            .unwrap();
+        let counts: Vec<_> = counts.iter().map(|c| (c.value.clone(), c.count)).collect();
+        assert_eq!(
+            counts,
+            [
+                (serde_json::json!(1), 3),
+                (serde_json::json!(2), 3),
+                (serde_json::json!(3), 3)
+            ]
+        );
+        let roles = db
+            .count_by(
+                &config(r#"{"role":{"mode":"Start","value":"n"}}"#),
+                &MessageField::Role,
+            )
+            .unwrap();
+        assert_eq!(
+            roles,
+            [FieldCount {
+                value: serde_json::json!("net"),
+                count: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn distinct_values_for_autocomplete() {
+        let db = open();
+        let values = |query: DistinctQuery| -> Vec<(serde_json::Value, i64)> {
+            db.get_distinct(&MessageField::Role, &query)
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.value, c.count))
+                .collect()
+        };
+        assert_eq!(
+            values(DistinctQuery::default()),
+            [
+                (serde_json::json!("db"), 4),
+                (serde_json::json!("net"), 4),
+                (serde_json::json!("ui"), 4)
+            ]
+        );
+        // 前 5 条消息的角色依次为 net、db、ui、net、db
+        let scoped = DistinctQuery {
+            config: Some(config(r#"{"time":{"min":null,"max":1040}}"#)),
+            by_frequency: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            values(scoped.clone()),
+            [
+                (serde_json::json!("db"), 2),
+                (serde_json::json!("net"), 2),
+                (serde_json::json!("ui"), 1)
+            ]
+        );
+        let limited = DistinctQuery {
+            limit: Some(1),
+            ..scoped
+        };
+        assert_eq!(values(limited), [(serde_json::json!("db"), 2)]);
+        let prefixed = DistinctQuery {
+            prefix: Some("N".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(values(prefixed), [(serde_json::json!("net"), 4)]);
+        // 前缀中的通配符按字面匹配
+        let literal = DistinctQuery {
+            prefix: Some("_".to_string()),
+            ..Default::default()
+        };
+        assert!(values(literal).is_empty());
+    }
+
+    #[test]
+    fn histogram_buckets_by_level() {
+        let db = open();
+        assert!(db.time_histogram(&FilterConfig::default(), 0).is_err());
+        let buckets = db.time_histogram(&FilterConfig::default(), 40).unwrap();
+        let summary: Vec<_> = buckets
+            .iter()
+            .map(|b| (b.start, b.count, b.levels.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (1000, 4, BTreeMap::from([(0, 1), (1, 1), (2, 1), (3, 1)])),
+                (1040, 4, BTreeMap::from([(0, 1), (1, 1), (2, 1), (3, 1)])),
+                (1080, 4, BTreeMap::from([(0, 1), (1, 1), (2, 1), (3, 1)])),
+            ]
+        );
+        let errors = db
+            .time_histogram(&config(r#"{"level":{"min":3,"max":null}}"#), 1000)
+            .unwrap();
+        assert_eq!(
+            (errors.len(), errors[0].start, errors[0].count),
+            (1, 1000, 3)
+        );
+    }
+
+    #[test]
+    fn levels_per_role() {
+        let db = open();
+        let roles = db.level_by_role(&FilterConfig::default()).unwrap();
+        let summary: Vec<_> = roles
+            .iter()
+            .map(|r| (r.role.as_str(), r.count, r.levels.clone()))
+            .collect();
+        // 第 i 条消息的角色为 i % 3，级别为 i % 4
+        assert_eq!(
+            summary,
+            [
+                ("db", 4, BTreeMap::from([(0, 1), (1, 1), (2, 1), (3, 1)])),
+                ("net", 4, BTreeMap::from([(0, 1), (1, 1), (2, 1), (3, 1)])),
+                ("ui", 4, BTreeMap::from([(0, 1), (1, 1), (2, 1), (3, 1)])),
+            ]
+        );
+    }
+}
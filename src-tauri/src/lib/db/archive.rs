@@ -0,0 +1,349 @@
+use super::export::read_chunks;
+use super::messagedb::fields_text;
+use super::{Config, DBMessage, FilterConfig, MessageField, Session, SessionDB};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+pub const ARCHIVE_FORMAT: &str = "xclog";
+pub const ARCHIVE_VERSION: u32 = 1;
+// 随归档一起保存的配置项，打开归档时恢复相同的颜色与规则
+pub const ARCHIVE_CONFIG_KEYS: [&str; 3] = ["level_rule_sets", "role_rule_sets", "label_rule_sets"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HostInfo {
+    pub hostname: String,
+    pub os: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArchiveHeader {
+    pub format: String,
+    pub version: u32,
+    pub created_at: String,
+    pub project: String,
+    pub host: HostInfo,
+    pub message_count: usize,
+    pub process_ids: Vec<usize>,
+    pub sessions: Vec<Session>,
+    pub config: Vec<(String, String)>,
+    /// 导出时的过滤条件和排序，打开归档时作为固定的保存搜索恢复
+    #[serde(default)]
+    pub filter: FilterConfig,
+    #[serde(default)]
+    pub order_by: Option<MessageField>,
+    #[serde(default)]
+    pub desc: bool,
+}
+
+/// 归档中的一条消息，会话与来源在旧版本归档中不存在
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ArchiveMessage {
+    #[serde(flatten)]
+    message: DBMessage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+}
+
+/// `.xclog` 归档：zstd 压缩的 JSON Lines，第一行为 `ArchiveHeader`，其余每行为一条 `ArchiveMessage`
+pub trait ArchiveDB {
+    /// `order` 为导出时界面上的排序字段和是否降序
+    fn export_archive(
+        &self,
+        config: &FilterConfig,
+        order: Option<(MessageField, bool)>,
+        path: &Path,
+        project: &str,
+    ) -> Result<ArchiveHeader, String>;
+    fn load_archive(&self, path: &Path) -> Result<ArchiveHeader, String>;
+}
+
+pub fn read_archive_header(path: &Path) -> Result<ArchiveHeader, String> {
+    let file = File::open(path).map_err(|e| format!("打开归档失败: {}", e))?;
+    let decoder = zstd::Decoder::new(file).map_err(|e| format!("解压归档失败: {}", e))?;
+    let mut line = String::new();
+    BufReader::new(decoder)
+        .read_line(&mut line)
+        .map_err(|e| format!("读取归档失败: {}", e))?;
+    let header: ArchiveHeader =
+        serde_json::from_str(&line).map_err(|e| format!("归档头格式错误: {}", e))?;
+    if header.format != ARCHIVE_FORMAT || header.version > ARCHIVE_VERSION {
+        return Err(format!(
+            "不支持的归档格式: {} v{}",
+            header.format, header.version
+        ));
+    }
+    Ok(header)
+}
+
+impl ArchiveDB for Arc<Mutex<Option<Connection>>> {
+    fn export_archive(
+        &self,
+        config: &FilterConfig,
+        order: Option<(MessageField, bool)>,
+        path: &Path,
+        project: &str,
+    ) -> Result<ArchiveHeader, String> {
+        // 先在一次遍历中收集元数据，再写入消息
+        let mut process_ids = BTreeSet::new();
+        let mut session_ids = BTreeSet::new();
+        let mut message_count = 0;
+        {
+            let conn_guard = self.lock().map_err(|e| e.to_string())?;
+            let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+            let (where_clause, params) = super::messagedb::get_params(config);
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT process_id, session_id, COUNT(*) FROM all_messages {} GROUP BY process_id, session_id",
+                    where_clause
+                ))
+                .map_err(|e| e.to_string())?;
+            let mut rows = stmt
+                .query(rusqlite::params_from_iter(params.iter().map(|p| &**p)))
+                .map_err(|e| e.to_string())?;
+            while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+                process_ids.insert(row.get::<_, i64>(0).map_err(|e| e.to_string())? as usize);
+                if let Some(id) = row.get::<_, Option<i64>>(1).map_err(|e| e.to_string())? {
+                    session_ids.insert(id);
+                }
+                message_count += row.get::<_, i64>(2).map_err(|e| e.to_string())? as usize;
+            }
+        }
+        let mut archive_config = Vec::new();
+        for key in ARCHIVE_CONFIG_KEYS {
+            if let Some(value) = self.get_config(key)? {
+                archive_config.push((key.to_string(), value));
+            }
+        }
+        let header = ArchiveHeader {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            created_at: chrono::Local::now().to_rfc3339(),
+            project: project.to_string(),
+            host: HostInfo {
+                hostname: gethostname::gethostname().to_string_lossy().to_string(),
+                os: std::env::consts::OS.to_string(),
+            },
+            message_count,
+            process_ids: process_ids.into_iter().collect(),
+            sessions: self
+                .list_sessions()?
+                .into_iter()
+                .filter(|s| session_ids.contains(&s.id))
+                .collect(),
+            config: archive_config,
+            filter: config.clone(),
+            order_by: order.map(|(field, _)| field),
+            desc: order.is_some_and(|(_, desc)| desc),
+        };
+
+        let file = File::create(path).map_err(|e| format!("创建归档失败: {}", e))?;
+        let result = (|| {
+            let mut encoder = zstd::Encoder::new(BufWriter::new(file), 0)
+                .map_err(|e| format!("创建归档失败: {}", e))?;
+            writeln!(
+                encoder,
+                "{}",
+                serde_json::to_string(&header).map_err(|e| e.to_string())?
+            )
+            .map_err(|e| format!("写入归档失败: {}", e))?;
+            read_chunks(self, config, |rows| {
+                for row in rows {
+                    let line = serde_json::to_string(&ArchiveMessage {
+                        message: row.message.clone(),
+                        session_id: row.session_id,
+                        source: row.source.clone(),
+                    })
+                    .map_err(|e| e.to_string())?;
+                    writeln!(encoder, "{}", line).map_err(|e| format!("写入归档失败: {}", e))?;
+                }
+                Ok(())
+            })?;
+            encoder
+                .finish()
+                .and_then(|mut w| w.flush())
+                .map_err(|e| format!("写入归档失败: {}", e))
+        })();
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(path);
+            return Err(e);
+        }
+        Ok(header)
+    }
+
+    fn load_archive(&self, path: &Path) -> Result<ArchiveHeader, String> {
+        let header = read_archive_header(path)?;
+        let file = File::open(path).map_err(|e| format!("打开归档失败: {}", e))?;
+        let decoder = zstd::Decoder::new(file).map_err(|e| format!("解压归档失败: {}", e))?;
+        let lines = BufReader::new(decoder).lines().skip(1);
+
+        let mut conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_mut().ok_or("数据库未连接".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        for session in &header.sessions {
+            tx.execute(
+                "INSERT OR REPLACE INTO sessions (id, name, source, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![session.id, session.name, session.source, session.created_at],
+            )
+            .map_err(|e| format!("创建会话失败: {}", e))?;
+        }
+        for (key, value) in &header.config {
+            tx.execute(
+                "INSERT OR REPLACE INTO app_config (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )
+            .map_err(|e| format!("设置配置失败: {}", e))?;
+        }
+        if !header.filter.is_empty() || header.order_by.is_some() {
+            // 归档只读，以固定的保存搜索打开导出时的视图
+            let name = match header.project.trim() {
+                "" => "archive",
+                project => project,
+            };
+            tx.execute(
+                "INSERT OR REPLACE INTO saved_searches (name, config, order_by, descending, pinned)
+                VALUES (?1, ?2, ?3, ?4, 1)",
+                params![
+                    name,
+                    serde_json::to_string(&header.filter).map_err(|e| e.to_string())?,
+                    serde_json::to_string(&header.order_by.unwrap_or(MessageField::Id))
+                        .map_err(|e| e.to_string())?,
+                    header.desc
+                ],
+            )
+            .map_err(|e| format!("保存搜索失败: {}", e))?;
+        }
+        {
+            // 保留原始 id，与导出时看到的数据保持一致
+            let mut stmt = tx
+                .prepare(
+                    "INSERT OR REPLACE INTO log_messages
+                    (id, role, label, file, function, time, process_id, thread_id, line, level, messages, fields, session_id, source)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                )
+                .map_err(|e| e.to_string())?;
+            for line in lines {
+                let line = line.map_err(|e| format!("读取归档失败: {}", e))?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let ArchiveMessage {
+                    message,
+                    session_id,
+                    source,
+                } = serde_json::from_str(&line).map_err(|e| format!("归档消息格式错误: {}", e))?;
+                let messages_text = serde_json::to_string(&message.messages)
+                    .map_err(|e| format!("序列化消息列表失败: {}", e))?;
+                stmt.execute(params![
+                    message.id as i64,
+                    message.role,
+                    message.label,
+                    message.file,
+                    message.function,
+                    message.time as i64,
+                    message.process_id as i64,
+                    message.thread_id as i64,
+                    message.line,
+                    message.level,
+                    messages_text,
+                    fields_text(&message.fields)?,
+                    session_id,
+                    source
+                ])
+                .map_err(|e| format!("插入消息失败: {}", e))?;
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{MessageDB, NumberRange, SearchDB};
+    use crate::testing::{message, TestDb};
+    use msg_server::MessageData;
+
+    #[test]
+    fn archive_keeps_sessions_and_sources() {
+        let db = TestDb::new();
+        let session_id = db.create_session("run", Some("./run --fast")).unwrap();
+        for (session, source) in [(Some(session_id), Some("stdout")), (None, None)] {
+            db.insert_message_into(&message("hello"), session, source)
+                .unwrap();
+        }
+        let path = db.dir.join("test.xclog");
+        let header = db
+            .export_archive(&FilterConfig::default(), None, &path, "test")
+            .unwrap();
+        assert_eq!((header.message_count, header.sessions.len()), (2, 1));
+
+        let restored = TestDb::new();
+        restored.load_archive(&path).unwrap();
+        assert_eq!(restored.list_sessions().unwrap()[0].name, "run");
+        let conn_guard = restored.lock().unwrap();
+        let mut stmt = conn_guard
+            .as_ref()
+            .unwrap()
+            .prepare("SELECT session_id, source FROM log_messages ORDER BY id")
+            .unwrap();
+        let rows: Vec<(Option<i64>, Option<String>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [(Some(session_id), Some("stdout".to_string())), (None, None)]
+        );
+        drop(stmt);
+        drop(conn_guard);
+        // 没有过滤条件和排序时不生成保存搜索
+        assert!(restored.list_searches().unwrap().is_empty());
+    }
+
+    #[test]
+    fn archive_restores_the_exported_view() {
+        let db = TestDb::with([
+            message("kept"),
+            MessageData {
+                level: 4,
+                ..message("kept")
+            },
+        ]);
+        let filter = FilterConfig {
+            level: Some(NumberRange {
+                min: Some(3),
+                max: None,
+            }),
+            ..Default::default()
+        };
+        let path = db.dir.join("view.xclog");
+        let header = db
+            .export_archive(&filter, Some((MessageField::Time, true)), &path, "app")
+            .unwrap();
+        assert_eq!(header.message_count, 1);
+        let read = read_archive_header(&path).unwrap();
+        assert_eq!(read.filter.level.unwrap().min, Some(3));
+        assert!(matches!(read.order_by, Some(MessageField::Time)) && read.desc);
+
+        let restored = TestDb::new();
+        restored.load_archive(&path).unwrap();
+        let searches = restored.list_searches().unwrap();
+        assert_eq!(searches.len(), 1);
+        let view = &searches[0];
+        assert_eq!(view.name, "app");
+        assert!(view.pinned && view.desc);
+        assert!(matches!(view.order_by, MessageField::Time));
+        assert_eq!(view.config.level.as_ref().unwrap().min, Some(3));
+    }
+}
@@ -0,0 +1,290 @@
+use super::messagedb::get_params;
+use super::{FilterConfig, NumberRange};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Bookmark {
+    pub id: i64,
+    pub name: String,
+    pub message_id: Option<i64>,
+    pub time: i64,
+    pub created_at: String,
+}
+
+pub trait BookmarkDB {
+    /// 书签可以指向某条消息，也可以只记录一个时间点
+    fn add_bookmark(
+        &self,
+        name: &str,
+        message_id: Option<i64>,
+        time: Option<i64>,
+    ) -> Result<Bookmark, String>;
+    fn list_bookmarks(&self) -> Result<Vec<Bookmark>, String>;
+    fn get_bookmark(&self, id: i64) -> Result<Option<Bookmark>, String>;
+    fn rename_bookmark(&self, id: i64, name: &str) -> Result<(), String>;
+    fn delete_bookmark(&self, id: i64) -> Result<(), String>;
+    /// 返回书签在按时间排序的过滤结果中的偏移量，用于跳转
+    fn locate_bookmark(&self, id: i64, config: &FilterConfig, desc: bool) -> Result<i32, String>;
+    /// 按 id 查找书签并通过 `apply_bookmark_range` 限定过滤范围，书签不存在时报错
+    fn bookmark_range(
+        &self,
+        config: &FilterConfig,
+        from: Option<i64>,
+        to: Option<i64>,
+    ) -> Result<FilterConfig, String>;
+}
+
+pub(crate) fn create_bookmark_table(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "
+    CREATE TABLE IF NOT EXISTS
+    bookmarks (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL, -- 书签名称
+        message_id INTEGER DEFAULT NULL, -- 指向的消息，对应log_messages.id
+        time INTEGER NOT NULL, -- 书签时间点
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    );",
+        [],
+    )
+    .map_err(|e| format!("创建书签表失败: {}", e))?;
+    Ok(())
+}
+
+/// 用两个书签作为过滤边界，书签均指向消息时按 id 限定，否则按时间限定
+pub fn apply_bookmark_range(
+    config: &mut FilterConfig,
+    from: Option<&Bookmark>,
+    to: Option<&Bookmark>,
+) {
+    let by_id =
+        from.is_none_or(|b| b.message_id.is_some()) && to.is_none_or(|b| b.message_id.is_some());
+    if by_id {
+        config.id = Some(NumberRange {
+            min: from.and_then(|b| b.message_id),
+            max: to.and_then(|b| b.message_id),
+        });
+    } else {
+        config.time = Some(NumberRange {
+            min: from.map(|b| b.time),
+            max: to.map(|b| b.time),
+        });
+    }
+}
+
+fn row_to_bookmark(row: &rusqlite::Row) -> rusqlite::Result<Bookmark> {
+    Ok(Bookmark {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        message_id: row.get(2)?,
+        time: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+impl BookmarkDB for Arc<Mutex<Option<Connection>>> {
+    fn add_bookmark(
+        &self,
+        name: &str,
+        message_id: Option<i64>,
+        time: Option<i64>,
+    ) -> Result<Bookmark, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let time = match (message_id, time) {
+            (_, Some(time)) => time,
+            (Some(message_id), None) => conn
+                .query_row(
+                    "SELECT time FROM all_messages WHERE id = ?1",
+                    params![message_id],
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("消息不存在: {}", e))?,
+            (None, None) => return Err("书签需要消息ID或时间".to_string()),
+        };
+        conn.execute(
+            "INSERT INTO bookmarks (name, message_id, time) VALUES (?1, ?2, ?3)",
+            params![name, message_id, time],
+        )
+        .map_err(|e| format!("添加书签失败: {}", e))?;
+        conn.query_row(
+            "SELECT id, name, message_id, time, created_at FROM bookmarks WHERE id = ?1",
+            params![conn.last_insert_rowid()],
+            row_to_bookmark,
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    fn list_bookmarks(&self) -> Result<Vec<Bookmark>, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, name, message_id, time, created_at FROM bookmarks ORDER BY time, id",
+            )
+            .map_err(|e| format!("准备查询语句失败: {}", e))?;
+        let bookmarks: Result<Vec<_>, _> = stmt
+            .query_map([], row_to_bookmark)
+            .map_err(|e| format!("查询书签失败: {}", e))?
+            .collect();
+        bookmarks.map_err(|e| format!("收集书签结果失败: {}", e))
+    }
+
+    fn get_bookmark(&self, id: i64) -> Result<Option<Bookmark>, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        match conn.query_row(
+            "SELECT id, name, message_id, time, created_at FROM bookmarks WHERE id = ?1",
+            params![id],
+            row_to_bookmark,
+        ) {
+            Ok(bookmark) => Ok(Some(bookmark)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(format!("查询书签失败: {}", e)),
+        }
+    }
+
+    fn rename_bookmark(&self, id: i64, name: &str) -> Result<(), String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let updated = conn
+            .execute(
+                "UPDATE bookmarks SET name = ?2 WHERE id = ?1",
+                params![id, name],
+            )
+            .map_err(|e| format!("重命名书签失败: {}", e))?;
+        if updated == 0 {
+            return Err(format!("书签不存在: {}", id));
+        }
+        Ok(())
+    }
+
+    fn delete_bookmark(&self, id: i64) -> Result<(), String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let deleted = conn
+            .execute("DELETE FROM bookmarks WHERE id = ?1", params![id])
+            .map_err(|e| format!("删除书签失败: {}", e))?;
+        if deleted == 0 {
+            return Err(format!("书签不存在: {}", id));
+        }
+        Ok(())
+    }
+
+    fn locate_bookmark(&self, id: i64, config: &FilterConfig, desc: bool) -> Result<i32, String> {
+        let bookmark = self
+            .get_bookmark(id)?
+            .ok_or(format!("书签不存在: {}", id))?;
+
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let (where_clause, mut params) = get_params(config);
+        let op = if desc { ">" } else { "<" };
+        // 时间相同的消息按 id 排序，只有时间的书签位于该时间点之前
+        let condition = match bookmark.message_id {
+            Some(message_id) => {
+                params.push(Box::new(bookmark.time));
+                params.push(Box::new(bookmark.time));
+                params.push(Box::new(message_id));
+                format!("(time {op} ? OR (time = ? AND id {op} ?))", op = op)
+            }
+            None => {
+                params.push(Box::new(bookmark.time));
+                format!("time {} ?", op)
+            }
+        };
+        let where_clause = if where_clause.is_empty() {
+            format!("WHERE {}", condition)
+        } else {
+            format!("{} AND {}", where_clause, condition)
+        };
+        conn.query_row(
+            &format!("SELECT COUNT(*) FROM all_messages {}", where_clause),
+            rusqlite::params_from_iter(params.iter().map(|p| &**p)),
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    fn bookmark_range(
+        &self,
+        config: &FilterConfig,
+        from: Option<i64>,
+        to: Option<i64>,
+    ) -> Result<FilterConfig, String> {
+        let find = |id: Option<i64>| match id {
+            Some(id) => self
+                .get_bookmark(id)?
+                .map(Some)
+                .ok_or(format!("书签不存在: {}", id)),
+            None => Ok(None),
+        };
+        let (from, to) = (find(from)?, find(to)?);
+        let mut config = config.clone();
+        apply_bookmark_range(&mut config, from.as_ref(), to.as_ref());
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{MessageDB, MessageField};
+    use crate::testing::{message, TestDb};
+    use msg_server::MessageData;
+
+    // 10 条消息，每两条共用一个时间戳
+    fn open() -> TestDb {
+        TestDb::with((0..10).map(|i| MessageData {
+            time: 1000 + i / 2 * 10,
+            ..message(&format!("message {}", i))
+        }))
+    }
+
+    #[test]
+    fn bookmarks_locate_and_bound_queries() {
+        let db = open();
+        let start = db.add_bookmark("start", Some(3), None).unwrap();
+        assert_eq!(start.time, 1010);
+        let end = db.add_bookmark("end", Some(8), None).unwrap();
+        let noon = db.add_bookmark("noon", None, Some(1020)).unwrap();
+        assert!(db.add_bookmark("empty", None, None).is_err());
+
+        let all = FilterConfig::default();
+        assert_eq!(db.locate_bookmark(start.id, &all, false).unwrap(), 2);
+        assert_eq!(db.locate_bookmark(start.id, &all, true).unwrap(), 7);
+        assert_eq!(db.locate_bookmark(noon.id, &all, false).unwrap(), 4);
+
+        let ids = |config: &FilterConfig| -> Vec<usize> {
+            db.query_messages(config, &MessageField::Id, &100, &0, false)
+                .unwrap()
+                .iter()
+                .map(|m| m.id)
+                .collect()
+        };
+        let by_id = db
+            .bookmark_range(&all, Some(start.id), Some(end.id))
+            .unwrap();
+        assert_eq!(ids(&by_id), [3, 4, 5, 6, 7, 8]);
+        // 只有时间的书签按时间限定，包含该时间点的所有消息
+        let by_time = db
+            .bookmark_range(&all, Some(start.id), Some(noon.id))
+            .unwrap();
+        assert_eq!(ids(&by_time), [3, 4, 5, 6]);
+        assert!(db.bookmark_range(&all, Some(999), None).is_err());
+
+        db.rename_bookmark(noon.id, "midday").unwrap();
+        assert!(db.rename_bookmark(999, "missing").is_err());
+        db.delete_bookmark(noon.id).unwrap();
+        assert!(db.delete_bookmark(noon.id).is_err());
+        assert_eq!(db.list_bookmarks().unwrap().len(), 2);
+    }
+}
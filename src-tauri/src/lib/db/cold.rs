@@ -0,0 +1,403 @@
+use super::ensure_column;
+use rusqlite::{functions::FunctionFlags, params, Connection};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// 冷存储默认每块的行数
+pub const COLD_BLOCK_SIZE: usize = 2000;
+const COLD_COMPRESSION_LEVEL: i32 = 9;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ColdReport {
+    pub moved: usize,
+    pub blocks: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StorageStats {
+    pub hot_messages: i64,
+    pub cold_messages: i64,
+    pub cold_blocks: i64,
+    /// 冷存储中消息内容压缩前的字节数
+    pub cold_raw_bytes: i64,
+    /// 冷存储中消息内容压缩后的字节数
+    pub cold_compressed_bytes: i64,
+}
+
+/// 冷存储：旧消息的常用列保留在 `cold_messages` 中用于过滤，
+/// 消息内容按块压缩存放在 `cold_blocks`，查询统一通过 `all_messages` 视图进行
+pub trait ColdDB {
+    fn move_to_cold(&self, before_time: i64, block_size: usize) -> Result<ColdReport, String>;
+    fn storage_stats(&self) -> Result<StorageStats, String>;
+}
+
+pub(crate) fn create_cold_tables(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+    CREATE TABLE IF NOT EXISTS
+    cold_blocks (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        min_id INTEGER NOT NULL, -- 块内最小消息ID
+        max_id INTEGER NOT NULL, -- 块内最大消息ID
+        min_time INTEGER NOT NULL, -- 块内最早时间
+        max_time INTEGER NOT NULL, -- 块内最晚时间
+        row_count INTEGER NOT NULL, -- 块内消息数
+        raw_size INTEGER NOT NULL, -- 压缩前字节数
+        data BLOB NOT NULL -- zstd 压缩的 [[id, messages], ...]
+    );
+    CREATE TABLE IF NOT EXISTS
+    cold_messages (
+        id INTEGER PRIMARY KEY, -- 与原 log_messages.id 相同
+        role TEXT NOT NULL,
+        label TEXT,
+        file TEXT DEFAULT NULL,
+        function TEXT DEFAULT NULL,
+        time INTEGER NOT NULL,
+        process_id INTEGER NOT NULL,
+        thread_id INTEGER NOT NULL,
+        line INTEGER DEFAULT NULL,
+        level INTEGER NOT NULL,
+        fields TEXT DEFAULT NULL,
+        session_id INTEGER DEFAULT NULL,
+        source TEXT DEFAULT NULL,
+        created_at DATETIME,
+        block_id INTEGER NOT NULL -- 所属压缩块，对应cold_blocks.id
+    );
+    CREATE INDEX IF NOT EXISTS cold_messages_time ON cold_messages (time);
+    CREATE INDEX IF NOT EXISTS cold_messages_block ON cold_messages (block_id);",
+    )
+    .map_err(|e| format!("创建冷存储表失败: {}", e))?;
+    // 旧版本数据库缺少的列
+    ensure_column(conn, "cold_messages", "fields", "TEXT DEFAULT NULL")?;
+    // 视图依赖只在本连接注册的 cold_body，因此建为临时视图，
+    // 其他 SQLite 工具仍可直接读取数据库，旧版本创建的持久视图一并删除
+    conn.execute_batch(
+        "
+    DROP VIEW IF EXISTS main.all_messages;
+    CREATE TEMP VIEW IF NOT EXISTS all_messages AS
+        SELECT id, role, label, file, function, time, process_id, thread_id, line, level,
+               messages, fields, session_id, source, created_at
+        FROM log_messages
+        UNION ALL
+        SELECT id, role, label, file, function, time, process_id, thread_id, line, level,
+               cold_body(block_id, id) AS messages, fields, session_id, source, created_at
+        FROM cold_messages;",
+    )
+    .map_err(|e| format!("创建统一查询视图失败: {}", e))
+}
+
+/// 注册 `cold_body(block_id, id)`，解压对应块并返回该消息的内容，
+/// 最近一次解压的块会被缓存，按 id 顺序扫描时每块只解压一次
+pub(crate) fn register_cold_functions(conn: &Connection) -> Result<(), String> {
+    let mut cache: Option<(i64, HashMap<i64, String>)> = None;
+    conn.create_scalar_function(
+        "cold_body",
+        2,
+        // 块可能被重写，结果不是确定的
+        FunctionFlags::SQLITE_UTF8,
+        move |ctx| {
+            let block_id: i64 = ctx.get(0)?;
+            let id: i64 = ctx.get(1)?;
+            if cache.as_ref().map(|(b, _)| *b) != Some(block_id) {
+                let conn = unsafe { ctx.get_connection()? };
+                let data: Vec<u8> = conn.query_row(
+                    "SELECT data FROM cold_blocks WHERE id = ?1",
+                    params![block_id],
+                    |row| row.get(0),
+                )?;
+                let rows = decode_block(&data)
+                    .map_err(|e| rusqlite::Error::UserFunctionError(e.into()))?;
+                cache = Some((block_id, rows.into_iter().collect()));
+            }
+            Ok(cache
+                .as_ref()
+                .and_then(|(_, rows)| rows.get(&id).cloned())
+                .unwrap_or_else(|| "[]".to_string()))
+        },
+    )
+    .map_err(|e| format!("注册冷存储函数失败: {}", e))
+}
+
+fn encode_block(rows: &[(i64, String)]) -> Result<Vec<u8>, String> {
+    let json = serde_json::to_vec(rows).map_err(|e| e.to_string())?;
+    zstd::encode_all(json.as_slice(), COLD_COMPRESSION_LEVEL).map_err(|e| e.to_string())
+}
+
+fn decode_block(data: &[u8]) -> Result<Vec<(i64, String)>, String> {
+    let json = zstd::decode_all(data).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&json).map_err(|e| e.to_string())
+}
+
+/// 删除冷消息后调用：删除已空的块，并重写部分删除的块，去掉不再引用的内容
+pub(crate) fn compact_cold_blocks(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM cold_blocks WHERE id NOT IN (SELECT DISTINCT block_id FROM cold_messages)",
+        [],
+    )
+    .map_err(|e| format!("回收冷存储失败: {}", e))?;
+    let blocks: Vec<(i64, Vec<u8>)> = {
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, data FROM cold_blocks
+                 WHERE row_count > (SELECT COUNT(*) FROM cold_messages WHERE block_id = cold_blocks.id)",
+            )
+            .map_err(|e| e.to_string())?;
+        let blocks: Result<Vec<_>, _> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect();
+        blocks.map_err(|e| e.to_string())?
+    };
+    for (block_id, data) in blocks {
+        let remaining: HashMap<i64, i64> = {
+            let mut stmt = conn
+                .prepare("SELECT id, time FROM cold_messages WHERE block_id = ?1")
+                .map_err(|e| e.to_string())?;
+            let rows: Result<HashMap<_, _>, _> = stmt
+                .query_map(params![block_id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| e.to_string())?
+                .collect();
+            rows.map_err(|e| e.to_string())?
+        };
+        let bodies: Vec<(i64, String)> = decode_block(&data)?
+            .into_iter()
+            .filter(|(id, _)| remaining.contains_key(id))
+            .collect();
+        conn.execute(
+            "UPDATE cold_blocks
+             SET min_id = ?2, max_id = ?3, min_time = ?4, max_time = ?5, row_count = ?6, raw_size = ?7, data = ?8
+             WHERE id = ?1",
+            params![
+                block_id,
+                remaining.keys().min(),
+                remaining.keys().max(),
+                remaining.values().min(),
+                remaining.values().max(),
+                remaining.len() as i64,
+                bodies.iter().map(|(_, m)| m.len()).sum::<usize>() as i64,
+                encode_block(&bodies)?
+            ],
+        )
+        .map_err(|e| format!("回收冷存储失败: {}", e))?;
+    }
+    Ok(())
+}
+
+impl ColdDB for Arc<Mutex<Option<Connection>>> {
+    fn move_to_cold(&self, before_time: i64, block_size: usize) -> Result<ColdReport, String> {
+        let mut report = ColdReport {
+            moved: 0,
+            blocks: 0,
+        };
+        loop {
+            // 每块一个事务，期间释放锁以便继续写入新消息
+            let mut conn_guard = self.lock().map_err(|e| e.to_string())?;
+            let conn = conn_guard.as_mut().ok_or("数据库未连接".to_string())?;
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+            let rows: Vec<(i64, i64, String)> = {
+                let mut stmt = tx
+                    .prepare(
+                        "SELECT id, time, messages FROM log_messages
+                         WHERE time < ?1 ORDER BY id LIMIT ?2",
+                    )
+                    .map_err(|e| e.to_string())?;
+                let rows: Result<Vec<_>, _> = stmt
+                    .query_map(params![before_time, block_size as i64], |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                    })
+                    .map_err(|e| e.to_string())?
+                    .collect();
+                rows.map_err(|e| e.to_string())?
+            };
+            if rows.is_empty() {
+                break;
+            }
+
+            let min_id = rows.first().unwrap().0;
+            let max_id = rows.last().unwrap().0;
+            let min_time = rows.iter().map(|r| r.1).min().unwrap();
+            let max_time = rows.iter().map(|r| r.1).max().unwrap();
+            let raw_size: usize = rows.iter().map(|r| r.2.len()).sum();
+            let bodies: Vec<(i64, String)> = rows.into_iter().map(|(id, _, m)| (id, m)).collect();
+            let data = encode_block(&bodies)?;
+
+            tx.execute(
+                "INSERT INTO cold_blocks (min_id, max_id, min_time, max_time, row_count, raw_size, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    min_id,
+                    max_id,
+                    min_time,
+                    max_time,
+                    bodies.len() as i64,
+                    raw_size as i64,
+                    data
+                ],
+            )
+            .map_err(|e| format!("写入冷存储失败: {}", e))?;
+            let block_id = tx.last_insert_rowid();
+            tx.execute(
+                "INSERT INTO cold_messages
+                 (id, role, label, file, function, time, process_id, thread_id, line, level, fields, session_id, source, created_at, block_id)
+                 SELECT id, role, label, file, function, time, process_id, thread_id, line, level, fields, session_id, source, created_at, ?1
+                 FROM log_messages WHERE time < ?2 AND id BETWEEN ?3 AND ?4",
+                params![block_id, before_time, min_id, max_id],
+            )
+            .map_err(|e| format!("写入冷存储失败: {}", e))?;
+            tx.execute(
+                "DELETE FROM log_messages WHERE time < ?1 AND id BETWEEN ?2 AND ?3",
+                params![before_time, min_id, max_id],
+            )
+            .map_err(|e| format!("删除已归档消息失败: {}", e))?;
+            tx.commit().map_err(|e| e.to_string())?;
+
+            report.moved += bodies.len();
+            report.blocks += 1;
+        }
+        Ok(report)
+    }
+
+    fn storage_stats(&self) -> Result<StorageStats, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        conn.query_row(
+            "SELECT
+                (SELECT COUNT(*) FROM log_messages),
+                (SELECT COUNT(*) FROM cold_messages),
+                (SELECT COUNT(*) FROM cold_blocks),
+                (SELECT IFNULL(SUM(raw_size), 0) FROM cold_blocks),
+                (SELECT IFNULL(SUM(length(data)), 0) FROM cold_blocks)",
+            [],
+            |row| {
+                Ok(StorageStats {
+                    hot_messages: row.get(0)?,
+                    cold_messages: row.get(1)?,
+                    cold_blocks: row.get(2)?,
+                    cold_raw_bytes: row.get(3)?,
+                    cold_compressed_bytes: row.get(4)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{FilterConfig, MessageDB, MessageField};
+    use crate::testing::TestDb;
+    use msg_server::MessageData;
+
+    fn message(i: usize) -> MessageData {
+        MessageData {
+            role: format!("worker-{}", i % 4),
+            label: format!("stage-{}", i % 7),
+            file: "src/pipeline.cc".to_string(),
+            function: "process_frame".to_string(),
+            time: 1_000_000 + i * 1000,
+            process_id: 100 + i % 2,
+            thread_id: i % 8,
+            line: (i % 300) as i32,
+            level: (i % 5) as i32,
+            messages: vec![
+                format!("processing frame {} of stream {}", i, i % 3),
+                "buffer state: ready, queue depth nominal, no retransmission".to_string(),
+            ],
+            fields: Default::default(),
+        }
+    }
+
+    #[test]
+    fn cold_storage_parity_and_size() {
+        let db = TestDb::new();
+        for i in 0..5000 {
+            db.insert_message(&message(i)).unwrap();
+        }
+        let filters = [
+            r#"{}"#,
+            r#"{"role":{"mode":"Equal","value":"worker-1"}}"#,
+            r#"{"messages":{"mode":"Contain","value":"frame 42"}}"#,
+            r#"{"level":{"min":3,"max":null},"time":{"min":2000000,"max":4000000}}"#,
+        ];
+        let query = |db: &Arc<Mutex<Option<Connection>>>| {
+            filters
+                .iter()
+                .map(|f| {
+                    let config: FilterConfig = serde_json::from_str(f).unwrap();
+                    (
+                        db.filter_messages_count(&config).unwrap(),
+                        db.filter_messages(&config, &MessageField::Time, &200, &0, true)
+                            .unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let before = query(&db);
+
+        // 将前 4000 条移入冷存储，后 1000 条保持热数据
+        let report = db
+            .move_to_cold(1_000_000 + 4000 * 1000, COLD_BLOCK_SIZE)
+            .unwrap();
+        assert_eq!(report.moved, 4000);
+        assert_eq!(report.blocks, 2);
+
+        let stats = db.storage_stats().unwrap();
+        assert_eq!(stats.hot_messages, 1000);
+        assert_eq!(stats.cold_messages, 4000);
+        assert!(stats.cold_compressed_bytes * 5 < stats.cold_raw_bytes);
+
+        assert_eq!(before, query(&db));
+
+        let config: FilterConfig =
+            serde_json::from_str(r#"{"messages":{"mode":"Contain","value":"frame 1"}}"#).unwrap();
+        let expected = db.filter_messages_count(&config).unwrap() as usize;
+        assert_eq!(db.delete_messages(&config).unwrap(), expected);
+        assert_eq!(db.filter_messages_count(&config).unwrap(), 0);
+
+        // 部分删除的块被重写，只保留剩余消息的内容
+        let after = db.storage_stats().unwrap();
+        assert!(after.cold_raw_bytes < stats.cold_raw_bytes);
+        assert_eq!(after.cold_blocks, 2);
+        let counts: Vec<(i64, i64)> = {
+            let conn_guard = db.lock().unwrap();
+            let mut stmt = conn_guard
+                .as_ref()
+                .unwrap()
+                .prepare(
+                    "SELECT row_count, (SELECT COUNT(*) FROM cold_messages WHERE block_id = cold_blocks.id)
+                     FROM cold_blocks",
+                )
+                .unwrap();
+            let counts = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            counts
+        };
+        assert!(counts.iter().all(|(stored, actual)| stored == actual));
+        let config: FilterConfig =
+            serde_json::from_str(r#"{"messages":{"mode":"Contain","value":"frame 2"}}"#).unwrap();
+        let remaining = db
+            .query_messages(&config, &MessageField::Id, &1, &0, false)
+            .unwrap();
+        assert_eq!(remaining[0].messages[0], "processing frame 2 of stream 2");
+
+        // 视图不写入数据库文件，没有注册 cold_body 的连接也能打开
+        let other = Connection::open(db.dir.join("messages.db")).unwrap();
+        let views: i64 = other
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'all_messages'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(views, 0);
+    }
+}
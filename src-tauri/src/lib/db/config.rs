@@ -4,6 +4,8 @@ use std::sync::{Arc, Mutex};
 pub trait Config {
     fn get_config(&self, key: &str) -> Result<Option<String>, String>;
     fn set_config(&self, key: &str, value: &str) -> Result<(), String>;
+    // 在同一个事务中写入多个配置，失败时全部回滚
+    fn set_configs(&self, entries: &[(&str, String)]) -> Result<(), String>;
     fn get_all_configs(&self) -> Result<Vec<(String, String)>, String>;
 }
 
@@ -38,6 +40,22 @@ impl Config for Arc<Mutex<Option<Connection>>> {
         Ok(())
     }
 
+    fn set_configs(&self, entries: &[(&str, String)]) -> Result<(), String> {
+        let mut conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_mut().ok_or("数据库未连接".to_string())?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("开启事务失败: {}", e))?;
+        for (key, value) in entries {
+            tx.execute(
+                "INSERT OR REPLACE INTO app_config (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )
+            .map_err(|e| format!("设置配置失败: {}", e))?;
+        }
+        tx.commit().map_err(|e| format!("提交事务失败: {}", e))
+    }
+
     fn get_all_configs(&self) -> Result<Vec<(String, String)>, String> {
         let conn_guard = self.lock().map_err(|e| e.to_string())?;
         let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
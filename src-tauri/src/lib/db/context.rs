@@ -0,0 +1,164 @@
+use super::messagedb::row_to_message;
+use super::DBMessage;
+use rusqlite::{params, types::Value, Connection};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// 上下文范围：只取与目标消息同一线程、进程或角色的消息
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub enum ContextScope {
+    #[default]
+    All,
+    Thread,
+    Process,
+    Role,
+}
+
+pub trait ContextDB {
+    /// 返回目标消息前后各若干条消息组成的连续窗口（含目标消息），按时间和 id 排序
+    fn get_context(
+        &self,
+        id: i64,
+        before: usize,
+        after: usize,
+        scope: ContextScope,
+    ) -> Result<Vec<DBMessage>, String>;
+}
+
+impl ContextDB for Arc<Mutex<Option<Connection>>> {
+    fn get_context(
+        &self,
+        id: i64,
+        before: usize,
+        after: usize,
+        scope: ContextScope,
+    ) -> Result<Vec<DBMessage>, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let target = conn
+            .query_row(
+                "SELECT id, role, label, file, function, time, process_id, thread_id, line, level, messages, fields
+                FROM all_messages WHERE id = ?1",
+                params![id],
+                row_to_message,
+            )
+            .map_err(|e| format!("消息不存在: {}", e))?;
+
+        // 线程 id 只在同一进程内唯一
+        let mut scope_params: Vec<Value> = Vec::new();
+        let scope_condition = match scope {
+            ContextScope::All => "",
+            ContextScope::Thread => {
+                scope_params.push(Value::Integer(target.process_id as i64));
+                scope_params.push(Value::Integer(target.thread_id as i64));
+                "AND process_id = ? AND thread_id = ?"
+            }
+            ContextScope::Process => {
+                scope_params.push(Value::Integer(target.process_id as i64));
+                "AND process_id = ?"
+            }
+            ContextScope::Role => {
+                scope_params.push(Value::Text(target.role.clone()));
+                "AND role = ?"
+            }
+        };
+
+        let query_side = |op: &str, order: &str, limit: usize| -> Result<Vec<DBMessage>, String> {
+            let query = format!(
+                "SELECT id, role, label, file, function, time, process_id, thread_id, line, level, messages, fields
+                FROM all_messages
+                WHERE (time {op} ? OR (time = ? AND id {op} ?)) {scope}
+                ORDER BY time {order}, id {order} LIMIT ?",
+                op = op,
+                scope = scope_condition,
+                order = order
+            );
+            let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![
+                Box::new(target.time as i64),
+                Box::new(target.time as i64),
+                Box::new(target.id),
+            ];
+            for value in &scope_params {
+                params.push(Box::new(value.clone()));
+            }
+            params.push(Box::new(limit as i64));
+            let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+            let messages: Result<Vec<_>, _> = stmt
+                .query_map(
+                    rusqlite::params_from_iter(params.iter().map(|p| &**p)),
+                    row_to_message,
+                )
+                .map_err(|e| format!("查询上下文失败: {}", e))?
+                .collect();
+            messages.map_err(|e| e.to_string())
+        };
+
+        let mut window = query_side("<", "DESC", before)?;
+        window.reverse();
+        let after = query_side(">", "ASC", after)?;
+        window.push(target);
+        window.extend(after);
+        Ok(window)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{message, TestDb};
+    use msg_server::MessageData;
+
+    // 12 条消息，id 1..=12：每两条共用一个时间戳，两个进程各六条，
+    // 线程 0/1 交替，角色 net/db/ui 轮流
+    fn open() -> TestDb {
+        TestDb::with((0..12).map(|i| MessageData {
+            role: ["net", "db", "ui"][i % 3].to_string(),
+            label: "context".to_string(),
+            time: 1000 + (i / 2) * 10,
+            process_id: 1 + i / 6,
+            thread_id: i % 2,
+            ..message(&format!("message {}", i))
+        }))
+    }
+
+    fn ids(window: &[DBMessage]) -> Vec<usize> {
+        window.iter().map(|m| m.id).collect()
+    }
+
+    #[test]
+    fn window_orders_ties_by_id() {
+        let db = open();
+        let window = db.get_context(6, 3, 3, ContextScope::All).unwrap();
+        assert_eq!(ids(&window), vec![3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn window_is_truncated_at_edges() {
+        let db = open();
+        let window = db.get_context(2, 5, 1, ContextScope::All).unwrap();
+        assert_eq!(ids(&window), vec![1, 2, 3]);
+        let window = db.get_context(12, 1, 5, ContextScope::All).unwrap();
+        assert_eq!(ids(&window), vec![11, 12]);
+        let window = db.get_context(7, 0, 0, ContextScope::All).unwrap();
+        assert_eq!(ids(&window), vec![7]);
+    }
+
+    #[test]
+    fn window_respects_scope() {
+        let db = open();
+        // id 7 属于进程 2、线程 0、角色 net
+        let window = db.get_context(7, 5, 5, ContextScope::Process).unwrap();
+        assert_eq!(ids(&window), vec![7, 8, 9, 10, 11, 12]);
+        let window = db.get_context(7, 5, 5, ContextScope::Thread).unwrap();
+        assert_eq!(ids(&window), vec![7, 9, 11]);
+        let window = db.get_context(7, 5, 5, ContextScope::Role).unwrap();
+        assert_eq!(ids(&window), vec![1, 4, 7, 10]);
+    }
+
+    #[test]
+    fn missing_message_is_an_error() {
+        let db = open();
+        assert!(db.get_context(100, 1, 1, ContextScope::All).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use super::messagedb::{fields_text, get_params, row_to_message};
+use super::{DBMessage, FilterConfig, MessageDB};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+// 每次从数据库读取的行数，读取之间释放连接锁，避免长时间阻塞写入
+const EXPORT_CHUNK_SIZE: i64 = 1000;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Jsonl,
+    Csv,
+    Text,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ExportProgress {
+    pub path: String,
+    pub exported: usize,
+    pub total: usize,
+}
+
+pub trait ExportDB {
+    /// 按 id 顺序分批导出匹配的消息，`progress` 返回 false 时取消导出
+    fn export_messages<W, F>(
+        &self,
+        config: &FilterConfig,
+        format: ExportFormat,
+        writer: &mut W,
+        progress: F,
+    ) -> Result<usize, String>
+    where
+        W: Write,
+        F: FnMut(usize, usize) -> bool;
+    fn export_messages_to_file<F>(
+        &self,
+        config: &FilterConfig,
+        format: ExportFormat,
+        path: &Path,
+        progress: F,
+    ) -> Result<usize, String>
+    where
+        F: FnMut(usize, usize) -> bool;
+}
+
+// 与 message.cc 中 operator<< 的输出格式保持一致
+pub fn format_text_line(message: &DBMessage) -> String {
+    let mut line = format!(
+        "[{}:{}][{}][{}][{}][{}][{}][{}us][level={}] ",
+        message.file,
+        message.line,
+        message.function,
+        message.role,
+        message.label,
+        message.process_id,
+        message.thread_id,
+        message.time,
+        message.level
+    );
+    for m in &message.messages {
+        line.push_str(m);
+        line.push(' ');
+    }
+    line
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn format_csv_line(message: &DBMessage) -> Result<String, String> {
+    let messages = serde_json::to_string(&message.messages).map_err(|e| e.to_string())?;
+    let fields = fields_text(&message.fields)?.unwrap_or_default();
+    Ok([
+        message.id.to_string(),
+        csv_field(&message.role),
+        csv_field(&message.label),
+        csv_field(&message.file),
+        csv_field(&message.function),
+        message.time.to_string(),
+        message.process_id.to_string(),
+        message.thread_id.to_string(),
+        message.line.to_string(),
+        message.level.to_string(),
+        csv_field(&messages),
+        csv_field(&fields),
+    ]
+    .join(","))
+}
+
+/// 导出时读取的一行，除消息外还带有所属会话与来源
+pub(crate) struct ExportRow {
+    pub message: DBMessage,
+    pub session_id: Option<i64>,
+    pub source: Option<String>,
+}
+
+/// 按 id 顺序分批读取匹配的消息，`chunk` 返回错误时停止
+pub(crate) fn read_chunks<F>(
+    db: &Arc<Mutex<Option<Connection>>>,
+    config: &FilterConfig,
+    mut chunk: F,
+) -> Result<(), String>
+where
+    F: FnMut(&[ExportRow]) -> Result<(), String>,
+{
+    let mut last_id: i64 = -1;
+    loop {
+        let rows = {
+            let conn_guard = db.lock().map_err(|e| e.to_string())?;
+            let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+            let (where_clause, mut params) = get_params(config);
+            let where_clause = if where_clause.is_empty() {
+                "WHERE id > ?".to_string()
+            } else {
+                format!("{} AND id > ?", where_clause)
+            };
+            params.push(Box::new(last_id));
+            params.push(Box::new(EXPORT_CHUNK_SIZE));
+
+            let query = format!(
+                "SELECT id, role, label, file, function, time, process_id, thread_id, line, level, messages, fields,
+                        session_id, source
+                 FROM all_messages
+                 {} ORDER BY id ASC LIMIT ?",
+                where_clause
+            );
+            let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+            let rows: Result<Vec<_>, _> = stmt
+                .query_map(
+                    rusqlite::params_from_iter(params.iter().map(|p| &**p)),
+                    |row| {
+                        Ok(ExportRow {
+                            message: row_to_message(row)?,
+                            session_id: row.get(12)?,
+                            source: row.get(13)?,
+                        })
+                    },
+                )
+                .map_err(|e| e.to_string())?
+                .collect();
+            rows.map_err(|e| e.to_string())?
+        };
+        match rows.last() {
+            Some(row) => last_id = row.message.id as i64,
+            None => return Ok(()),
+        }
+        chunk(&rows)?;
+    }
+}
+
+impl ExportDB for Arc<Mutex<Option<Connection>>> {
+    fn export_messages<W, F>(
+        &self,
+        config: &FilterConfig,
+        format: ExportFormat,
+        writer: &mut W,
+        mut progress: F,
+    ) -> Result<usize, String>
+    where
+        W: Write,
+        F: FnMut(usize, usize) -> bool,
+    {
+        let total = self.filter_messages_count(config)? as usize;
+        if let ExportFormat::Csv = format {
+            writeln!(
+                writer,
+                "id,role,label,file,function,time,process_id,thread_id,line,level,messages,fields"
+            )
+            .map_err(|e| format!("写入导出文件失败: {}", e))?;
+        }
+
+        let mut exported = 0;
+        if !progress(exported, total) {
+            return Err("导出已取消".to_string());
+        }
+        read_chunks(self, config, |rows| {
+            for ExportRow { message, .. } in rows {
+                let line = match format {
+                    ExportFormat::Jsonl => {
+                        serde_json::to_string(message).map_err(|e| e.to_string())?
+                    }
+                    ExportFormat::Csv => format_csv_line(message)?,
+                    ExportFormat::Text => format_text_line(message),
+                };
+                writeln!(writer, "{}", line).map_err(|e| format!("写入导出文件失败: {}", e))?;
+            }
+            exported += rows.len();
+            match progress(exported, total) {
+                true => Ok(()),
+                false => Err("导出已取消".to_string()),
+            }
+        })?;
+        writer
+            .flush()
+            .map_err(|e| format!("写入导出文件失败: {}", e))?;
+        Ok(exported)
+    }
+
+    fn export_messages_to_file<F>(
+        &self,
+        config: &FilterConfig,
+        format: ExportFormat,
+        path: &Path,
+        progress: F,
+    ) -> Result<usize, String>
+    where
+        F: FnMut(usize, usize) -> bool,
+    {
+        let file = File::create(path).map_err(|e| format!("创建导出文件失败: {}", e))?;
+        let mut writer = BufWriter::new(file);
+        let result = self.export_messages(config, format, &mut writer, progress);
+        if result.is_err() {
+            // 取消或失败时删除未完成的文件
+            drop(writer);
+            let _ = std::fs::remove_file(path);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{message, TestDb};
+    use msg_server::MessageData;
+
+    // 第一条消息的 label 含有逗号和引号
+    fn open(count: usize) -> TestDb {
+        TestDb::with((0..count).map(|i| MessageData {
+            label: if i == 0 { "a,\"b\"" } else { "c" }.to_string(),
+            time: 1000 + i,
+            ..message(&format!("message {}", i))
+        }))
+    }
+
+    fn export(db: &Arc<Mutex<Option<Connection>>>, format: ExportFormat) -> Vec<String> {
+        let mut out = Vec::new();
+        db.export_messages(&FilterConfig::default(), format, &mut out, |_, _| true)
+            .unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| l.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn export_formats() {
+        let db = open(2);
+        let jsonl = export(&db, ExportFormat::Jsonl);
+        assert_eq!(jsonl.len(), 2);
+        let first: DBMessage = serde_json::from_str(&jsonl[0]).unwrap();
+        assert_eq!(first.messages, ["message 0"]);
+
+        let csv = export(&db, ExportFormat::Csv);
+        assert!(csv[0].starts_with("id,role,label,"));
+        assert!(csv[1].starts_with("1,worker,\"a,\"\"b\"\"\",main.cc,"));
+        assert_eq!(csv.len(), 3);
+
+        let text = export(&db, ExportFormat::Text);
+        assert_eq!(
+            text[1],
+            "[main.cc:3][run][worker][c][1][2][1001us][level=2] message 1 "
+        );
+    }
+
+    #[test]
+    fn cancelled_export_removes_file() {
+        let db = open(EXPORT_CHUNK_SIZE as usize + 1);
+        let path = db.dir.join("cancelled.jsonl");
+        let mut calls = Vec::new();
+        let result = db.export_messages_to_file(
+            &FilterConfig::default(),
+            ExportFormat::Jsonl,
+            &path,
+            |exported, total| {
+                calls.push((exported, total));
+                exported == 0
+            },
+        );
+        assert!(result.is_err());
+        assert_eq!(calls, [(0, 1001), (1000, 1001)]);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,284 @@
+use super::messagedb::fields_text;
+use super::session::insert_session;
+use super::DBMessage;
+use crate::template::{LineParser, LineTemplate};
+use msg_server::{frame::read_frame, MessageData};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum ImportFormat {
+    /// 查看器导出的 JSON Lines
+    Jsonl,
+    /// 以长度前缀分隔的 XCLOG 二进制帧
+    Frames,
+    /// 按正则模板解析的纯文本，不匹配的行追加到上一条消息
+    Text(LineTemplate),
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportReport {
+    pub session_id: i64,
+    pub source: String,
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+pub trait ImportDB {
+    /// 先解析整个文件，再在同一事务中创建会话并写入消息，失败时不留下任何数据
+    fn import_messages(&self, path: &Path, format: &ImportFormat) -> Result<ImportReport, String>;
+    /// 在同一事务中写入一批消息，按顺序返回各条消息的 id
+    fn insert_messages(
+        &self,
+        messages: &[MessageData],
+        session_id: Option<i64>,
+        source: Option<&str>,
+    ) -> Result<Vec<usize>, String>;
+}
+
+impl From<DBMessage> for MessageData {
+    fn from(message: DBMessage) -> Self {
+        MessageData {
+            role: message.role,
+            label: message.label,
+            file: message.file,
+            function: message.function,
+            time: message.time,
+            process_id: message.process_id,
+            thread_id: message.thread_id,
+            line: message.line,
+            level: message.level,
+            messages: message.messages,
+            fields: message.fields,
+        }
+    }
+}
+
+impl ImportDB for Arc<Mutex<Option<Connection>>> {
+    fn import_messages(&self, path: &Path, format: &ImportFormat) -> Result<ImportReport, String> {
+        let file = File::open(path).map_err(|e| format!("打开导入文件失败: {}", e))?;
+        let mut reader = BufReader::new(file);
+        let source = path.to_string_lossy().to_string();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or(source.clone());
+
+        let mut parsed = Vec::new();
+        let mut skipped = 0;
+
+        match format {
+            ImportFormat::Jsonl => {
+                for line in reader.lines() {
+                    let line = line.map_err(|e| format!("读取导入文件失败: {}", e))?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str::<DBMessage>(&line) {
+                        Ok(message) => parsed.push(message.into()),
+                        Err(_) => skipped += 1,
+                    }
+                }
+            }
+            ImportFormat::Frames => loop {
+                match read_frame(&mut reader) {
+                    Ok(Some(data)) => match MessageData::from_bytes(&data) {
+                        Ok(message) => parsed.push(message),
+                        Err(_) => skipped += 1,
+                    },
+                    Ok(None) => break,
+                    // 截断的尾部帧
+                    Err(_) => {
+                        skipped += 1;
+                        break;
+                    }
+                }
+            },
+            ImportFormat::Text(template) => {
+                let parser = LineParser::new(template.clone())?;
+                let mut pending: Option<MessageData> = None;
+                for line in reader.lines() {
+                    let line = line.map_err(|e| format!("读取导入文件失败: {}", e))?;
+                    match parser.parse(&line) {
+                        Some(message) => {
+                            if let Some(previous) = pending.replace(message) {
+                                parsed.push(previous);
+                            }
+                        }
+                        None => match pending.as_mut() {
+                            Some(previous) => previous.messages.push(line),
+                            None => skipped += 1,
+                        },
+                    }
+                }
+                if let Some(previous) = pending {
+                    parsed.push(previous);
+                }
+            }
+        }
+
+        let mut conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_mut().ok_or("数据库未连接".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let session_id = insert_session(&tx, &name, Some(&source))?;
+        let imported = insert_rows(&tx, &parsed, Some(session_id), Some(&source))?.len();
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(ImportReport {
+            session_id,
+            source,
+            imported,
+            skipped,
+        })
+    }
+
+    fn insert_messages(
+        &self,
+        messages: &[MessageData],
+        session_id: Option<i64>,
+        source: Option<&str>,
+    ) -> Result<Vec<usize>, String> {
+        let mut conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_mut().ok_or("数据库未连接".to_string())?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let ids = insert_rows(&tx, messages, session_id, source)?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(ids)
+    }
+}
+
+fn insert_rows(
+    conn: &Connection,
+    messages: &[MessageData],
+    session_id: Option<i64>,
+    source: Option<&str>,
+) -> Result<Vec<usize>, String> {
+    let mut ids = Vec::with_capacity(messages.len());
+    let mut stmt = conn
+                .prepare(
+                    "INSERT INTO log_messages
+                    (role, label, file, function, time, process_id, thread_id, line, level, messages, fields, session_id, source)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                )
+                .map_err(|e| e.to_string())?;
+    for message in messages {
+        let messages_text = serde_json::to_string(&message.messages)
+            .map_err(|e| format!("序列化消息列表失败: {}", e))?;
+        let id = stmt
+            .insert(params![
+                message.role,
+                message.label,
+                message.file,
+                message.function,
+                message.time as i64,
+                message.process_id as i64,
+                message.thread_id as i64,
+                message.line,
+                message.level,
+                messages_text,
+                fields_text(&message.fields)?,
+                session_id,
+                source
+            ])
+            .map_err(|e| format!("插入消息失败: {}", e))?;
+        ids.push(id as usize);
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{FilterConfig, MessageDB, MessageField, SessionDB};
+    use crate::testing::{message, TestDb};
+    use msg_server::frame::write_frame;
+    use std::path::PathBuf;
+
+    fn write(db: &TestDb, name: &str, content: &[u8]) -> PathBuf {
+        let path = db.dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    // 查看器导出的 JSON 行比 MessageData 多一个 id
+    fn exported(text: &str) -> String {
+        let mut value = serde_json::to_value(message(text)).unwrap();
+        value["id"] = 1.into();
+        value.to_string()
+    }
+
+    fn texts(db: &Arc<Mutex<Option<Connection>>>) -> Vec<String> {
+        db.query_messages(&FilterConfig::default(), &MessageField::Id, &100, &0, false)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.messages.join("|"))
+            .collect()
+    }
+
+    #[test]
+    fn import_formats() {
+        let db = TestDb::new();
+
+        let jsonl = format!("{}\nnot json\n\n", exported("from jsonl"));
+        let path = write(&db, "import.jsonl", jsonl.as_bytes());
+        let report = db.import_messages(&path, &ImportFormat::Jsonl).unwrap();
+        assert_eq!((report.imported, report.skipped), (1, 1));
+
+        let mut frames = Vec::new();
+        write_frame(
+            &mut frames,
+            &message("from frames").to_ffi().unwrap().encode().unwrap(),
+        )
+        .unwrap();
+        // 截断的尾部帧
+        frames.extend_from_slice(&9u64.to_le_bytes());
+        frames.extend_from_slice(b"abc");
+        let path = write(&db, "import.bin", &frames);
+        let report = db.import_messages(&path, &ImportFormat::Frames).unwrap();
+        assert_eq!((report.imported, report.skipped), (1, 1));
+
+        let text = "orphan\n1000 [warn] from text\n  continued\n";
+        let path = write(&db, "import.log", text.as_bytes());
+        let template = LineTemplate {
+            pattern: String::new(),
+            format: Some("{time} [{level}] {message}".to_string()),
+            time_format: None,
+            role: None,
+            label: None,
+            level: None,
+        };
+        let report = db
+            .import_messages(&path, &ImportFormat::Text(template))
+            .unwrap();
+        assert_eq!((report.imported, report.skipped), (1, 1));
+
+        assert_eq!(
+            texts(&db),
+            ["from jsonl", "from frames", "from text|  continued"]
+        );
+        assert_eq!(db.list_sessions().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn failed_import_leaves_no_session() {
+        let db = TestDb::new();
+        db.lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .execute_batch(
+                "CREATE TEMP TRIGGER reject BEFORE INSERT ON log_messages
+                 BEGIN SELECT RAISE(ABORT, 'rejected'); END;",
+            )
+            .unwrap();
+        let path = write(&db, "import.jsonl", exported("lost").as_bytes());
+        assert!(db.import_messages(&path, &ImportFormat::Jsonl).is_err());
+        assert!(db.list_sessions().unwrap().is_empty());
+    }
+}
@@ -1,8 +1,11 @@
+use super::cold::compact_cold_blocks;
+use crate::rules::MessageStyles;
 use msg_server::MessageData;
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DBMessage {
     pub id: usize,
     pub role: String,
@@ -15,8 +18,14 @@ pub struct DBMessage {
     pub line: i32,
     pub level: i32,
     pub messages: Vec<String>,
+    /// 来源附带的键值对，例如 syslog 的结构化数据
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+    /// 规则匹配得到的样式，仅在返回给界面时填充
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub styles: Option<MessageStyles>,
 }
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum PatternMode {
     Equal,
     Contain,
@@ -24,20 +33,22 @@ pub enum PatternMode {
     End,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StringPattern {
     pub mode: PatternMode,
     pub value: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NumberRange {
     pub min: Option<i64>,
     pub max: Option<i64>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct FilterConfig {
+    #[serde(default)]
+    pub id: Option<NumberRange>,
     pub label: Option<StringPattern>,
     pub role: Option<StringPattern>,
     pub file: Option<StringPattern>,
@@ -49,7 +60,7 @@ pub struct FilterConfig {
     pub line: Option<NumberRange>,
     pub messages: Option<StringPattern>,
 }
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum MessageField {
     Id,
     Role,
@@ -63,8 +74,97 @@ pub enum MessageField {
     Level,
 }
 
+impl MessageField {
+    pub fn column(&self) -> &'static str {
+        match self {
+            MessageField::Id => "id",
+            MessageField::Role => "role",
+            MessageField::Label => "label",
+            MessageField::File => "file",
+            MessageField::Function => "function",
+            MessageField::Time => "time",
+            MessageField::ProcessId => "process_id",
+            MessageField::ThreadId => "thread_id",
+            MessageField::Line => "line",
+            MessageField::Level => "level",
+        }
+    }
+}
+
+impl StringPattern {
+    // 与 SQL 中的 LIKE 保持一致：Equal 区分大小写，其余模式对 ASCII 不区分大小写
+    pub fn matches(&self, value: &str) -> bool {
+        match self.mode {
+            PatternMode::Equal => value == self.value,
+            PatternMode::Contain => value
+                .to_ascii_lowercase()
+                .contains(&self.value.to_ascii_lowercase()),
+            PatternMode::Start => value
+                .to_ascii_lowercase()
+                .starts_with(&self.value.to_ascii_lowercase()),
+            PatternMode::End => value
+                .to_ascii_lowercase()
+                .ends_with(&self.value.to_ascii_lowercase()),
+        }
+    }
+}
+
+impl NumberRange {
+    pub fn contains(&self, value: i64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+impl FilterConfig {
+    /// 没有任何条件，匹配全部消息
+    pub fn is_empty(&self) -> bool {
+        self.id.is_none()
+            && self.label.is_none()
+            && self.role.is_none()
+            && self.file.is_none()
+            && self.function.is_none()
+            && self.level.is_none()
+            && self.time.is_none()
+            && self.process_id.is_none()
+            && self.thread_id.is_none()
+            && self.line.is_none()
+            && self.messages.is_none()
+    }
+
+    /// 在内存中判断消息是否满足过滤条件，结果与 `get_params` 生成的 SQL 条件一致
+    pub fn matches(&self, message: &DBMessage) -> bool {
+        let string_ok = |pattern: &Option<StringPattern>, value: &str| {
+            pattern.as_ref().is_none_or(|p| p.matches(value))
+        };
+        let number_ok = |range: &Option<NumberRange>, value: i64| {
+            range.as_ref().is_none_or(|r| r.contains(value))
+        };
+        number_ok(&self.id, message.id as i64)
+            && string_ok(&self.label, &message.label)
+            && string_ok(&self.role, &message.role)
+            && string_ok(&self.file, &message.file)
+            && string_ok(&self.function, &message.function)
+            && number_ok(&self.level, message.level as i64)
+            && number_ok(&self.time, message.time as i64)
+            && number_ok(&self.process_id, message.process_id as i64)
+            && number_ok(&self.thread_id, message.thread_id as i64)
+            && number_ok(&self.line, message.line as i64)
+            && self.messages.as_ref().is_none_or(|p| {
+                // 数据库中 messages 以 JSON 文本保存
+                p.matches(&serde_json::to_string(&message.messages).unwrap_or_default())
+            })
+    }
+}
+
 pub trait MessageDB {
     fn insert_message(&self, message: &MessageData) -> Result<usize, String>;
+    // 插入实时消息并归入指定会话，例如由查看器启动的子进程输出
+    fn insert_message_into(
+        &self,
+        message: &MessageData,
+        session_id: Option<i64>,
+        source: Option<&str>,
+    ) -> Result<usize, String>;
     fn get_messages(&self, limit: i32, offset: i32, desc: bool) -> Result<String, String>;
     fn get_message_count(&self) -> Result<i32, String>;
     fn filter_messages(
@@ -75,8 +175,16 @@ pub trait MessageDB {
         offset: &i32,
         desc: bool,
     ) -> Result<String, String>;
+    fn query_messages(
+        &self,
+        config: &FilterConfig,
+        order_by: &MessageField,
+        limit: &i32,
+        offset: &i32,
+        desc: bool,
+    ) -> Result<Vec<DBMessage>, String>;
     fn filter_messages_count(&self, config: &FilterConfig) -> Result<i32, String>;
-    fn get_distinct(&self, field: &MessageField) -> Result<String, String>;
+    fn last_message_id(&self) -> Result<usize, String>;
     fn delete_messages(&self, config: &FilterConfig) -> Result<usize, String>;
 }
 // 辅助函数：构建字符串条件
@@ -84,23 +192,25 @@ fn build_string_condition(
     column: &str,
     pattern: &StringPattern,
 ) -> (String, Box<dyn rusqlite::ToSql>) {
+    // 与 `StringPattern::matches` 一致，按字面匹配
+    let like = format!("{} LIKE ? ESCAPE '\\'", column);
+    let value = escape_like(&pattern.value);
     match pattern.mode {
         PatternMode::Equal => (format!("{} = ?", column), Box::new(pattern.value.clone())),
-        PatternMode::Contain => (
-            format!("{} LIKE ?", column),
-            Box::new(format!("%{}%", pattern.value)),
-        ),
-        PatternMode::Start => (
-            format!("{} LIKE ?", column),
-            Box::new(format!("{}%", pattern.value)),
-        ),
-        PatternMode::End => (
-            format!("{} LIKE ?", column),
-            Box::new(format!("%{}", pattern.value)),
-        ),
+        PatternMode::Contain => (like, Box::new(format!("%{}%", value))),
+        PatternMode::Start => (like, Box::new(format!("{}%", value))),
+        PatternMode::End => (like, Box::new(format!("%{}", value))),
     }
 }
 
+// 转义 LIKE 通配符，配合 `ESCAPE '\'` 使用
+pub(crate) fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
 // 辅助函数：构建数字范围条件
 fn build_number_range_condition(
     column: &str,
@@ -127,11 +237,22 @@ fn build_number_range_condition(
     }
 }
 
-fn get_params(config: &FilterConfig) -> (String, Vec<Box<dyn rusqlite::ToSql>>) {
+pub(crate) fn get_params(config: &FilterConfig) -> (String, Vec<Box<dyn rusqlite::ToSql>>) {
     let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
     let mut conditions = Vec::new();
 
     // 构建过滤条件
+    if let Some(id_range) = config.id.as_ref() {
+        let (condition, min_param, max_param) = build_number_range_condition("id", id_range);
+        conditions.push(condition);
+        if let Some(min) = min_param {
+            params.push(Box::new(min));
+        }
+        if let Some(max) = max_param {
+            params.push(Box::new(max));
+        }
+    }
+
     if let Some(label_pattern) = config.label.as_ref() {
         let (condition, param) = build_string_condition("label", label_pattern);
         conditions.push(condition);
@@ -229,8 +350,50 @@ fn get_params(config: &FilterConfig) -> (String, Vec<Box<dyn rusqlite::ToSql>>)
     (where_clause, params)
 }
 
+// 辅助函数：fields 以 JSON 对象保存，为空时存为 NULL
+pub(crate) fn fields_text(fields: &BTreeMap<String, String>) -> Result<Option<String>, String> {
+    if fields.is_empty() {
+        return Ok(None);
+    }
+    serde_json::to_string(fields)
+        .map(Some)
+        .map_err(|e| format!("序列化字段失败: {}", e))
+}
+
+// 辅助函数：将查询结果行转换为 DBMessage，列顺序与 SELECT 语句保持一致
+pub(crate) fn row_to_message(row: &rusqlite::Row) -> rusqlite::Result<DBMessage> {
+    Ok(DBMessage {
+        id: row.get(0)?,
+        role: row.get(1)?,
+        label: row.get(2)?,
+        file: row.get(3)?,
+        function: row.get(4)?,
+        time: row.get::<_, i64>(5)? as usize,
+        process_id: row.get::<_, i64>(6)? as usize,
+        thread_id: row.get::<_, i64>(7)? as usize,
+        line: row.get(8)?,
+        level: row.get(9)?,
+        messages: serde_json::from_str(row.get::<_, String>(10)?.as_str()).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(10, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        fields: row
+            .get::<_, Option<String>>(11)?
+            .and_then(|f| serde_json::from_str(&f).ok())
+            .unwrap_or_default(),
+        styles: None,
+    })
+}
+
 impl MessageDB for Arc<Mutex<Option<Connection>>> {
     fn insert_message(&self, message: &MessageData) -> Result<usize, String> {
+        self.insert_message_into(message, None, None)
+    }
+    fn insert_message_into(
+        &self,
+        message: &MessageData,
+        session_id: Option<i64>,
+        source: Option<&str>,
+    ) -> Result<usize, String> {
         let conn_guard = self.lock().map_err(|e| e.to_string())?;
         let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
 
@@ -240,8 +403,8 @@ impl MessageDB for Arc<Mutex<Option<Connection>>> {
 
         conn.execute(
             "INSERT INTO log_messages 
-            (role, label, file, function, time, process_id, thread_id, line, level, messages) 
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            (role, label, file, function, time, process_id, thread_id, line, level, messages, fields, session_id, source) 
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
             params![
                 message.role,
                 message.label,
@@ -252,22 +415,25 @@ impl MessageDB for Arc<Mutex<Option<Connection>>> {
                 message.thread_id as i64,
                 message.line,
                 message.level,
-                messages_text
+                messages_text,
+                fields_text(&message.fields)?,
+                session_id,
+                source
             ],
         )
-        .map_err(|e| format!("插入消息失败: {}", e))
+        .map_err(|e| format!("插入消息失败: {}", e))?;
+        Ok(conn.last_insert_rowid() as usize)
     }
     fn get_messages(&self, limit: i32, offset: i32, desc: bool) -> Result<String, String> {
         let conn_guard = self.lock().map_err(|e| e.to_string())?;
         let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
-        println!("select with: lim:{}, off:{}, desc:{}", limit, offset, desc);
 
         let order_clause = if desc { "DESC" } else { "ASC" };
 
         let mut stmt = conn
             .prepare(&format!(
-                "SELECT id, role, label, file, function, time, process_id, thread_id, line, level, messages 
-                 FROM log_messages 
+                "SELECT id, role, label, file, function, time, process_id, thread_id, line, level, messages, fields 
+                 FROM all_messages 
                  ORDER BY id {}
                  LIMIT ?1 OFFSET ?2",
                 order_clause
@@ -275,26 +441,11 @@ impl MessageDB for Arc<Mutex<Option<Connection>>> {
             .map_err(|e| e.to_string())?;
 
         let messages_iter = stmt
-            .query_map(params![limit, offset], |row| {
-                Ok(DBMessage {
-                    id: row.get(0)?,
-                    role: row.get(1)?,
-                    label: row.get(2)?,
-                    file: row.get(3)?,
-                    function: row.get(4)?,
-                    time: row.get::<_, i64>(5)? as usize,
-                    process_id: row.get::<_, i64>(6)? as usize,
-                    thread_id: row.get::<_, i64>(7)? as usize,
-                    line: row.get(8)?,
-                    level: row.get(9)?,
-                    messages: serde_json::from_str(row.get::<_, String>(10)?.as_str()).unwrap(),
-                })
-            })
+            .query_map(params![limit, offset], row_to_message)
             .map_err(|e| e.to_string())?;
 
         let messages: Result<Vec<_>, _> = messages_iter.collect();
         let messages = messages.map_err(|e| e.to_string())?;
-        println!("success to get message {:?}", messages);
         serde_json::to_string(&messages).map_err(|e| e.to_string())
     }
 
@@ -303,7 +454,7 @@ impl MessageDB for Arc<Mutex<Option<Connection>>> {
         let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
 
         let mut stmt = conn
-            .prepare("SELECT COUNT(*) FROM log_messages")
+            .prepare("SELECT COUNT(*) FROM all_messages")
             .map_err(|e| e.to_string())?;
 
         let count = stmt
@@ -320,69 +471,52 @@ impl MessageDB for Arc<Mutex<Option<Connection>>> {
         offset: &i32,
         desc: bool,
     ) -> Result<String, String> {
+        let messages = self.query_messages(config, order_by, limit, offset, desc)?;
+        serde_json::to_string(&messages).map_err(|e| e.to_string())
+    }
+
+    fn query_messages(
+        &self,
+        config: &FilterConfig,
+        order_by: &MessageField,
+        limit: &i32,
+        offset: &i32,
+        desc: bool,
+    ) -> Result<Vec<DBMessage>, String> {
         let conn_guard = self.lock().map_err(|e| e.to_string())?;
         let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
 
-        let (where_clause, mut params) = get_params(&config);
+        let (where_clause, mut params) = get_params(config);
 
         let order_clause = if desc { "DESC" } else { "ASC" };
 
         let mut query = format!(
-            "SELECT id, role, label, file, function, time, process_id, thread_id, line, level, messages 
-             FROM log_messages 
+            "SELECT id, role, label, file, function, time, process_id, thread_id, line, level, messages, fields 
+             FROM all_messages 
              {}",
             where_clause
         );
 
         query.push_str(" ORDER BY ");
-        match order_by {
-            MessageField::Id => query.push_str("id"),
-            MessageField::Role => query.push_str("role"),
-            MessageField::Label => query.push_str("label"),
-            MessageField::File => query.push_str("file"),
-            MessageField::Function => query.push_str("function"),
-            MessageField::Time => query.push_str("time"),
-            MessageField::ProcessId => query.push_str("process_id"),
-            MessageField::ThreadId => query.push_str("thread_id"),
-            MessageField::Line => query.push_str("line"),
-            MessageField::Level => query.push_str("level"),
-        }
-        query.push_str(" ");
+        query.push_str(order_by.column());
+        query.push(' ');
         query.push_str(order_clause);
 
         query.push_str(" LIMIT ? OFFSET ?");
         params.push(Box::new(limit));
         params.push(Box::new(offset));
 
-        println!("Filter query: {}", query);
-
         let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
 
         let messages_iter = stmt
             .query_map(
                 rusqlite::params_from_iter(params.iter().map(|p| &**p)),
-                |row| {
-                    Ok(DBMessage {
-                        id: row.get(0)?,
-                        role: row.get(1)?,
-                        label: row.get(2)?,
-                        file: row.get(3)?,
-                        function: row.get(4)?,
-                        time: row.get::<_, i64>(5)? as usize,
-                        process_id: row.get::<_, i64>(6)? as usize,
-                        thread_id: row.get::<_, i64>(7)? as usize,
-                        line: row.get(8)?,
-                        level: row.get(9)?,
-                        messages: serde_json::from_str(row.get::<_, String>(10)?.as_str()).unwrap(),
-                    })
-                },
+                row_to_message,
             )
             .map_err(|e| e.to_string())?;
 
         let messages: Result<Vec<_>, _> = messages_iter.collect();
-        let messages = messages.map_err(|e| e.to_string())?;
-
-        serde_json::to_string(&messages).map_err(|e| e.to_string())
+        messages.map_err(|e| e.to_string())
     }
 
     // 实现 filter_messages_count 函数
@@ -391,11 +525,9 @@ impl MessageDB for Arc<Mutex<Option<Connection>>> {
         let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
 
         // 获取条件语句和参数
-        let (where_clause, params) = get_params(&config);
-
-        let query = format!("SELECT COUNT(*) FROM log_messages {}", where_clause);
+        let (where_clause, params) = get_params(config);
 
-        println!("Count query: {}", query);
+        let query = format!("SELECT COUNT(*) FROM all_messages {}", where_clause);
 
         let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
 
@@ -409,90 +541,52 @@ impl MessageDB for Arc<Mutex<Option<Connection>>> {
         Ok(count)
     }
 
-    fn get_distinct(&self, field: &MessageField) -> Result<String, String> {
+    fn last_message_id(&self) -> Result<usize, String> {
         let conn_guard = self.lock().map_err(|e| e.to_string())?;
         let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
 
-        // 根据字段确定要查询的列名
-        let column = match field {
-            MessageField::Id => "id",
-            MessageField::Role => "role",
-            MessageField::Label => "label",
-            MessageField::File => "file",
-            MessageField::Function => "function",
-            MessageField::Time => "time",
-            MessageField::ProcessId => "process_id",
-            MessageField::ThreadId => "thread_id",
-            MessageField::Line => "line",
-            MessageField::Level => "level",
-        };
-
-        let query = format!(
-            "SELECT DISTINCT {} FROM log_messages ORDER BY {}",
-            column, column
-        );
-
-        let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
-
-        // 根据字段类型处理不同的返回值
-        match field {
-            MessageField::Id => {
-                let ids: Result<Vec<u64>, _> = stmt
-                    .query_map([], |row| row.get(0))
-                    .map_err(|e| e.to_string())?
-                    .collect();
-                serde_json::to_string(&ids.map_err(|e| e.to_string())?).map_err(|e| e.to_string())
-            }
-            MessageField::Role
-            | MessageField::Label
-            | MessageField::File
-            | MessageField::Function => {
-                let strings: Result<Vec<String>, _> = stmt
-                    .query_map([], |row| row.get(0))
-                    .map_err(|e| e.to_string())?
-                    .collect();
-                serde_json::to_string(&strings.map_err(|e| e.to_string())?)
-                    .map_err(|e| e.to_string())
-            }
-            MessageField::Time | MessageField::ProcessId | MessageField::ThreadId => {
-                let numbers: Result<Vec<i64>, _> = stmt
-                    .query_map([], |row| row.get(0))
-                    .map_err(|e| e.to_string())?
-                    .collect();
-                serde_json::to_string(&numbers.map_err(|e| e.to_string())?)
-                    .map_err(|e| e.to_string())
-            }
-            MessageField::Line | MessageField::Level => {
-                let numbers: Result<Vec<i32>, _> = stmt
-                    .query_map([], |row| row.get(0))
-                    .map_err(|e| e.to_string())?
-                    .collect();
-                serde_json::to_string(&numbers.map_err(|e| e.to_string())?)
-                    .map_err(|e| e.to_string())
-            }
-        }
+        conn.query_row("SELECT IFNULL(MAX(id), 0) FROM all_messages", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .map(|id| id as usize)
+        .map_err(|e| e.to_string())
     }
 
     fn delete_messages(&self, config: &FilterConfig) -> Result<usize, String> {
-        let conn_guard = self.lock().map_err(|e| e.to_string())?;
-        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+        let mut conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_mut().ok_or("数据库未连接".to_string())?;
+        // 冷热数据与压缩块在同一事务中删除
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
 
         // 获取条件语句和参数
-        let (where_clause, params) = get_params(&config);
+        let (where_clause, params) = get_params(config);
 
         // 构建删除语句
         let query = format!("DELETE FROM log_messages {}", where_clause);
+        // 冷存储没有 messages 列，通过视图匹配 id
+        let cold_query = format!(
+            "DELETE FROM cold_messages WHERE id IN (SELECT id FROM all_messages {})",
+            where_clause
+        );
 
-        println!("Delete query: {}", query);
-
-        // 执行删除操作
-        let rows_deleted = conn
+        // 执行删除操作，先删除冷存储，否则视图中的热数据已经不存在
+        let cold_deleted = tx
+            .execute(
+                &cold_query,
+                rusqlite::params_from_iter(params.iter().map(|p| &**p)),
+            )
+            .map_err(|e| format!("删除消息失败: {}", e))?;
+        let rows_deleted = tx
             .execute(
                 &query,
                 rusqlite::params_from_iter(params.iter().map(|p| &**p)),
             )
             .map_err(|e| format!("删除消息失败: {}", e))?;
+        if cold_deleted > 0 {
+            compact_cold_blocks(&tx)?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
 
-        Ok(rows_deleted)
+        Ok(rows_deleted + cold_deleted)
     }
 }
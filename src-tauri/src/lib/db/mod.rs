@@ -1,24 +1,52 @@
+mod aggregate;
+mod archive;
+mod bookmark;
+mod cold;
 mod config;
+mod context;
+mod export;
+mod import;
 mod messagedb;
+mod project;
+mod search;
+mod session;
+pub use aggregate::*;
+pub use archive::*;
+pub use bookmark::*;
+pub use cold::*;
 pub use config::*;
+pub use context::*;
+pub use export::*;
+pub use import::*;
 pub use messagedb::*;
+pub use project::*;
 use rusqlite::Connection;
+pub use search::*;
+pub use session::*;
 use std::{
-    path::PathBuf,
+    path::Path,
     sync::{Arc, Mutex},
 };
 
 pub trait DB {
-    fn connect(&self, path: &PathBuf) -> Result<(), String>;
+    fn connect(&self, path: &Path) -> Result<(), String>;
     fn is_connected(&self) -> bool;
+    fn set_read_only(&self, read_only: bool) -> Result<(), String>;
 }
 
 impl DB for Arc<Mutex<Option<Connection>>> {
-    fn connect(&self, path: &PathBuf) -> Result<(), String> {
+    fn connect(&self, path: &Path) -> Result<(), String> {
         let mut conn = self.lock().unwrap();
         if conn.is_none() {
-            std::fs::create_dir_all(&path.parent().unwrap()).map_err(|e| e.to_string())?;
-            let new_conn = Connection::open(&path).unwrap();
+            std::fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+            let new_conn = Connection::open(path).map_err(|e| format!("打开数据库失败: {}", e))?;
+            // WAL 模式允许守护进程写入的同时由桌面端或命令行读取同一个数据库
+            new_conn
+                .pragma_update(None, "journal_mode", "WAL")
+                .map_err(|e| format!("设置日志模式失败: {}", e))?;
+            new_conn
+                .busy_timeout(std::time::Duration::from_secs(5))
+                .map_err(|e| e.to_string())?;
             new_conn
                 .execute(
                     "
@@ -35,11 +63,37 @@ impl DB for Arc<Mutex<Option<Connection>>> {
         line INTEGER DEFAULT NULL, -- 行号，对应Message.line
         level INTEGER NOT NULL, -- 日志级别，对应Message.level
         messages TEXT NOT NULL, -- 合并后的消息内容
+        fields TEXT DEFAULT NULL, -- 附加键值对的 JSON 对象，对应Message.fields
+        session_id INTEGER DEFAULT NULL, -- 所属会话，对应sessions.id
+        source TEXT DEFAULT NULL, -- 消息来源，例如导入的文件路径
         created_at DATETIME DEFAULT CURRENT_TIMESTAMP -- 记录插入时间，便于查询[8](@ref)
     );",
                     [],
                 )
                 .map_err(|e| format!("创建表失败: {}", e))?;
+            // 旧版本数据库缺少的列
+            ensure_column(
+                &new_conn,
+                "log_messages",
+                "session_id",
+                "INTEGER DEFAULT NULL",
+            )?;
+            ensure_column(&new_conn, "log_messages", "source", "TEXT DEFAULT NULL")?;
+            ensure_column(&new_conn, "log_messages", "fields", "TEXT DEFAULT NULL")?;
+            // 创建会话表
+            new_conn
+                .execute(
+                    "
+    CREATE TABLE IF NOT EXISTS
+    sessions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL, -- 会话名称
+        source TEXT DEFAULT NULL, -- 会话来源
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    );",
+                    [],
+                )
+                .map_err(|e| format!("创建会话表失败: {}", e))?;
             // 创建配置表
             new_conn
                 .execute(
@@ -63,6 +117,12 @@ impl DB for Arc<Mutex<Option<Connection>>> {
                 )
                 .map_err(|e| format!("插入默认配置失败: {}", e))?;
 
+            create_bookmark_table(&new_conn)?;
+            create_search_table(&new_conn)?;
+            // 冷存储表与统一查询视图
+            register_cold_functions(&new_conn)?;
+            create_cold_tables(&new_conn)?;
+
             *conn = Some(new_conn);
         }
         Ok(())
@@ -71,11 +131,46 @@ impl DB for Arc<Mutex<Option<Connection>>> {
     fn is_connected(&self) -> bool {
         self.lock().unwrap().is_some()
     }
+
+    fn set_read_only(&self, read_only: bool) -> Result<(), String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+        conn.execute_batch(if read_only {
+            "PRAGMA query_only = ON"
+        } else {
+            "PRAGMA query_only = OFF"
+        })
+        .map_err(|e| e.to_string())
+    }
+}
+
+// 辅助函数：为已存在的表补充新增的列
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| e.to_string())?;
+    let columns: Result<Vec<String>, _> = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| e.to_string())?
+        .collect();
+    if !columns
+        .map_err(|e| e.to_string())?
+        .iter()
+        .any(|c| c == column)
+    {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl),
+            [],
+        )
+        .map_err(|e| format!("更新表结构失败: {}", e))?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::path::PathBuf;
 
     #[test]
     fn test() {
@@ -83,6 +178,7 @@ mod test {
         db.connect(&PathBuf::from("xclogger.db")).unwrap();
         // println!("{:?}", db.get_messages(100, 0));
         let config = FilterConfig {
+            id: None,
             label: Some(StringPattern {
                 mode: PatternMode::Start,
                 value: "data1".to_string(),
@@ -110,15 +206,16 @@ mod test {
     fn get_distinct() {
         let db = Arc::new(Mutex::new(Option::<Connection>::None));
         db.connect(&PathBuf::from("xclogger.db")).unwrap();
-        let labels = db.get_distinct(&MessageField::Label).unwrap();
+        let query = DistinctQuery::default();
+        let labels = db.get_distinct(&MessageField::Label, &query).unwrap();
         println!("{:?}", labels);
-        let files = db.get_distinct(&MessageField::File).unwrap();
+        let files = db.get_distinct(&MessageField::File, &query).unwrap();
         println!("{:?}", files);
-        let roles = db.get_distinct(&MessageField::Role).unwrap();
+        let roles = db.get_distinct(&MessageField::Role, &query).unwrap();
         println!("{:?}", roles);
-        let process_ids = db.get_distinct(&MessageField::ProcessId).unwrap();
+        let process_ids = db.get_distinct(&MessageField::ProcessId, &query).unwrap();
         println!("{:?}", process_ids);
-        let thread_ids = db.get_distinct(&MessageField::ThreadId).unwrap();
+        let thread_ids = db.get_distinct(&MessageField::ThreadId, &query).unwrap();
         println!("{:?}", thread_ids);
     }
 }
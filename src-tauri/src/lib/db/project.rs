@@ -0,0 +1,271 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Project {
+    pub name: String,
+    pub db_path: String,
+    pub active: bool,
+}
+
+/// 项目注册表，保存在独立的 SQLite 文件中，每个项目拥有自己的日志数据库；
+/// 监听地址和规则等设置保存在项目数据库中
+pub trait ProjectDB {
+    fn connect_registry(&self, path: &Path, default_project: &Project) -> Result<(), String>;
+    fn list_projects(&self) -> Result<Vec<Project>, String>;
+    fn get_project(&self, name: &str) -> Result<Option<Project>, String>;
+    fn get_active_project(&self) -> Result<Project, String>;
+    fn create_project(&self, project: &Project) -> Result<(), String>;
+    fn set_active_project(&self, name: &str) -> Result<(), String>;
+    fn rename_project(&self, name: &str, new_name: &str) -> Result<(), String>;
+    fn delete_project(&self, name: &str) -> Result<Project, String>;
+    /// 在 dir 下为新项目挑选数据库文件，避开已存在的文件和已登记的路径
+    fn new_db_path(&self, dir: &Path, stem: &str) -> Result<PathBuf, String>;
+}
+
+fn row_to_project(row: &rusqlite::Row) -> rusqlite::Result<Project> {
+    Ok(Project {
+        name: row.get(0)?,
+        db_path: row.get(1)?,
+        active: row.get(2)?,
+    })
+}
+
+impl ProjectDB for Arc<Mutex<Option<Connection>>> {
+    fn connect_registry(&self, path: &Path, default_project: &Project) -> Result<(), String> {
+        let mut conn = self.lock().map_err(|e| e.to_string())?;
+        if conn.is_some() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+        let new_conn = Connection::open(path).map_err(|e| e.to_string())?;
+        new_conn
+            .execute(
+                "
+    CREATE TABLE IF NOT EXISTS
+    projects (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE, -- 项目名称
+        db_path TEXT NOT NULL, -- 项目日志数据库文件路径
+        active INTEGER NOT NULL DEFAULT 0, -- 是否为当前项目
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    );",
+                [],
+            )
+            .map_err(|e| format!("创建项目表失败: {}", e))?;
+        // 首次启动时将原有的数据库登记为默认项目
+        let count: i64 = new_conn
+            .query_row("SELECT COUNT(*) FROM projects", [], |row| row.get(0))
+            .map_err(|e| format!("查询项目失败: {}", e))?;
+        if count == 0 {
+            new_conn
+                .execute(
+                    "INSERT INTO projects (name, db_path, active) VALUES (?1, ?2, 1)",
+                    params![default_project.name, default_project.db_path],
+                )
+                .map_err(|e| format!("插入默认项目失败: {}", e))?;
+        }
+        *conn = Some(new_conn);
+        Ok(())
+    }
+
+    fn list_projects(&self) -> Result<Vec<Project>, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let mut stmt = conn
+            .prepare("SELECT name, db_path, active FROM projects ORDER BY id")
+            .map_err(|e| format!("准备查询语句失败: {}", e))?;
+        let projects: Result<Vec<_>, _> = stmt
+            .query_map([], row_to_project)
+            .map_err(|e| format!("查询项目失败: {}", e))?
+            .collect();
+        projects.map_err(|e| format!("收集项目结果失败: {}", e))
+    }
+
+    fn get_project(&self, name: &str) -> Result<Option<Project>, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        match conn.query_row(
+            "SELECT name, db_path, active FROM projects WHERE name = ?1",
+            params![name],
+            row_to_project,
+        ) {
+            Ok(project) => Ok(Some(project)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(format!("查询项目失败: {}", e)),
+        }
+    }
+
+    fn get_active_project(&self) -> Result<Project, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        conn.query_row(
+            "SELECT name, db_path, active FROM projects WHERE active = 1 ORDER BY id LIMIT 1",
+            [],
+            row_to_project,
+        )
+        .map_err(|e| format!("查询当前项目失败: {}", e))
+    }
+
+    fn create_project(&self, project: &Project) -> Result<(), String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        // 两个项目共用一个数据库会互相覆盖设置和消息
+        let owner: Option<String> = conn
+            .query_row(
+                "SELECT name FROM projects WHERE db_path = ?1",
+                params![project.db_path],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("查询项目失败: {}", e))?;
+        if let Some(owner) = owner {
+            return Err(format!("数据库已属于项目 {}: {}", owner, project.db_path));
+        }
+        conn.execute(
+            "INSERT INTO projects (name, db_path, active) VALUES (?1, ?2, 0)",
+            params![project.name, project.db_path],
+        )
+        .map_err(|e| format!("创建项目失败: {}", e))?;
+        Ok(())
+    }
+
+    fn set_active_project(&self, name: &str) -> Result<(), String> {
+        let mut conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_mut().ok_or("数据库未连接".to_string())?;
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute("UPDATE projects SET active = 0", [])
+            .map_err(|e| format!("更新项目失败: {}", e))?;
+        let updated = tx
+            .execute(
+                "UPDATE projects SET active = 1 WHERE name = ?1",
+                params![name],
+            )
+            .map_err(|e| format!("更新项目失败: {}", e))?;
+        if updated == 0 {
+            return Err(format!("项目不存在: {}", name));
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn rename_project(&self, name: &str, new_name: &str) -> Result<(), String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let updated = conn
+            .execute(
+                "UPDATE projects SET name = ?2 WHERE name = ?1",
+                params![name, new_name],
+            )
+            .map_err(|e| format!("重命名项目失败: {}", e))?;
+        if updated == 0 {
+            return Err(format!("项目不存在: {}", name));
+        }
+        Ok(())
+    }
+
+    fn delete_project(&self, name: &str) -> Result<Project, String> {
+        let project = self
+            .get_project(name)?
+            .ok_or(format!("项目不存在: {}", name))?;
+        if project.active {
+            return Err("不能删除当前项目".to_string());
+        }
+
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+        conn.execute("DELETE FROM projects WHERE name = ?1", params![name])
+            .map_err(|e| format!("删除项目失败: {}", e))?;
+        Ok(project)
+    }
+
+    fn new_db_path(&self, dir: &Path, stem: &str) -> Result<PathBuf, String> {
+        // 不同的项目名可能得到相同的文件名，例如 "a b" 和 "a_b"
+        let taken: Vec<PathBuf> = self
+            .list_projects()?
+            .into_iter()
+            .map(|p| PathBuf::from(p.db_path))
+            .collect();
+        let mut db_path = dir.join(format!("{}.db", stem));
+        let mut suffix = 1;
+        while db_path.exists() || taken.contains(&db_path) {
+            db_path = dir.join(format!("{}-{}.db", stem, suffix));
+            suffix += 1;
+        }
+        Ok(db_path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn project(name: &str, db_path: &str) -> Project {
+        Project {
+            name: name.to_string(),
+            db_path: db_path.to_string(),
+            active: false,
+        }
+    }
+
+    fn open(dir: &TempDir) -> Arc<Mutex<Option<Connection>>> {
+        let registry = Arc::new(Mutex::new(Option::<Connection>::None));
+        registry
+            .connect_registry(&dir.join("projects.db"), &project("default", "default.db"))
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    fn registry_lifecycle() {
+        let dir = TempDir::new();
+        let registry = open(&dir);
+        assert!(registry.get_active_project().unwrap().active);
+        registry.create_project(&project("app", "app.db")).unwrap();
+        assert!(registry
+            .create_project(&project("app", "other.db"))
+            .is_err());
+        assert!(registry.create_project(&project("copy", "app.db")).is_err());
+
+        registry.set_active_project("app").unwrap();
+        assert_eq!(registry.get_active_project().unwrap().name, "app");
+        assert!(registry.set_active_project("missing").is_err());
+        assert_eq!(registry.get_active_project().unwrap().name, "app");
+        assert!(registry.delete_project("app").is_err());
+
+        registry.rename_project("default", "old").unwrap();
+        assert!(registry.rename_project("default", "again").is_err());
+        assert_eq!(
+            registry.delete_project("old").unwrap().db_path,
+            "default.db"
+        );
+        assert_eq!(registry.list_projects().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn new_db_path_skips_registered_paths() {
+        let temp = TempDir::new();
+        let registry = open(&temp);
+        let dir = temp.join("projects");
+        let first = registry.new_db_path(&dir, "a_b").unwrap();
+        assert_eq!(first, dir.join("a_b.db"));
+        registry
+            .create_project(&project("a b", &first.to_string_lossy()))
+            .unwrap();
+        // 文件尚未创建，但路径已被 "a b" 登记
+        assert_eq!(
+            registry.new_db_path(&dir, "a_b").unwrap(),
+            dir.join("a_b-1.db")
+        );
+    }
+}
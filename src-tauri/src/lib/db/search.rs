@@ -0,0 +1,273 @@
+use super::{DBMessage, FilterConfig, MessageField};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedSearch {
+    /// 新建时忽略，由数据库分配
+    #[serde(default)]
+    pub id: i64,
+    pub name: String,
+    pub config: FilterConfig,
+    pub order_by: MessageField,
+    #[serde(default)]
+    pub desc: bool,
+    /// 固定为标签页
+    #[serde(default)]
+    pub pinned: bool,
+    /// 作为实时告警来源，新消息命中时推送 saved-search-alert 事件
+    #[serde(default)]
+    pub alert: bool,
+    #[serde(default)]
+    pub created_at: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SearchAlert {
+    pub search_id: i64,
+    pub name: String,
+    pub message: DBMessage,
+}
+
+pub trait SearchDB {
+    fn create_search(&self, search: &SavedSearch) -> Result<SavedSearch, String>;
+    fn list_searches(&self) -> Result<Vec<SavedSearch>, String>;
+    fn get_search(&self, id: i64) -> Result<Option<SavedSearch>, String>;
+    fn update_search(&self, search: &SavedSearch) -> Result<(), String>;
+    fn delete_search(&self, id: i64) -> Result<(), String>;
+}
+
+pub(crate) fn create_search_table(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "
+    CREATE TABLE IF NOT EXISTS
+    saved_searches (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE, -- 搜索名称
+        config TEXT NOT NULL, -- FilterConfig 的 JSON
+        order_by TEXT NOT NULL, -- 排序字段，MessageField 的 JSON
+        descending INTEGER NOT NULL DEFAULT 0,
+        pinned INTEGER NOT NULL DEFAULT 0,
+        alert INTEGER NOT NULL DEFAULT 0,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    );",
+        [],
+    )
+    .map_err(|e| format!("创建搜索表失败: {}", e))?;
+    Ok(())
+}
+
+fn row_to_search(row: &rusqlite::Row) -> rusqlite::Result<SavedSearch> {
+    let json_err = |i, e: serde_json::Error| {
+        rusqlite::Error::FromSqlConversionFailure(i, rusqlite::types::Type::Text, Box::new(e))
+    };
+    Ok(SavedSearch {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        config: serde_json::from_str(&row.get::<_, String>(2)?).map_err(|e| json_err(2, e))?,
+        order_by: serde_json::from_str(&row.get::<_, String>(3)?).map_err(|e| json_err(3, e))?,
+        desc: row.get(4)?,
+        pinned: row.get(5)?,
+        alert: row.get(6)?,
+        created_at: row.get(7)?,
+    })
+}
+
+const SEARCH_COLUMNS: &str = "id, name, config, order_by, descending, pinned, alert, created_at";
+
+impl SearchDB for Arc<Mutex<Option<Connection>>> {
+    fn create_search(&self, search: &SavedSearch) -> Result<SavedSearch, String> {
+        if search.name.trim().is_empty() {
+            return Err("搜索名称不能为空".to_string());
+        }
+        let id = {
+            let conn_guard = self.lock().map_err(|e| e.to_string())?;
+            let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+            conn.execute(
+                "INSERT INTO saved_searches (name, config, order_by, descending, pinned, alert)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    search.name,
+                    serde_json::to_string(&search.config).map_err(|e| e.to_string())?,
+                    serde_json::to_string(&search.order_by).map_err(|e| e.to_string())?,
+                    search.desc,
+                    search.pinned,
+                    search.alert
+                ],
+            )
+            .map_err(|e| format!("保存搜索失败: {}", e))?;
+            conn.last_insert_rowid()
+        };
+        self.get_search(id)?.ok_or(format!("搜索不存在: {}", id))
+    }
+
+    fn list_searches(&self) -> Result<Vec<SavedSearch>, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM saved_searches ORDER BY pinned DESC, name",
+                SEARCH_COLUMNS
+            ))
+            .map_err(|e| format!("准备查询语句失败: {}", e))?;
+        let searches: Result<Vec<_>, _> = stmt
+            .query_map([], row_to_search)
+            .map_err(|e| format!("查询搜索失败: {}", e))?
+            .collect();
+        searches.map_err(|e| format!("收集搜索结果失败: {}", e))
+    }
+
+    fn get_search(&self, id: i64) -> Result<Option<SavedSearch>, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        match conn.query_row(
+            &format!(
+                "SELECT {} FROM saved_searches WHERE id = ?1",
+                SEARCH_COLUMNS
+            ),
+            params![id],
+            row_to_search,
+        ) {
+            Ok(search) => Ok(Some(search)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(format!("查询搜索失败: {}", e)),
+        }
+    }
+
+    fn update_search(&self, search: &SavedSearch) -> Result<(), String> {
+        if search.name.trim().is_empty() {
+            return Err("搜索名称不能为空".to_string());
+        }
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let updated = conn
+            .execute(
+                "UPDATE saved_searches
+                SET name = ?2, config = ?3, order_by = ?4, descending = ?5, pinned = ?6, alert = ?7
+                WHERE id = ?1",
+                params![
+                    search.id,
+                    search.name,
+                    serde_json::to_string(&search.config).map_err(|e| e.to_string())?,
+                    serde_json::to_string(&search.order_by).map_err(|e| e.to_string())?,
+                    search.desc,
+                    search.pinned,
+                    search.alert
+                ],
+            )
+            .map_err(|e| format!("更新搜索失败: {}", e))?;
+        if updated == 0 {
+            return Err(format!("搜索不存在: {}", search.id));
+        }
+        Ok(())
+    }
+
+    fn delete_search(&self, id: i64) -> Result<(), String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let deleted = conn
+            .execute("DELETE FROM saved_searches WHERE id = ?1", params![id])
+            .map_err(|e| format!("删除搜索失败: {}", e))?;
+        if deleted == 0 {
+            return Err(format!("搜索不存在: {}", id));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{PatternMode, StringPattern};
+    use crate::testing::TestDb;
+
+    fn search(name: &str, role: &str) -> SavedSearch {
+        SavedSearch {
+            id: 0,
+            name: name.to_string(),
+            config: FilterConfig {
+                role: Some(StringPattern {
+                    mode: PatternMode::Equal,
+                    value: role.to_string(),
+                }),
+                ..Default::default()
+            },
+            order_by: MessageField::Id,
+            desc: false,
+            pinned: false,
+            alert: false,
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn searches_round_trip() {
+        let db = TestDb::new();
+        let created = db.create_search(&search("network", "net")).unwrap();
+        assert!(created.id > 0);
+        assert!(!created.created_at.is_empty());
+        let loaded = db.get_search(created.id).unwrap().unwrap();
+        assert_eq!(loaded.name, "network");
+        assert_eq!(loaded.config.role.unwrap().value, "net");
+
+        let mut renamed = created.clone();
+        renamed.name = "net only".to_string();
+        renamed.desc = true;
+        db.update_search(&renamed).unwrap();
+        let loaded = db.get_search(created.id).unwrap().unwrap();
+        assert_eq!((loaded.name.as_str(), loaded.desc), ("net only", true));
+
+        db.delete_search(created.id).unwrap();
+        assert!(db.get_search(created.id).unwrap().is_none());
+        assert!(db.delete_search(created.id).is_err());
+        renamed.id = 999;
+        assert!(db.update_search(&renamed).is_err());
+    }
+
+    #[test]
+    fn names_are_unique_and_required() {
+        let db = TestDb::new();
+        let first = db.create_search(&search("network", "net")).unwrap();
+        assert!(db.create_search(&search("network", "disk")).is_err());
+        assert!(db.create_search(&search(" ", "disk")).is_err());
+        let second = db.create_search(&search("disk", "disk")).unwrap();
+        let mut clash = second.clone();
+        clash.name = first.name.clone();
+        assert!(db.update_search(&clash).is_err());
+    }
+
+    #[test]
+    fn pinned_searches_come_first_and_flags_persist() {
+        let db = TestDb::new();
+        db.create_search(&search("a", "a")).unwrap();
+        let mut b = db.create_search(&search("b", "b")).unwrap();
+        b.pinned = true;
+        b.alert = true;
+        db.update_search(&b).unwrap();
+        let names: Vec<_> = db
+            .list_searches()
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.name, s.pinned, s.alert))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("b".to_string(), true, true),
+                ("a".to_string(), false, false)
+            ]
+        );
+
+        b.pinned = false;
+        b.alert = false;
+        db.update_search(&b).unwrap();
+        let loaded = db.get_search(b.id).unwrap().unwrap();
+        assert!(!loaded.pinned && !loaded.alert);
+    }
+}
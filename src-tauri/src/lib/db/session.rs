@@ -0,0 +1,59 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    pub id: i64,
+    pub name: String,
+    pub source: Option<String>,
+    pub created_at: String,
+}
+
+pub trait SessionDB {
+    fn create_session(&self, name: &str, source: Option<&str>) -> Result<i64, String>;
+    fn list_sessions(&self) -> Result<Vec<Session>, String>;
+}
+
+// 也可以传入事务，使会话与其中的消息一同提交
+pub(crate) fn insert_session(
+    conn: &Connection,
+    name: &str,
+    source: Option<&str>,
+) -> Result<i64, String> {
+    conn.execute(
+        "INSERT INTO sessions (name, source) VALUES (?1, ?2)",
+        params![name, source],
+    )
+    .map_err(|e| format!("创建会话失败: {}", e))?;
+    Ok(conn.last_insert_rowid())
+}
+
+impl SessionDB for Arc<Mutex<Option<Connection>>> {
+    fn create_session(&self, name: &str, source: Option<&str>) -> Result<i64, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+        insert_session(conn, name, source)
+    }
+
+    fn list_sessions(&self) -> Result<Vec<Session>, String> {
+        let conn_guard = self.lock().map_err(|e| e.to_string())?;
+        let conn = conn_guard.as_ref().ok_or("数据库未连接".to_string())?;
+
+        let mut stmt = conn
+            .prepare("SELECT id, name, source, created_at FROM sessions ORDER BY id")
+            .map_err(|e| format!("准备查询语句失败: {}", e))?;
+        let sessions: Result<Vec<_>, _> = stmt
+            .query_map([], |row| {
+                Ok(Session {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    source: row.get(2)?,
+                    created_at: row.get(3)?,
+                })
+            })
+            .map_err(|e| format!("查询会话失败: {}", e))?
+            .collect();
+        sessions.map_err(|e| format!("收集会话结果失败: {}", e))
+    }
+}
@@ -0,0 +1,727 @@
+use crate::db::{
+    AggregateDB, Config, DBMessage, DistinctQuery, FieldCount, FilterConfig, MessageDB,
+    MessageField,
+};
+use crate::pipeline::MessagePipeline;
+use crate::query::parse_field;
+use crate::settings::{apply_retention, validate_http_access, validate_http_address, Settings};
+use axum::body::Bytes;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use msg_server::json::parse_batch;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+// 查询命令的 JSON 版本，供无法使用 Tauri `invoke` 的工具调用。
+// 请求体与命令参数结构相同，例如 `FilterConfig`。设置了 `http_token` 时，
+// 每个请求都需要 `Authorization: Bearer <token>`；浏览器无法为 WebSocket 设置
+// 该请求头，因此 `/ws` 也接受 `?token=<token>`。未设置令牌时，`/ws` 拒绝
+// `Origin` 不是本服务的升级请求，其他网页无法读取实时消息。
+//
+//   POST   /api/messages/query   QueryRequest        -> [DBMessage]
+//   POST   /api/messages/count   FilterConfig        -> 条数
+//   DELETE /api/messages?all=    FilterConfig        -> 删除条数，空过滤条件
+//                                                       需要 all=true
+//   GET    /api/distinct/{field}?prefix=&limit=&by_frequency= -> [FieldCount]
+//   POST   /api/distinct/{field} DistinctQuery       -> [FieldCount]
+//   GET    /api/config                               -> Settings
+//   GET    /api/config/{key}                         -> string | null
+//   PUT    /api/config/{key}     原始值
+//   POST   /api/ingest           MessageData | [MessageData] -> IngestReport
+//   GET    /ws?token=                                -> WebSocket 实时推送
+
+const DEFAULT_QUERY_LIMIT: i32 = 1000;
+// /api/distinct 返回值数量的默认值和上限
+const MAX_DISTINCT_LIMIT: usize = 1000;
+// 新的 WebSocket 客户端发送过滤条件的等待时间，超时后推送全部消息
+const STREAM_FILTER_WAIT: Duration = Duration::from_millis(500);
+// 慢速 WebSocket 客户端最多排队的批次数，超出后丢弃新批次
+const STREAM_QUEUE: usize = 64;
+const DEFAULT_STREAM_THROTTLE_MS: u64 = 100;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct QueryRequest {
+    #[serde(default)]
+    pub config: FilterConfig,
+    #[serde(default = "default_order_by")]
+    pub order_by: MessageField,
+    #[serde(default = "default_limit")]
+    pub limit: i32,
+    #[serde(default)]
+    pub offset: i32,
+    #[serde(default)]
+    pub desc: bool,
+}
+
+/// `/ws` 客户端可选的第一个文本帧，之后的帧替换过滤条件
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct StreamRequest {
+    #[serde(default)]
+    pub config: FilterConfig,
+    #[serde(default)]
+    pub throttle_ms: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct DeleteParams {
+    /// 过滤条件为空时必须设置，与 `xclogger delete --all` 相同
+    #[serde(default)]
+    pub all: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+struct TokenParams {
+    #[serde(default)]
+    token: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct IngestRejection {
+    /// 记录在提交批次中的位置
+    pub index: usize,
+    pub error: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct IngestReport {
+    pub accepted: usize,
+    pub rejected: Vec<IngestRejection>,
+}
+
+fn default_order_by() -> MessageField {
+    MessageField::Id
+}
+
+fn default_limit() -> i32 {
+    DEFAULT_QUERY_LIMIT
+}
+
+pub(crate) struct ApiError(StatusCode, String);
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        ApiError(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+pub(crate) type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// rusqlite 调用会阻塞，数据库操作放到异步工作线程之外执行
+pub(crate) async fn blocking<T, F>(f: F) -> ApiResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| ApiError::from(e.to_string()))?
+        .map(Json)
+        .map_err(ApiError::from)
+}
+
+type ApiState = Arc<MessagePipeline>;
+
+async fn query_messages(
+    State(pipeline): State<ApiState>,
+    Json(request): Json<QueryRequest>,
+) -> ApiResult<Vec<DBMessage>> {
+    blocking(move || {
+        let mut messages = pipeline.db.query_messages(
+            &request.config,
+            &request.order_by,
+            &request.limit,
+            &request.offset,
+            request.desc,
+        )?;
+        pipeline.annotate(&mut messages);
+        Ok(messages)
+    })
+    .await
+}
+
+async fn count_messages(
+    State(pipeline): State<ApiState>,
+    Json(config): Json<FilterConfig>,
+) -> ApiResult<i32> {
+    blocking(move || pipeline.db.filter_messages_count(&config)).await
+}
+
+async fn delete_messages(
+    State(pipeline): State<ApiState>,
+    Query(params): Query<DeleteParams>,
+    Json(config): Json<FilterConfig>,
+) -> ApiResult<usize> {
+    if config.is_empty() && !params.all {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            "refusing to delete every message without all=true".to_string(),
+        ));
+    }
+    blocking(move || pipeline.db.delete_messages(&config)).await
+}
+
+async fn distinct(
+    pipeline: ApiState,
+    field: String,
+    mut query: DistinctQuery,
+) -> ApiResult<Vec<FieldCount>> {
+    let field = parse_field(&field)
+        .ok_or_else(|| ApiError(StatusCode::BAD_REQUEST, format!("unknown field: {}", field)))?;
+    query.limit = Some(
+        query
+            .limit
+            .unwrap_or(MAX_DISTINCT_LIMIT)
+            .min(MAX_DISTINCT_LIMIT),
+    );
+    blocking(move || pipeline.db.get_distinct(&field, &query)).await
+}
+
+async fn get_distinct(
+    State(pipeline): State<ApiState>,
+    Path(field): Path<String>,
+    Query(query): Query<DistinctQuery>,
+) -> ApiResult<Vec<FieldCount>> {
+    distinct(pipeline, field, query).await
+}
+
+// 范围过滤条件放不进查询字符串，POST 接收完整的查询
+async fn scoped_distinct(
+    State(pipeline): State<ApiState>,
+    Path(field): Path<String>,
+    Json(query): Json<DistinctQuery>,
+) -> ApiResult<Vec<FieldCount>> {
+    distinct(pipeline, field, query).await
+}
+
+async fn get_settings(State(pipeline): State<ApiState>) -> ApiResult<Settings> {
+    blocking(move || Settings::load(&pipeline.db)).await
+}
+
+async fn config_get(
+    State(pipeline): State<ApiState>,
+    Path(key): Path<String>,
+) -> ApiResult<Option<String>> {
+    blocking(move || pipeline.db.get_config(&key)).await
+}
+
+// 设置项先写入完整的 `Settings` 并校验，无效值返回 400，其余键原样保存
+async fn config_set(
+    State(pipeline): State<ApiState>,
+    Path(key): Path<String>,
+    value: String,
+) -> ApiResult<()> {
+    let bad_request = |e| ApiError(StatusCode::BAD_REQUEST, e);
+    let current = {
+        let pipeline = pipeline.clone();
+        blocking(move || Settings::load(&pipeline.db)).await?.0
+    };
+    let mut settings = current.clone();
+    if !settings.set(&key, &value).map_err(bad_request)? {
+        return blocking(move || pipeline.db.set_config(&key, &value)).await;
+    }
+    settings.validate().map_err(bad_request)?;
+    blocking(move || {
+        settings.save(&pipeline.db)?;
+        if current.retention != settings.retention {
+            apply_retention(&pipeline.db, &settings.retention)?;
+        }
+        pipeline.settings_changed(&settings)
+    })
+    .await
+}
+
+// 记录使用 `msg_server::json` 的宽松 `MessageData` JSON，time、process_id
+// 等缺失字段在那里补齐。有效记录在同一个事务中写入。
+async fn ingest(
+    State(pipeline): State<ApiState>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<IngestReport>), ApiError> {
+    let batch = parse_batch(&body).map_err(|e| ApiError(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let mut report = IngestReport::default();
+    let mut valid = Vec::with_capacity(batch.len());
+    for (index, record) in batch.into_iter().enumerate() {
+        match record {
+            Ok(data) => valid.push(data),
+            Err(e) => report.rejected.push(IngestRejection {
+                index,
+                error: e.to_string(),
+            }),
+        }
+    }
+    if !valid.is_empty() {
+        let Json(stored) = blocking(move || pipeline.ingest_batch(&valid)).await?;
+        report.accepted = stored.len();
+    }
+    let status = if report.accepted == 0 && !report.rejected.is_empty() {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(report)))
+}
+
+async fn stream(ws: WebSocketUpgrade, State(pipeline): State<ApiState>) -> Response {
+    ws.on_upgrade(move |socket| stream_messages(socket, pipeline))
+}
+
+fn subscribe(
+    pipeline: &MessagePipeline,
+    request: StreamRequest,
+    queue: &mpsc::Sender<Vec<DBMessage>>,
+) -> u64 {
+    let queue = queue.clone();
+    pipeline.subscriptions.subscribe(
+        request.config,
+        request.throttle_ms.unwrap_or(DEFAULT_STREAM_THROTTLE_MS),
+        move |batch| match queue.try_send(batch) {
+            // 跟不上的客户端丢弃批次，不阻塞消息接收
+            Err(mpsc::error::TrySendError::Full(_)) => true,
+            result => result.is_ok(),
+        },
+    )
+}
+
+fn error_frame(error: impl std::fmt::Display) -> Message {
+    Message::Text(serde_json::json!({ "error": error.to_string() }).to_string())
+}
+
+// 每条匹配的消息作为一个 `DBMessage` JSON 文本帧发送
+async fn stream_messages(mut socket: WebSocket, pipeline: ApiState) {
+    let request = match tokio::time::timeout(STREAM_FILTER_WAIT, socket.recv()).await {
+        Ok(Some(Ok(Message::Text(text)))) => match serde_json::from_str(&text) {
+            Ok(request) => request,
+            Err(e) => {
+                let _ = socket.send(error_frame(e)).await;
+                return;
+            }
+        },
+        Ok(None) | Ok(Some(Err(_))) | Ok(Some(Ok(Message::Close(_)))) => return,
+        _ => StreamRequest::default(),
+    };
+    let (queue, mut batches) = mpsc::channel(STREAM_QUEUE);
+    let mut id = subscribe(&pipeline, request, &queue);
+    'stream: loop {
+        tokio::select! {
+            batch = batches.recv() => {
+                let Some(batch) = batch else { break };
+                for message in batch {
+                    let Ok(text) = serde_json::to_string(&message) else { continue };
+                    if socket.send(Message::Text(text)).await.is_err() {
+                        break 'stream;
+                    }
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(request) => {
+                        pipeline.subscriptions.unsubscribe(id);
+                        id = subscribe(&pipeline, request, &queue);
+                    }
+                    Err(e) => {
+                        if socket.send(error_frame(e)).await.is_err() {
+                            break;
+                        }
+                    }
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+    pipeline.subscriptions.unsubscribe(id);
+}
+
+async fn require_token(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| value == &*token);
+    // 查询参数中的令牌只用于 WebSocket，其他接口不应把令牌放进 URL
+    let query = request.uri().path() == "/ws"
+        && Query::<TokenParams>::try_from_uri(request.uri())
+            .ok()
+            .and_then(|Query(params)| params.token)
+            .is_some_and(|value| value == *token);
+    if !bearer && !query {
+        return ApiError(
+            StatusCode::UNAUTHORIZED,
+            "missing or invalid token".to_string(),
+        )
+        .into_response();
+    }
+    next.run(request).await
+}
+
+// 未设置令牌时只能靠来源区分其他网页；不发送 Origin 的客户端（如脚本）放行
+async fn require_same_origin(request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    let same_origin = match headers.get(header::ORIGIN) {
+        None => true,
+        Some(origin) => origin
+            .to_str()
+            .ok()
+            .and_then(|origin| origin.split_once("://"))
+            .is_some_and(|(_, origin)| Some(origin) == host),
+    };
+    if !same_origin {
+        return ApiError(
+            StatusCode::FORBIDDEN,
+            "cross-origin WebSocket needs a token".to_string(),
+        )
+        .into_response();
+    }
+    next.run(request).await
+}
+
+fn router(pipeline: ApiState, token: Option<&str>) -> Router {
+    let ws = match token {
+        Some(_) => get(stream),
+        None => get(stream).layer(middleware::from_fn(require_same_origin)),
+    };
+    let router = Router::new()
+        .route("/api/messages/query", post(query_messages))
+        .route("/api/messages/count", post(count_messages))
+        .route("/api/messages", axum::routing::delete(delete_messages))
+        .route(
+            "/api/distinct/:field",
+            get(get_distinct).post(scoped_distinct),
+        )
+        .route("/api/config", get(get_settings))
+        .route("/api/config/:key", get(config_get).put(config_set))
+        .route("/api/ingest", post(ingest))
+        .route("/ws", ws)
+        .with_state(pipeline);
+    match token {
+        Some(token) => router.layer(middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            require_token,
+        )),
+        None => router,
+    }
+}
+
+/// 内嵌的 HTTP 接口，运行在独立线程和 Tokio 运行时上，析构时停止
+pub struct HttpServer {
+    address: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HttpServer {
+    /// 立即绑定 `address`，端口被占用时直接返回错误。
+    /// 非回环地址需要 `token`。
+    pub fn start(
+        address: &str,
+        token: Option<&str>,
+        pipeline: Arc<MessagePipeline>,
+    ) -> Result<Self, String> {
+        validate_http_access(address, token)?;
+        let token = token.filter(|token| !token.is_empty());
+        Self::serve(address, router(pipeline, token))
+    }
+
+    /// 共用这套实现的其他服务使用相同的生命周期，例如 [`crate::otlp`]
+    pub(crate) fn serve(address: &str, router: Router) -> Result<Self, String> {
+        validate_http_address(address)?;
+        let listener = std::net::TcpListener::bind(address)
+            .map_err(|e| format!("failed to bind {}: {}", address, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let address = listener.local_addr().map_err(|e| e.to_string())?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("xclogger-http")
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            runtime.block_on(async move {
+                let result = async {
+                    let listener = tokio::net::TcpListener::from_std(listener)?;
+                    axum::serve(listener, router)
+                        .with_graceful_shutdown(async {
+                            let _ = stopped.await;
+                        })
+                        .await
+                }
+                .await;
+                if let Err(e) = result {
+                    eprintln!("HTTP server stopped: {}", e);
+                }
+            });
+        });
+        Ok(Self {
+            address,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{message, TestDb};
+    use axum::body::Body;
+    use msg_server::MessageData;
+    use tower::ServiceExt;
+
+    // 6 条消息，级别 0..6
+    fn open() -> TestDb {
+        TestDb::with((0..6).map(|level| MessageData {
+            role: "net".to_string(),
+            label: "http".to_string(),
+            time: 1000 + level as usize,
+            level,
+            ..message(&format!("message {}", level))
+        }))
+    }
+
+    async fn send(
+        router: &Router,
+        method: &str,
+        uri: &str,
+        body: &str,
+    ) -> (StatusCode, serde_json::Value) {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn query_count_and_delete() {
+        let db = open();
+        let router = router(db.pipeline(), None);
+        let (status, messages) = send(
+            &router,
+            "POST",
+            "/api/messages/query",
+            r#"{"config":{"level":{"min":2,"max":null}},"limit":3,"desc":true}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let levels: Vec<_> = messages
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["level"].as_i64().unwrap())
+            .collect();
+        assert_eq!(levels, [5, 4, 3]);
+
+        let level = r#"{"level":{"min":4,"max":null}}"#;
+        let (status, count) = send(&router, "POST", "/api/messages/count", level).await;
+        assert_eq!((status, count), (StatusCode::OK, 2.into()));
+
+        let (status, _) = send(&router, "DELETE", "/api/messages", "{}").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, deleted) = send(&router, "DELETE", "/api/messages", level).await;
+        assert_eq!((status, deleted), (StatusCode::OK, 2.into()));
+        let (status, deleted) = send(&router, "DELETE", "/api/messages?all=true", "{}").await;
+        assert_eq!((status, deleted), (StatusCode::OK, 4.into()));
+    }
+
+    #[tokio::test]
+    async fn config_round_trip() {
+        let db = open();
+        let router = router(db.pipeline(), None);
+        let (status, _) = send(&router, "PUT", "/api/config/theme", "dark").await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, value) = send(&router, "GET", "/api/config/theme", "").await;
+        assert_eq!(value, "dark");
+        let (status, settings) = send(&router, "GET", "/api/config", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(settings["theme"], "dark");
+        let (_, value) = send(&router, "GET", "/api/config/missing", "").await;
+        assert!(value.is_null());
+    }
+
+    #[tokio::test]
+    async fn config_set_validates_settings() {
+        let db = open();
+        let router = router(db.pipeline(), None);
+        let (status, _) = send(&router, "PUT", "/api/config/address", "nonsense").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&router, "PUT", "/api/config/theme", "purple").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = send(&router, "PUT", "/api/config/http_token", "secret").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&router, "PUT", "/api/config/http_address", "0.0.0.0:7878").await;
+        assert_eq!(status, StatusCode::OK);
+        // 非回环地址上不能清空令牌
+        let (status, _) = send(&router, "PUT", "/api/config/http_token", "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, value) = send(&router, "GET", "/api/config/http_token", "").await;
+        assert_eq!(value, "secret");
+    }
+
+    #[tokio::test]
+    async fn token_guards_every_route() {
+        let db = open();
+        let router = router(db.pipeline(), Some("secret"));
+        let (status, _) = send(&router, "POST", "/api/messages/count", "{}").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let request = axum::http::Request::builder()
+            .method("POST")
+            .uri("/api/messages/count")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .body(Body::from("{}"))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert!(validate_http_access("127.0.0.1:7878", None).is_ok());
+        assert!(validate_http_access("0.0.0.0:7878", None).is_err());
+        assert!(validate_http_access("0.0.0.0:7878", Some("")).is_err());
+        assert!(validate_http_access("0.0.0.0:7878", Some("secret")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn ingest_reports_rejected_records() {
+        let db = open();
+        let pipeline = db.pipeline();
+        let router = router(pipeline.clone(), None);
+        let (status, report) = send(
+            &router,
+            "POST",
+            "/api/ingest",
+            r#"[{"messages":"a"},{"role":1},{"messages":["b"],"level":3}]"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["accepted"], 2);
+        assert_eq!(report["rejected"][0]["index"], 1);
+        assert_eq!(pipeline.db.get_message_count().unwrap(), 8);
+
+        let (status, report) = send(&router, "POST", "/api/ingest", r#"{"role":1}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(report["accepted"], 0);
+        let (status, _) = send(&router, "POST", "/api/ingest", "not json").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(pipeline.db.get_message_count().unwrap(), 8);
+    }
+
+    type Client = tungstenite::WebSocket<tungstenite::stream::MaybeTlsStream<std::net::TcpStream>>;
+
+    fn connect(address: SocketAddr, query: &str, origin: Option<&str>) -> Result<Client, u16> {
+        use tungstenite::client::IntoClientRequest;
+        let mut request = format!("ws://{}/ws{}", address, query)
+            .into_client_request()
+            .unwrap();
+        if let Some(origin) = origin {
+            request
+                .headers_mut()
+                .insert(header::ORIGIN, origin.parse().unwrap());
+        }
+        match tungstenite::connect(request) {
+            Ok((socket, _)) => Ok(socket),
+            Err(tungstenite::Error::Http(response)) => Err(response.status().as_u16()),
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn websocket_streams_matching_messages() {
+        let db = open();
+        let pipeline = db.pipeline();
+        let mut server =
+            HttpServer::start("127.0.0.1:0", Some("secret"), pipeline.clone()).unwrap();
+        let address = server.address();
+
+        assert_eq!(connect(address, "", None).err(), Some(401));
+        assert_eq!(connect(address, "?token=wrong", None).err(), Some(401));
+        let mut socket = connect(address, "?token=secret", None).unwrap();
+        socket
+            .send(tungstenite::Message::text(
+                r#"{"config":{"level":{"min":4,"max":null}},"throttle_ms":0}"#,
+            ))
+            .unwrap();
+        // 等待服务端按过滤条件订阅
+        std::thread::sleep(Duration::from_millis(200));
+        for level in 0..6 {
+            pipeline
+                .ingest(&MessageData {
+                    level,
+                    ..message(&format!("live {}", level))
+                })
+                .unwrap();
+        }
+        if let tungstenite::stream::MaybeTlsStream::Plain(stream) = socket.get_ref() {
+            stream
+                .set_read_timeout(Some(Duration::from_millis(500)))
+                .unwrap();
+        }
+        let mut received = Vec::new();
+        while let Ok(frame) = socket.read() {
+            if let tungstenite::Message::Text(text) = frame {
+                let message: DBMessage = serde_json::from_str(&text).unwrap();
+                received.push(message.messages.join(""));
+            }
+        }
+        assert_eq!(received, ["live 4", "live 5"]);
+        drop(socket);
+        server.stop();
+    }
+
+    #[test]
+    fn websocket_rejects_other_origins_without_token() {
+        let db = open();
+        let mut server = HttpServer::start("127.0.0.1:0", None, db.pipeline()).unwrap();
+        let address = server.address();
+        assert_eq!(
+            connect(address, "", Some("https://evil.example")).err(),
+            Some(403)
+        );
+        let same_origin = format!("http://{}", address);
+        assert!(connect(address, "", Some(&same_origin)).is_ok());
+        assert!(connect(address, "", None).is_ok());
+        server.stop();
+    }
+}
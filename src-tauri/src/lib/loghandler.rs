@@ -1,83 +1,123 @@
 use crate::db::*;
+use crate::http::HttpServer;
+use crate::pipeline::{MessagePipeline, PipelineEvent};
+use crate::process::{ProcessInfo, ProcessManager, ProcessSpec};
+use crate::profile::{Profile, ProfileFormat, ProfileReport, ProfileStrategy};
+use crate::settings::{apply_retention, validate_address, Settings};
+use crate::tail::FileTailer;
+use msg_server::stream_support::StreamServerHandler;
+use msg_server::syslog_support::SyslogServerHandler;
+use msg_server::udp_support::{UdpServerHandler, UdpStats};
 use msg_server::zmq_support::ServerHandler;
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 use tauri::{AppHandle, Emitter, Manager};
+const DEFAULT_PROJECT: &str = "default";
 pub struct LogHandler {
-    address: Arc<RwLock<String>>,
     pub db: Arc<Mutex<Option<Connection>>>,
+    pub registry: Arc<Mutex<Option<Connection>>>,
+    project: Arc<RwLock<Option<Project>>>,
     pub server_handler: Arc<RwLock<Option<ServerHandler>>>,
+    // 与 ZeroMQ 服务一起启动和停止的 TCP / Unix 监听
+    stream_servers: RwLock<Vec<StreamServerHandler>>,
+    // 可选的 UDP 监听，统计数据在停止后保留到下次启动
+    udp_server: RwLock<Option<UdpServerHandler>>,
+    syslog_servers: RwLock<Vec<SyslogServerHandler>>,
+    // OTLP 接收端，gRPC 与 HTTP 共用一个端口
+    otlp_server: Mutex<Option<HttpServer>>,
+    // 跟踪的日志文件，停止时保存读取位置
+    tailers: RwLock<Vec<FileTailer>>,
+    // 从查看器启动的子进程，输出写入各自的会话
+    pub processes: ProcessManager,
+    // 进行中的导出，按目标文件路径区分，各自可单独取消
+    exports: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    archive: Arc<RwLock<Option<ArchiveHeader>>>,
+    pub pipeline: Arc<MessagePipeline>,
+    emitter_registered: AtomicBool,
+    // 当前运行的 HTTP 接口及其配置的地址和令牌
+    http: Mutex<Option<(String, Option<String>, HttpServer)>>,
 }
 #[derive(Serialize, Deserialize)]
 pub struct ServerState {
     is_running: bool,
     address: String,
+    project: String,
+    read_only: bool,
+    paused: bool,
+    listeners: Vec<String>,
+    udp: Option<UdpStats>,
+}
+fn data_dir(app: &AppHandle) -> PathBuf {
+    app.path().data_dir().unwrap().join("xclogger")
 }
 impl LogHandler {
     pub fn new() -> Self {
+        let db = Arc::new(Mutex::new(Option::<Connection>::None));
         Self {
-            address: Arc::new(RwLock::new("tcp://127.0.0.1:5555".to_string())),
-            db: Arc::new(Mutex::new(Option::<Connection>::None)),
+            db: db.clone(),
+            registry: Arc::new(Mutex::new(Option::<Connection>::None)),
+            project: Arc::new(RwLock::new(Option::<Project>::None)),
             server_handler: Arc::new(RwLock::new(Option::<ServerHandler>::None)),
+            stream_servers: RwLock::new(Vec::new()),
+            udp_server: RwLock::new(None),
+            syslog_servers: RwLock::new(Vec::new()),
+            otlp_server: Mutex::new(None),
+            tailers: RwLock::new(Vec::new()),
+            processes: ProcessManager::new(),
+            exports: Mutex::new(HashMap::new()),
+            archive: Arc::new(RwLock::new(Option::<ArchiveHeader>::None)),
+            pipeline: Arc::new(MessagePipeline::new(db)),
+            emitter_registered: AtomicBool::new(false),
+            http: Mutex::new(None),
         }
     }
     pub fn start_server(&self, app_handle: &AppHandle) -> Result<String, String> {
-        {
-            if let Some(server_handler) = self
-                .server_handler
-                .read()
-                .map_err(|e| e.to_string())?
-                .as_ref()
-            {
-                if server_handler.is_closed() {
-                    server_handler.set_address(&self.address.read().unwrap().as_str());
-                    server_handler.run();
-                }
-                return Ok("server already started".to_string());
-            }
+        if self.is_read_only() {
+            return Err("an archive is open, close it before starting the server".to_string());
         }
-        {
-            let address = self.address.read().unwrap();
-            let db = self.db.clone();
-            let app = app_handle.clone();
-            let server_handler = ServerHandler::new(&address.as_str(), move |data| {
-                {
-                    if !db.is_connected() {
-                        db.connect(
-                            &app.path()
-                                .data_dir()
-                                .unwrap()
-                                .join("xclogger")
-                                .join("xclogger.db"),
-                        )
-                        .expect("Failed to connect to database");
-                    }
+        self.connect_db(app_handle)?;
+        let settings = Settings::load(&self.db)?;
+        apply_retention(&self.db, &settings.retention)?;
+        let restarted = {
+            let server_handler = self.server_handler.read().map_err(|e| e.to_string())?;
+            match server_handler.as_ref() {
+                Some(server_handler) if !server_handler.is_closed() => {
+                    return Ok("server already started".to_string());
                 }
-                app.emit(
-                    "message-received",
-                    &DBMessage {
-                        id: db.insert_message(&data).expect("Failed to insert message"),
-                        role: data.role.clone(),
-                        label: data.label.clone(),
-                        file: data.file.clone(),
-                        function: data.function.clone(),
-                        time: data.time,
-                        process_id: data.process_id,
-                        thread_id: data.thread_id,
-                        line: data.line,
-                        level: data.level,
-                        messages: data.messages.clone(),
-                    },
-                )
-                .expect("Failed to emit message-received event");
-            });
-            server_handler.run();
+                Some(server_handler) => {
+                    server_handler.set_address(&settings.address);
+                    server_handler.set_mode(settings.socket_mode);
+                    server_handler
+                        .run()
+                        .map_err(|e| format!("failed to listen on {}: {}", settings.address, e))?;
+                    true
+                }
+                None => false,
+            }
+        };
+        if !restarted {
+            self.pipeline.reload_alerts()?;
+            let server_handler = ServerHandler::new(&settings.address, self.pipeline.handler());
+            server_handler.set_mode(settings.socket_mode);
+            server_handler
+                .run()
+                .map_err(|e| format!("failed to listen on {}: {}", settings.address, e))?;
             let mut grade = self.server_handler.write().map_err(|e| e.to_string())?;
 
             *grade = Some(server_handler);
         }
-
+        // 附加监听启动失败时整体回滚，避免只有部分入口在运行
+        if let Err(e) = self.start_listeners(&settings) {
+            self.stop_server()?;
+            return Err(e);
+        }
+        if restarted {
+            return Ok("server already started".to_string());
+        }
         Ok("server started".to_string())
     }
     pub fn stop_server(&self) -> Result<String, String> {
@@ -89,11 +129,98 @@ impl LogHandler {
         {
             server_handler.close();
         }
+        for server in self
+            .stream_servers
+            .write()
+            .map_err(|e| e.to_string())?
+            .drain(..)
+        {
+            server.close();
+        }
+        if let Some(server) = self.udp_server.read().map_err(|e| e.to_string())?.as_ref() {
+            server.close();
+        }
+        for server in self
+            .syslog_servers
+            .write()
+            .map_err(|e| e.to_string())?
+            .drain(..)
+        {
+            server.close();
+        }
+        if let Some(mut server) = self.otlp_server.lock().map_err(|e| e.to_string())?.take() {
+            server.stop();
+        }
+        for tailer in self.tailers.write().map_err(|e| e.to_string())?.drain(..) {
+            tailer.close();
+        }
         Ok("server stopped".to_string())
     }
+    fn start_listeners(&self, settings: &Settings) -> Result<(), String> {
+        let mut servers = self.stream_servers.write().map_err(|e| e.to_string())?;
+        for server in servers.drain(..) {
+            server.close();
+        }
+        for listener in &settings.stream_listeners {
+            let server = StreamServerHandler::new(
+                &listener.address,
+                listener.format,
+                self.pipeline.handler(),
+            );
+            server
+                .run()
+                .map_err(|e| format!("failed to listen on {}: {}", listener.address, e))?;
+            servers.push(server);
+        }
+        let mut udp = self.udp_server.write().map_err(|e| e.to_string())?;
+        if let Some(server) = udp.take() {
+            server.close();
+        }
+        if let Some(address) = &settings.udp_address {
+            let server = UdpServerHandler::new(address, self.pipeline.handler());
+            server
+                .run()
+                .map_err(|e| format!("failed to listen on {}: {}", address, e))?;
+            *udp = Some(server);
+        }
+        let mut syslog = self.syslog_servers.write().map_err(|e| e.to_string())?;
+        for server in syslog.drain(..) {
+            server.close();
+        }
+        for address in &settings.syslog_listeners {
+            let server = SyslogServerHandler::new(address, self.pipeline.handler());
+            server
+                .run()
+                .map_err(|e| format!("failed to listen on {}: {}", address, e))?;
+            syslog.push(server);
+        }
+        let mut otlp = self.otlp_server.lock().map_err(|e| e.to_string())?;
+        if let Some(mut server) = otlp.take() {
+            server.stop();
+        }
+        if let Some(address) = &settings.otlp_address {
+            *otlp = Some(crate::otlp::start(address, self.pipeline.clone())?);
+        }
+        let mut tailers = self.tailers.write().map_err(|e| e.to_string())?;
+        for tailer in tailers.drain(..) {
+            tailer.close();
+        }
+        for source in &settings.tail_sources {
+            let pipeline = self.pipeline.clone();
+            let path = source.path.clone();
+            let tailer = FileTailer::new(source.clone(), self.db.clone(), move |data| {
+                if let Err(e) = pipeline.ingest_into(&data, None, Some(&path)) {
+                    eprintln!("failed to ingest message: {}", e);
+                }
+            });
+            tailer.run()?;
+            tailers.push(tailer);
+        }
+        Ok(())
+    }
     pub fn get_address(&self) -> Result<String, String> {
         if !self.is_server_running().unwrap_or(false) {
-            return Ok(self.address.read().unwrap().clone());
+            return Ok(Settings::load(&self.db)?.address);
         }
         if let Some(server_handler) = self
             .server_handler
@@ -118,38 +245,498 @@ impl LogHandler {
             Ok(false)
         }
     }
+    fn connect_registry(&self, app: &AppHandle) -> Result<(), String> {
+        if self.registry.is_connected() {
+            return Ok(());
+        }
+        let dir = data_dir(app);
+        self.registry.connect_registry(
+            &dir.join("projects.db"),
+            &Project {
+                name: DEFAULT_PROJECT.to_string(),
+                db_path: dir.join("xclogger.db").to_string_lossy().to_string(),
+                active: true,
+            },
+        )
+    }
     pub fn connect_db(&self, app: &AppHandle) -> Result<String, String> {
+        // HTTP 接口和子进程在 ZeroMQ 服务停止时也会写入消息，界面始终需要收到推送
+        self.register_emitter(app);
         if !self.db.is_connected() {
+            let project = self.current_project(app)?;
             self.db
-                .connect(
-                    &app.path()
-                        .data_dir()
-                        .unwrap()
-                        .join("xclogger")
-                        .join("xclogger.db"),
-                )
+                .connect(&PathBuf::from(&project.db_path))
                 .map_err(|e| e.to_string())?;
+            self.pipeline.load_rules();
+            self.pipeline.reload_alerts()?;
+            self.load_http();
         }
         Ok("database connected".to_string())
     }
-    pub fn get_server_address(&self) -> Result<String, String> {
-        Ok(self.address.read().unwrap().clone())
+    pub fn current_project(&self, app: &AppHandle) -> Result<Project, String> {
+        if let Some(project) = self.project.read().map_err(|e| e.to_string())?.as_ref() {
+            return Ok(project.clone());
+        }
+        self.connect_registry(app)?;
+        let project = self.registry.get_active_project()?;
+        *self.project.write().map_err(|e| e.to_string())? = Some(project.clone());
+        Ok(project)
+    }
+    pub fn list_projects(&self, app: &AppHandle) -> Result<Vec<Project>, String> {
+        self.connect_registry(app)?;
+        self.registry.list_projects()
+    }
+    pub fn create_project(
+        &self,
+        app: &AppHandle,
+        name: &str,
+        address: Option<String>,
+        db_path: Option<String>,
+    ) -> Result<Project, String> {
+        if name.trim().is_empty() {
+            return Err("project name cannot be empty".to_string());
+        }
+        if let Some(address) = &address {
+            validate_address(address)?;
+        }
+        self.connect_registry(app)?;
+        // 指定已有数据库时直接使用，例如 xclogger-daemon 写入的数据库
+        let db_path = match db_path {
+            Some(path) => PathBuf::from(path),
+            None => {
+                // 文件名与项目名解耦，重命名项目时不需要移动数据库文件
+                self.registry
+                    .new_db_path(&data_dir(app).join("projects"), &sanitize_file_name(name))?
+            }
+        };
+        let project = Project {
+            name: name.to_string(),
+            db_path: db_path.to_string_lossy().to_string(),
+            active: false,
+        };
+        // 先登记项目，名称或数据库重复时不会留下新建的数据库文件
+        let existed = db_path.exists();
+        self.registry.create_project(&project)?;
+        // 监听地址保存在项目数据库中，未指定时使用默认地址
+        if let Some(address) = address {
+            if let Err(e) = save_project_address(&db_path, &address) {
+                let _ = self.registry.delete_project(name);
+                if !existed {
+                    let _ = std::fs::remove_file(&db_path);
+                    remove_wal_files(&db_path);
+                }
+                return Err(e);
+            }
+        }
+        Ok(project)
+    }
+    pub fn switch_project(&self, app: &AppHandle, name: &str) -> Result<Project, String> {
+        self.connect_registry(app)?;
+        let mut project = self
+            .registry
+            .get_project(name)?
+            .ok_or(format!("project not found: {}", name))?;
+        // 先打开新项目的数据库，失败时保持当前项目不变
+        let next_db = Arc::new(Mutex::new(Option::<Connection>::None));
+        next_db.connect(&PathBuf::from(&project.db_path))?;
+        let was_running = self.is_server_running().unwrap_or(false);
+        // 停止并丢弃旧的 ServerHandler，新的地址会在下次启动时绑定
+        self.stop_server()?;
+        *self.server_handler.write().map_err(|e| e.to_string())? = None;
+        // 捕获的子进程会话属于旧项目，切换前停止
+        self.processes.stop_all();
+        self.registry.set_active_project(name)?;
+        project.active = true;
+        *self.archive.write().map_err(|e| e.to_string())? = None;
+        let next_conn = next_db.lock().map_err(|e| e.to_string())?.take();
+        *self.db.lock().map_err(|e| e.to_string())? = next_conn;
+        self.pipeline.reload_alerts()?;
+        self.pipeline.load_rules();
+        self.load_http();
+        *self.project.write().map_err(|e| e.to_string())? = Some(project.clone());
+        app.emit("project-switched", &project)
+            .map_err(|e| e.to_string())?;
+        // 切换已经完成，新项目的监听启动失败时单独通知，不影响切换结果
+        if was_running {
+            if let Err(e) = self.start_server(app) {
+                eprintln!(
+                    "failed to restart server for project {}: {}",
+                    project.name, e
+                );
+                app.emit("server-error", &e).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(project)
+    }
+    pub fn rename_project(
+        &self,
+        app: &AppHandle,
+        name: &str,
+        new_name: &str,
+    ) -> Result<(), String> {
+        if new_name.trim().is_empty() {
+            return Err("project name cannot be empty".to_string());
+        }
+        self.connect_registry(app)?;
+        self.registry.rename_project(name, new_name)?;
+        if let Some(project) = self.project.write().map_err(|e| e.to_string())?.as_mut() {
+            if project.name == name {
+                project.name = new_name.to_string();
+            }
+        }
+        Ok(())
+    }
+    pub fn delete_project(
+        &self,
+        app: &AppHandle,
+        name: &str,
+        remove_file: bool,
+    ) -> Result<(), String> {
+        self.connect_registry(app)?;
+        let project = self.registry.delete_project(name)?;
+        if remove_file {
+            let path = PathBuf::from(&project.db_path);
+            if path.exists() {
+                std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+            }
+            remove_wal_files(&path);
+        }
+        Ok(())
     }
     pub fn set_server_address(&self, address: String) -> Result<String, String> {
         if self.is_server_running().unwrap_or(false) {
             return Err("server is running, cannot update address".to_string());
         }
-        *self.address.write().unwrap() = address;
-        println!(
-            "server address updated to {}",
-            *self.address.read().unwrap()
-        );
+        Settings::save_address(&self.db, &address)?;
+        println!("server address updated to {}", address);
         Ok("server address updated".to_string())
     }
+    pub fn get_settings(&self, app: &AppHandle) -> Result<Settings, String> {
+        self.connect_db(app)?;
+        Settings::load(&self.db)
+    }
+    pub fn update_settings(&self, app: &AppHandle, settings: Settings) -> Result<Settings, String> {
+        settings.validate()?;
+        let current = self.get_settings(app)?;
+        let running = self.is_server_running().unwrap_or(false);
+        if running
+            && (current.address != settings.address
+                || current.socket_mode != settings.socket_mode
+                || current.stream_listeners != settings.stream_listeners
+                || current.udp_address != settings.udp_address
+                || current.syslog_listeners != settings.syslog_listeners
+                || current.otlp_address != settings.otlp_address
+                || current.tail_sources != settings.tail_sources)
+        {
+            return Err(
+                "server is running, cannot update address, socket mode or listeners".to_string(),
+            );
+        }
+        settings.save(&self.db)?;
+        if current.retention != settings.retention {
+            apply_retention(&self.db, &settings.retention)?;
+        }
+        if current.http_address != settings.http_address
+            || current.http_token != settings.http_token
+        {
+            self.set_http_address(
+                settings.http_address.as_deref(),
+                settings.http_token.as_deref(),
+            )?;
+        }
+        self.pipeline.settings_changed(&settings)?;
+        Ok(settings)
+    }
+    pub fn export_profile(
+        &self,
+        app: &AppHandle,
+        path: &str,
+        format: Option<ProfileFormat>,
+    ) -> Result<Profile, String> {
+        let path = PathBuf::from(path);
+        let format = format
+            .or_else(|| ProfileFormat::from_path(&path))
+            .unwrap_or(ProfileFormat::Json);
+        let profile = Profile::from_settings(&self.get_settings(app)?);
+        profile.write(&path, format)?;
+        Ok(profile)
+    }
+    pub fn import_profile(
+        &self,
+        app: &AppHandle,
+        path: &str,
+        strategy: ProfileStrategy,
+    ) -> Result<ProfileReport, String> {
+        let profile = Profile::read(&PathBuf::from(path))?;
+        let current = self.get_settings(app)?;
+        let (mut settings, mut report) = profile.apply(&current, strategy);
+        // 服务运行时不能切换套接字模式，保留本地值，其余设置照常导入
+        if self.is_server_running().unwrap_or(false) && settings.socket_mode != current.socket_mode
+        {
+            settings.socket_mode = current.socket_mode;
+            report.skip("socket_mode");
+        }
+        self.update_settings(app, settings)?;
+        Ok(report)
+    }
     pub fn get_server_state(&self) -> Result<ServerState, String> {
         Ok(ServerState {
             is_running: self.is_server_running().unwrap_or(false),
             address: self.get_address().unwrap_or_default(),
+            project: self
+                .project
+                .read()
+                .map_err(|e| e.to_string())?
+                .as_ref()
+                .map(|p| p.name.clone())
+                .unwrap_or_default(),
+            read_only: self.is_read_only(),
+            paused: self.pipeline.is_paused(),
+            listeners: self
+                .stream_servers
+                .read()
+                .map_err(|e| e.to_string())?
+                .iter()
+                .map(|s| s.address())
+                .chain(
+                    self.syslog_servers
+                        .read()
+                        .map_err(|e| e.to_string())?
+                        .iter()
+                        .map(|s| s.address()),
+                )
+                .chain(
+                    self.otlp_server
+                        .lock()
+                        .map_err(|e| e.to_string())?
+                        .as_ref()
+                        .map(|s| format!("otlp://{}", s.address())),
+                )
+                .chain(
+                    self.tailers
+                        .read()
+                        .map_err(|e| e.to_string())?
+                        .iter()
+                        .map(|t| t.address()),
+                )
+                .collect(),
+            udp: self
+                .udp_server
+                .read()
+                .map_err(|e| e.to_string())?
+                .as_ref()
+                .map(|s| s.stats()),
         })
     }
+    pub fn export_messages(
+        &self,
+        app: &AppHandle,
+        config: &FilterConfig,
+        format: ExportFormat,
+        path: &str,
+    ) -> Result<usize, String> {
+        self.connect_db(app)?;
+        let cancel = Arc::new(AtomicBool::new(false));
+        {
+            let mut exports = self.exports.lock().map_err(|e| e.to_string())?;
+            if exports.contains_key(path) {
+                return Err(format!("already exporting to {}", path));
+            }
+            exports.insert(path.to_string(), cancel.clone());
+        }
+        let result = self.db.export_messages_to_file(
+            config,
+            format,
+            &PathBuf::from(path),
+            |exported, total| {
+                let _ = app.emit(
+                    "export-progress",
+                    &ExportProgress {
+                        path: path.to_string(),
+                        exported,
+                        total,
+                    },
+                );
+                !cancel.load(Ordering::SeqCst)
+            },
+        );
+        self.exports.lock().map_err(|e| e.to_string())?.remove(path);
+        result
+    }
+    pub fn cancel_export(&self, path: &str) -> Result<(), String> {
+        self.exports
+            .lock()
+            .map_err(|e| e.to_string())?
+            .get(path)
+            .ok_or(format!("no export to {}", path))?
+            .store(true, Ordering::SeqCst);
+        Ok(())
+    }
+    // 子进程的输出不依赖 ZeroMQ 服务，启动前只需连接数据库
+    fn prepare_capture(&self, app: &AppHandle) -> Result<(), String> {
+        if self.is_read_only() {
+            return Err("an archive is open, close it before launching a process".to_string());
+        }
+        self.connect_db(app)?;
+        Ok(())
+    }
+    pub fn launch_process(
+        &self,
+        app: &AppHandle,
+        spec: ProcessSpec,
+    ) -> Result<ProcessInfo, String> {
+        self.prepare_capture(app)?;
+        self.processes.launch(spec, &self.pipeline)
+    }
+    pub fn restart_process(&self, app: &AppHandle, id: u64) -> Result<ProcessInfo, String> {
+        self.prepare_capture(app)?;
+        self.processes.restart(id, &self.pipeline)
+    }
+    // 首次启动服务或恢复推送时注册，将流水线事件转发给前端
+    fn register_emitter(&self, app: &AppHandle) {
+        if self.emitter_registered.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let app = app.clone();
+        self.pipeline.add_listener(move |event| {
+            let result = match event {
+                PipelineEvent::Message(message) => app.emit("message-received", message),
+                PipelineEvent::Alert(alert) => app.emit("saved-search-alert", alert),
+                PipelineEvent::Replayed(batch) => app.emit("messages-replayed", batch),
+                PipelineEvent::SettingsChanged(settings) => app.emit("settings-changed", settings),
+            };
+            if let Err(e) = result {
+                eprintln!("failed to emit pipeline event: {}", e);
+            }
+        });
+    }
+    pub fn pause(&self, app: &AppHandle) -> Result<(), String> {
+        self.connect_db(app)?;
+        self.pipeline.pause()
+    }
+    pub fn resume(&self, app: &AppHandle) -> Result<usize, String> {
+        self.connect_db(app)?;
+        self.pipeline.resume()
+    }
+    // HTTP 接口跟随当前项目的 http_address 和 http_token 设置，两者未变化时保持运行
+    pub fn set_http_address(
+        &self,
+        address: Option<&str>,
+        token: Option<&str>,
+    ) -> Result<(), String> {
+        let mut http = self.http.lock().map_err(|e| e.to_string())?;
+        let running = http
+            .as_ref()
+            .map(|(current, current_token, _)| (current.as_str(), current_token.as_deref()));
+        if running == address.map(|address| (address, token)) {
+            return Ok(());
+        }
+        // 先停止旧的服务，新地址可能与旧地址使用同一端口
+        *http = None;
+        if let Some(address) = address {
+            let server = HttpServer::start(address, token, self.pipeline.clone())?;
+            *http = Some((address.to_string(), token.map(str::to_string), server));
+        }
+        Ok(())
+    }
+    // 连接数据库时按设置启动 HTTP 接口，失败不影响连接
+    fn load_http(&self) {
+        let settings = Settings::load(&self.db).unwrap_or_default();
+        if let Err(e) = self.set_http_address(
+            settings.http_address.as_deref(),
+            settings.http_token.as_deref(),
+        ) {
+            eprintln!("failed to start HTTP API: {}", e);
+        }
+    }
+    pub fn is_read_only(&self) -> bool {
+        self.archive.read().map(|a| a.is_some()).unwrap_or(false)
+    }
+    pub fn export_archive(
+        &self,
+        app: &AppHandle,
+        config: &FilterConfig,
+        order: Option<(MessageField, bool)>,
+        path: &str,
+    ) -> Result<ArchiveHeader, String> {
+        self.connect_db(app)?;
+        let project = self.current_project(app)?;
+        self.db
+            .export_archive(config, order, &PathBuf::from(path), &project.name)
+    }
+    pub fn open_archive(&self, app: &AppHandle, path: &str) -> Result<ArchiveHeader, String> {
+        let path = PathBuf::from(path);
+        read_archive_header(&path)?;
+        // stop_server 等待接收线程退出，之后不会再有消息写入即将替换的数据库
+        self.stop_server()?;
+        *self.server_handler.write().map_err(|e| e.to_string())? = None;
+        // 捕获的子进程不能继续写入归档数据库
+        self.processes.stop_all();
+        // 归档解压到缓存目录中的临时数据库，查询接口无需区分数据来源
+        let cache = app
+            .path()
+            .cache_dir()
+            .map_err(|e| e.to_string())?
+            .join("xclogger")
+            .join("archives");
+        let db_path = cache.join(format!(
+            "{}.db",
+            sanitize_file_name(
+                &path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            )
+        ));
+        *self.db.lock().map_err(|e| e.to_string())? = None;
+        if db_path.exists() {
+            std::fs::remove_file(&db_path).map_err(|e| e.to_string())?;
+        }
+        self.db.connect(&db_path)?;
+        let header = self.db.load_archive(&path)?;
+        self.pipeline.reload_alerts()?;
+        self.pipeline.load_rules();
+        self.db.set_read_only(true)?;
+        *self.archive.write().map_err(|e| e.to_string())? = Some(header.clone());
+        app.emit("archive-opened", &header)
+            .map_err(|e| e.to_string())?;
+        Ok(header)
+    }
+    pub fn close_archive(&self, app: &AppHandle) -> Result<(), String> {
+        if !self.is_read_only() {
+            return Ok(());
+        }
+        *self.archive.write().map_err(|e| e.to_string())? = None;
+        *self.db.lock().map_err(|e| e.to_string())? = None;
+        self.connect_db(app)?;
+        app.emit("archive-closed", ()).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+fn save_project_address(db_path: &Path, address: &str) -> Result<(), String> {
+    let project_db = Arc::new(Mutex::new(Option::<Connection>::None));
+    project_db.connect(db_path)?;
+    Settings::save_address(&project_db, address)
+}
+
+// 删除数据库旁边的 WAL 和共享内存文件，不存在时忽略
+fn remove_wal_files(path: &Path) {
+    for suffix in ["-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        let _ = std::fs::remove_file(file);
+    }
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
 }
@@ -0,0 +1,415 @@
+use crate::http::HttpServer;
+use crate::pipeline::MessagePipeline;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use msg_server::level::{parse_level, DEBUG, ERROR, FATAL, INFO, TRACE, WARN};
+use msg_server::MessageData;
+use opentelemetry_proto::tonic::collector::logs::v1::logs_service_server::{
+    LogsService, LogsServiceServer,
+};
+use opentelemetry_proto::tonic::collector::logs::v1::{
+    ExportLogsPartialSuccess, ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, KeyValue};
+use prost::Message;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::Arc;
+
+// OpenTelemetry 日志接收端，同一个端口提供两种 OTLP 传输方式：
+//
+//   gRPC  opentelemetry.proto.collector.logs.v1.LogsService/Export
+//   POST  /v1/logs   application/x-protobuf 或 application/json
+//
+// `service.name` 作为角色，scope 名称作为标签，`code.*` 属性对应文件、行号和
+// 函数。其余资源属性和记录属性，以及十六进制的 trace/span id 保存在 `fields`
+// 中；同名时记录属性覆盖资源属性。
+//
+// OTLP/HTTP 请求体可以使用 gzip 压缩，其他编码返回 415。
+
+const TRACE_ID_FIELD: &str = "trace_id";
+const SPAN_ID_FIELD: &str = "span_id";
+
+// 解压后超过此大小的 OTLP/HTTP 请求体被拒绝
+const MAX_DECODED_BODY: u64 = 64 * 1024 * 1024;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn any_json(value: &AnyValue) -> Value {
+    match &value.value {
+        None => Value::Null,
+        Some(any_value::Value::StringValue(s)) => Value::String(s.clone()),
+        Some(any_value::Value::BoolValue(b)) => Value::Bool(*b),
+        Some(any_value::Value::IntValue(i)) => Value::from(*i),
+        Some(any_value::Value::DoubleValue(d)) => Value::from(*d),
+        Some(any_value::Value::ArrayValue(array)) => {
+            Value::Array(array.values.iter().map(any_json).collect())
+        }
+        Some(any_value::Value::KvlistValue(list)) => Value::Object(
+            list.values
+                .iter()
+                .map(|kv| {
+                    (
+                        kv.key.clone(),
+                        kv.value.as_ref().map_or(Value::Null, any_json),
+                    )
+                })
+                .collect(),
+        ),
+        Some(any_value::Value::BytesValue(bytes)) => Value::String(hex(bytes)),
+    }
+}
+
+fn any_text(value: Option<&AnyValue>) -> String {
+    match value.map_or(Value::Null, any_json) {
+        Value::Null => String::new(),
+        Value::String(s) => s,
+        other => other.to_string(),
+    }
+}
+
+fn any_int(value: Option<&AnyValue>) -> Option<i64> {
+    match value?.value.as_ref()? {
+        any_value::Value::IntValue(i) => Some(*i),
+        any_value::Value::StringValue(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn find<'a>(attributes: &'a [KeyValue], key: &str) -> Option<&'a AnyValue> {
+    attributes
+        .iter()
+        .find(|kv| kv.key == key)
+        .and_then(|kv| kv.value.as_ref())
+}
+
+// 严重级别编号每四个对应一个级别，1..=4 为 TRACE
+fn severity_level(number: i32, text: &str) -> i32 {
+    match number {
+        1..=4 => TRACE,
+        5..=8 => DEBUG,
+        9..=12 => INFO,
+        13..=16 => WARN,
+        17..=20 => ERROR,
+        21..=24 => FATAL,
+        _ => parse_level(text).unwrap_or(INFO),
+    }
+}
+
+/// 将导出请求展开为消息，映射规则见本模块开头的说明
+pub fn convert(request: ExportLogsServiceRequest) -> Vec<MessageData> {
+    let now = crate::template::now_micros();
+    let mut messages = Vec::new();
+    for resource_logs in request.resource_logs {
+        let resource = resource_logs
+            .resource
+            .map(|r| r.attributes)
+            .unwrap_or_default();
+        let role = any_text(find(&resource, "service.name"));
+        let process_id = any_int(find(&resource, "process.pid")).unwrap_or(0);
+        let resource_fields: BTreeMap<String, String> = resource
+            .iter()
+            .filter(|kv| !matches!(kv.key.as_str(), "service.name" | "process.pid"))
+            .map(|kv| (kv.key.clone(), any_text(kv.value.as_ref())))
+            .collect();
+        for scope_logs in resource_logs.scope_logs {
+            let label = scope_logs.scope.map(|s| s.name).unwrap_or_default();
+            for record in scope_logs.log_records {
+                let mut data = MessageData {
+                    role: role.clone(),
+                    label: label.clone(),
+                    file: String::new(),
+                    function: String::new(),
+                    time: match (record.time_unix_nano, record.observed_time_unix_nano) {
+                        (0, 0) => now,
+                        (0, observed) => (observed / 1000) as usize,
+                        (time, _) => (time / 1000) as usize,
+                    },
+                    process_id: process_id.max(0) as usize,
+                    thread_id: 0,
+                    line: 0,
+                    level: severity_level(record.severity_number, &record.severity_text),
+                    messages: vec![any_text(record.body.as_ref())],
+                    fields: resource_fields.clone(),
+                };
+                for attribute in &record.attributes {
+                    let value = attribute.value.as_ref();
+                    match attribute.key.as_str() {
+                        "code.filepath" | "code.file.path" => data.file = any_text(value),
+                        "code.function" | "code.function.name" => data.function = any_text(value),
+                        "code.lineno" | "code.line.number" => {
+                            data.line = any_int(value).unwrap_or(0) as i32
+                        }
+                        "thread.id" => data.thread_id = any_int(value).unwrap_or(0).max(0) as usize,
+                        key => {
+                            data.fields.insert(key.to_string(), any_text(value));
+                        }
+                    }
+                }
+                if !record.trace_id.is_empty() {
+                    data.fields
+                        .insert(TRACE_ID_FIELD.to_string(), hex(&record.trace_id));
+                }
+                if !record.span_id.is_empty() {
+                    data.fields
+                        .insert(SPAN_ID_FIELD.to_string(), hex(&record.span_id));
+                }
+                messages.push(data);
+            }
+        }
+    }
+    messages
+}
+
+async fn ingest(
+    pipeline: Arc<MessagePipeline>,
+    request: ExportLogsServiceRequest,
+) -> Result<ExportLogsServiceResponse, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut rejected = 0;
+        let mut error_message = String::new();
+        for data in convert(request) {
+            if let Err(e) = pipeline.ingest(&data) {
+                rejected += 1;
+                error_message = e;
+            }
+        }
+        ExportLogsServiceResponse {
+            partial_success: (rejected > 0).then_some(ExportLogsPartialSuccess {
+                rejected_log_records: rejected,
+                error_message,
+            }),
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+struct LogsReceiver(Arc<MessagePipeline>);
+
+#[tonic::async_trait]
+impl LogsService for LogsReceiver {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportLogsServiceRequest>,
+    ) -> Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
+        ingest(self.0.clone(), request.into_inner())
+            .await
+            .map(tonic::Response::new)
+            .map_err(tonic::Status::internal)
+    }
+}
+
+fn gunzip(body: &[u8]) -> Result<Bytes, String> {
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(body)
+        .take(MAX_DECODED_BODY + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|e| e.to_string())?;
+    if decoded.len() as u64 > MAX_DECODED_BODY {
+        return Err(format!(
+            "decompressed body exceeds {} bytes",
+            MAX_DECODED_BODY
+        ));
+    }
+    Ok(Bytes::from(decoded))
+}
+
+// OTLP/HTTP 使用请求的编码格式应答
+async fn export_http(
+    State(pipeline): State<Arc<MessagePipeline>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let encoding = headers
+        .get(CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_ascii_lowercase());
+    let body = match encoding.as_deref() {
+        None | Some("") | Some("identity") => body,
+        Some("gzip") => match gunzip(&body) {
+            Ok(body) => body,
+            Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+        },
+        Some(other) => {
+            return (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("unsupported content encoding: {}", other),
+            )
+                .into_response()
+        }
+    };
+    let json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let request = match json {
+        true => serde_json::from_slice(&body).map_err(|e| e.to_string()),
+        false => ExportLogsServiceRequest::decode(body).map_err(|e| e.to_string()),
+    };
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match ingest(pipeline, request).await {
+        Ok(response) if json => Json(response).into_response(),
+        Ok(response) => (
+            [(CONTENT_TYPE, "application/x-protobuf")],
+            response.encode_to_vec(),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+fn router(pipeline: Arc<MessagePipeline>) -> Router {
+    let grpc = tonic::service::Routes::new(LogsServiceServer::new(LogsReceiver(pipeline.clone())))
+        .into_axum_router();
+    Router::new()
+        .route("/v1/logs", post(export_http))
+        .with_state(pipeline)
+        .merge(grpc)
+}
+
+/// 在 `address` 上启动 OTLP 接收端，例如 `127.0.0.1:4318`
+pub fn start(address: &str, pipeline: Arc<MessagePipeline>) -> Result<HttpServer, String> {
+    HttpServer::serve(address, router(pipeline))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use opentelemetry_proto::tonic::common::v1::InstrumentationScope;
+    use opentelemetry_proto::tonic::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+    use opentelemetry_proto::tonic::resource::v1::Resource;
+
+    fn attribute(key: &str, value: any_value::Value) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    #[test]
+    fn records_map_onto_messages() {
+        let string = |s: &str| any_value::Value::StringValue(s.to_string());
+        let request = ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(Resource {
+                    attributes: vec![
+                        attribute("service.name", string("checkout")),
+                        attribute("process.pid", any_value::Value::IntValue(42)),
+                        attribute("host.name", string("web-1")),
+                        attribute("user", string("resource")),
+                    ],
+                    dropped_attributes_count: 0,
+                }),
+                scope_logs: vec![ScopeLogs {
+                    scope: Some(InstrumentationScope {
+                        name: "cart".to_string(),
+                        ..Default::default()
+                    }),
+                    log_records: vec![LogRecord {
+                        time_unix_nano: 1_700_000_000_123_456_789,
+                        severity_number: 17,
+                        body: Some(AnyValue {
+                            value: Some(string("payment failed")),
+                        }),
+                        attributes: vec![
+                            attribute("code.filepath", string("cart.py")),
+                            attribute("code.lineno", any_value::Value::IntValue(12)),
+                            attribute("code.function", string("pay")),
+                            attribute("user", string("u1")),
+                        ],
+                        trace_id: vec![0xab; 16],
+                        span_id: vec![0x01; 8],
+                        ..Default::default()
+                    }],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        };
+        let messages = convert(request);
+        let data = &messages[0];
+        assert_eq!(
+            (data.role.as_str(), data.label.as_str()),
+            ("checkout", "cart")
+        );
+        assert_eq!((data.file.as_str(), data.line), ("cart.py", 12));
+        assert_eq!(data.function, "pay");
+        assert_eq!((data.level, data.process_id), (ERROR, 42));
+        assert_eq!(data.time, 1_700_000_000_123_456);
+        assert_eq!(data.messages, ["payment failed"]);
+        assert_eq!(data.fields["user"], "u1");
+        assert_eq!(data.fields["host.name"], "web-1");
+        assert!(!data.fields.contains_key("service.name"));
+        assert_eq!(data.fields[TRACE_ID_FIELD], "ab".repeat(16));
+        assert_eq!(data.fields[SPAN_ID_FIELD], "0101010101010101");
+    }
+
+    #[test]
+    fn severity_text_and_structured_bodies() {
+        assert_eq!(severity_level(0, "WARNING"), WARN);
+        assert_eq!(severity_level(0, ""), INFO);
+        assert_eq!(severity_level(24, ""), FATAL);
+        let body = AnyValue {
+            value: Some(any_value::Value::KvlistValue(
+                opentelemetry_proto::tonic::common::v1::KeyValueList {
+                    values: vec![attribute("n", any_value::Value::IntValue(1))],
+                },
+            )),
+        };
+        assert_eq!(any_text(Some(&body)), r#"{"n":1}"#);
+    }
+
+    #[tokio::test]
+    async fn gzip_bodies_are_decoded() {
+        use crate::db::MessageDB;
+        use crate::testing::TestDb;
+        use axum::body::Body;
+        use std::io::Write;
+        use tower::ServiceExt;
+
+        let db = TestDb::new();
+        let router = router(db.pipeline());
+
+        let json =
+            r#"{"resourceLogs":[{"scopeLogs":[{"logRecords":[{"body":{"stringValue":"hi"}}]}]}]}"#;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(json.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        let post = |encoding: &str, body: Vec<u8>| {
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/v1/logs")
+                .header(CONTENT_TYPE, "application/json")
+                .header(CONTENT_ENCODING, encoding)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = router.clone().oneshot(post("gzip", gzipped)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = router
+            .clone()
+            .oneshot(post("br", json.as_bytes().to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let response = router
+            .oneshot(post("gzip", json.as_bytes().to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(db.get_message_count().unwrap(), 1);
+    }
+}
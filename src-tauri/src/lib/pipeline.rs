@@ -0,0 +1,385 @@
+use crate::db::{
+    DBMessage, FilterConfig, ImportDB, MessageDB, MessageField, NumberRange, SavedSearch,
+    SearchAlert, SearchDB,
+};
+use crate::rules::RuleMatcher;
+use crate::settings::Settings;
+use crate::subscription::SubscriptionManager;
+use msg_server::MessageData;
+use rusqlite::Connection;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+
+/// 管道通知监听者的事件
+pub enum PipelineEvent<'a> {
+    /// 实时消息，暂停期间不推送
+    Message(&'a DBMessage),
+    /// 消息匹配了作为告警来源的保存搜索
+    Alert(&'a SearchAlert),
+    /// 暂停期间存储的一批消息，恢复时发送
+    Replayed(&'a [DBMessage]),
+    /// 设置已保存，包括通过 HTTP 接口的修改
+    SettingsChanged(&'a Settings),
+}
+
+pub type Listener = Box<dyn Fn(&PipelineEvent) + Send + Sync>;
+
+#[derive(Default)]
+struct PauseState {
+    paused: bool,
+    // 暂停前最后一条已推送消息的 id
+    last_emitted: usize,
+}
+
+/// 桌面应用与无界面守护进程共用的接收流程：存储每条消息、计算样式、检查告警，
+/// 并分发给实时订阅和监听者。不依赖 Tauri。
+pub struct MessagePipeline {
+    pub db: Arc<Mutex<Option<Connection>>>,
+    pub subscriptions: Arc<SubscriptionManager>,
+    pause: Mutex<PauseState>,
+    alerts: RwLock<Vec<SavedSearch>>,
+    rules: RwLock<RuleMatcher>,
+    next_listener: AtomicU64,
+    listeners: RwLock<Vec<(u64, Listener)>>,
+}
+
+impl MessagePipeline {
+    pub fn new(db: Arc<Mutex<Option<Connection>>>) -> Self {
+        Self {
+            db,
+            subscriptions: Arc::new(SubscriptionManager::new()),
+            pause: Mutex::new(PauseState::default()),
+            alerts: RwLock::new(Vec::new()),
+            rules: RwLock::new(RuleMatcher::default()),
+            next_listener: AtomicU64::new(1),
+            listeners: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn add_listener<F>(&self, listener: F) -> u64
+    where
+        F: 'static + Fn(&PipelineEvent) + Send + Sync,
+    {
+        let id = self.next_listener.fetch_add(1, Ordering::SeqCst);
+        self.listeners
+            .write()
+            .unwrap()
+            .push((id, Box::new(listener)));
+        id
+    }
+
+    pub fn remove_listener(&self, id: u64) -> bool {
+        let mut listeners = self.listeners.write().unwrap();
+        let len = listeners.len();
+        listeners.retain(|(listener_id, _)| *listener_id != id);
+        listeners.len() != len
+    }
+
+    fn notify(&self, event: &PipelineEvent) {
+        for (_, listener) in self.listeners.read().unwrap().iter() {
+            listener(event);
+        }
+    }
+
+    pub fn ingest(&self, data: &MessageData) -> Result<DBMessage, String> {
+        self.ingest_into(data, None, None)
+    }
+
+    /// 与 [`ingest`](Self::ingest) 相同，消息归入指定会话
+    pub fn ingest_into(
+        &self,
+        data: &MessageData,
+        session_id: Option<i64>,
+        source: Option<&str>,
+    ) -> Result<DBMessage, String> {
+        // 插入与暂停状态检查在同一把锁内完成，恢复时补发的范围不会与实时推送重叠
+        let state = self.pause.lock().map_err(|e| e.to_string())?;
+        let id = self.db.insert_message_into(data, session_id, source)?;
+        let message = self.to_message(id, data);
+        self.publish(state, std::slice::from_ref(&message));
+        Ok(message)
+    }
+
+    /// 与 [`ingest`](Self::ingest) 相同，整批消息在同一个事务中存储
+    pub fn ingest_batch(&self, batch: &[MessageData]) -> Result<Vec<DBMessage>, String> {
+        let state = self.pause.lock().map_err(|e| e.to_string())?;
+        let ids = self.db.insert_messages(batch, None, None)?;
+        let messages: Vec<_> = ids
+            .into_iter()
+            .zip(batch)
+            .map(|(id, data)| self.to_message(id, data))
+            .collect();
+        self.publish(state, &messages);
+        Ok(messages)
+    }
+
+    fn to_message(&self, id: usize, data: &MessageData) -> DBMessage {
+        let mut message = DBMessage {
+            id,
+            role: data.role.clone(),
+            label: data.label.clone(),
+            file: data.file.clone(),
+            function: data.function.clone(),
+            time: data.time,
+            process_id: data.process_id,
+            thread_id: data.thread_id,
+            line: data.line,
+            level: data.level,
+            messages: data.messages.clone(),
+            fields: data.fields.clone(),
+            styles: None,
+        };
+        self.rules.read().unwrap().annotate(&mut message);
+        message
+    }
+
+    // 在持有暂停锁时检查告警，未暂停时释放锁后推送给订阅和监听者
+    fn publish(&self, state: MutexGuard<PauseState>, messages: &[DBMessage]) {
+        // 告警不受暂停影响
+        for message in messages {
+            for search in self.alerts.read().unwrap().iter() {
+                if search.config.matches(message) {
+                    self.notify(&PipelineEvent::Alert(&SearchAlert {
+                        search_id: search.id,
+                        name: search.name.clone(),
+                        message: message.clone(),
+                    }));
+                }
+            }
+        }
+        if state.paused {
+            return;
+        }
+        drop(state);
+        for message in messages {
+            self.subscriptions.dispatch(message);
+            self.notify(&PipelineEvent::Message(message));
+        }
+    }
+
+    /// 供接收循环使用的消息处理函数，接收失败时输出日志
+    pub fn handler(self: &Arc<Self>) -> impl Fn(MessageData) + Send + Sync + 'static {
+        let pipeline = self.clone();
+        move |data| {
+            if let Err(e) = pipeline.ingest(&data) {
+                eprintln!("failed to ingest message: {}", e);
+            }
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause.lock().map(|s| s.paused).unwrap_or(false)
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        let mut state = self.pause.lock().map_err(|e| e.to_string())?;
+        if !state.paused {
+            state.last_emitted = self.db.last_message_id()?;
+            state.paused = true;
+        }
+        Ok(())
+    }
+
+    /// 结束暂停并补发暂停期间存储的消息，返回补发的条数
+    pub fn resume(&self) -> Result<usize, String> {
+        let mut replayed = 0;
+        loop {
+            // 补发期间保持暂停，新消息只存储，由下一轮补发；
+            // 确认没有剩余消息后才在同一把锁内恢复实时推送，保证按 id 顺序送达
+            let mut batch = {
+                let mut state = self.pause.lock().map_err(|e| e.to_string())?;
+                if !state.paused {
+                    return Ok(replayed);
+                }
+                let config = FilterConfig {
+                    id: Some(NumberRange {
+                        min: Some(state.last_emitted as i64 + 1),
+                        max: None,
+                    }),
+                    ..Default::default()
+                };
+                let batch = self
+                    .db
+                    .query_messages(&config, &MessageField::Id, &1000, &0, false)?;
+                match batch.last() {
+                    Some(last) => state.last_emitted = last.id,
+                    None => {
+                        state.paused = false;
+                        return Ok(replayed);
+                    }
+                }
+                batch
+            };
+            replayed += batch.len();
+            self.annotate(&mut batch);
+            for message in &batch {
+                self.subscriptions.dispatch(message);
+            }
+            self.notify(&PipelineEvent::Replayed(&batch));
+        }
+    }
+
+    // 重新加载作为告警来源的保存搜索，在切换数据库或修改搜索后调用
+    pub fn reload_alerts(&self) -> Result<(), String> {
+        let alerts = self
+            .db
+            .list_searches()?
+            .into_iter()
+            .filter(|s| s.alert)
+            .collect();
+        *self.alerts.write().map_err(|e| e.to_string())? = alerts;
+        Ok(())
+    }
+
+    pub fn set_rules(&self, settings: &Settings) -> Result<(), String> {
+        let matcher = RuleMatcher::new(
+            &settings.level_rule_sets,
+            &settings.role_rule_sets,
+            &settings.label_rule_sets,
+        )?;
+        *self.rules.write().map_err(|e| e.to_string())? = matcher;
+        Ok(())
+    }
+
+    /// 设置保存后调用：重建规则匹配器并通知监听者
+    pub fn settings_changed(&self, settings: &Settings) -> Result<(), String> {
+        self.set_rules(settings)?;
+        self.notify(&PipelineEvent::SettingsChanged(settings));
+        Ok(())
+    }
+
+    // 连接数据库时加载规则集，存储的规则无效时保留空规则，不影响连接
+    pub fn load_rules(&self) {
+        if let Ok(settings) = Settings::load(&self.db) {
+            let _ = self.set_rules(&settings);
+        }
+    }
+
+    pub fn annotate(&self, messages: &mut [DBMessage]) {
+        let rules = self.rules.read().unwrap();
+        for message in messages {
+            rules.annotate(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{PatternMode, StringPattern};
+    use crate::testing::{message, TestDb};
+
+    // 把事件记录为简短的文本，便于比较顺序
+    fn record(pipeline: &MessagePipeline) -> Arc<Mutex<Vec<String>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        pipeline.add_listener(move |event| {
+            let text = match event {
+                PipelineEvent::Message(m) => format!("message {}", m.id),
+                PipelineEvent::Alert(a) => format!("alert {} {}", a.name, a.message.id),
+                PipelineEvent::Replayed(batch) => {
+                    let ids: Vec<_> = batch.iter().map(|m| m.id.to_string()).collect();
+                    format!("replayed {}", ids.join(","))
+                }
+                PipelineEvent::SettingsChanged(_) => "settings".to_string(),
+            };
+            sink.lock().unwrap().push(text);
+        });
+        events
+    }
+
+    fn take(events: &Mutex<Vec<String>>) -> Vec<String> {
+        std::mem::take(&mut *events.lock().unwrap())
+    }
+
+    #[test]
+    fn paused_messages_are_stored_and_replayed_in_order() {
+        let db = TestDb::new();
+        let pipeline = db.pipeline();
+        let events = record(&pipeline);
+        pipeline.ingest(&message("live")).unwrap();
+        assert_eq!(take(&events), ["message 1"]);
+
+        pipeline.pause().unwrap();
+        assert!(pipeline.is_paused());
+        pipeline.ingest(&message("held 1")).unwrap();
+        pipeline
+            .ingest_batch(&[message("held 2"), message("held 3")])
+            .unwrap();
+        assert!(take(&events).is_empty());
+        assert_eq!(db.get_message_count().unwrap(), 4);
+
+        assert_eq!(pipeline.resume().unwrap(), 3);
+        assert!(!pipeline.is_paused());
+        assert_eq!(take(&events), ["replayed 2,3,4"]);
+        assert_eq!(pipeline.resume().unwrap(), 0);
+
+        pipeline.ingest(&message("live again")).unwrap();
+        assert_eq!(take(&events), ["message 5"]);
+    }
+
+    #[test]
+    fn messages_arriving_during_replay_follow_it() {
+        let db = TestDb::new();
+        let pipeline = db.pipeline();
+        let events = record(&pipeline);
+        pipeline.pause().unwrap();
+        pipeline.ingest(&message("held")).unwrap();
+
+        // 第一批补发时到达的消息应在补发之后按顺序送达，而不是抢先实时推送
+        let weak = Arc::downgrade(&pipeline);
+        pipeline.add_listener(move |event| {
+            if let (PipelineEvent::Replayed(batch), Some(pipeline)) = (event, weak.upgrade()) {
+                if batch[0].id == 1 {
+                    pipeline.ingest(&message("during replay")).unwrap();
+                }
+            }
+        });
+        assert_eq!(pipeline.resume().unwrap(), 2);
+        assert_eq!(take(&events), ["replayed 1", "replayed 2"]);
+        pipeline.ingest(&message("after")).unwrap();
+        assert_eq!(take(&events), ["message 3"]);
+    }
+
+    #[test]
+    fn alert_searches_fire_even_while_paused() {
+        let db = TestDb::new();
+        let search = |name: &str, alert| SavedSearch {
+            id: 0,
+            name: name.to_string(),
+            config: FilterConfig {
+                role: Some(StringPattern {
+                    mode: PatternMode::Equal,
+                    value: "net".to_string(),
+                }),
+                ..Default::default()
+            },
+            order_by: MessageField::Id,
+            desc: false,
+            pinned: false,
+            alert,
+            created_at: String::new(),
+        };
+        db.create_search(&search("network", true)).unwrap();
+        db.create_search(&search("quiet", false)).unwrap();
+        let pipeline = db.pipeline();
+        pipeline.reload_alerts().unwrap();
+        let events = record(&pipeline);
+        let net = |text| MessageData {
+            role: "net".to_string(),
+            ..message(text)
+        };
+
+        pipeline.ingest(&net("reset")).unwrap();
+        pipeline.ingest(&message("other")).unwrap();
+        assert_eq!(take(&events), ["alert network 1", "message 1", "message 2"]);
+
+        pipeline.pause().unwrap();
+        pipeline
+            .ingest_batch(&[net("held"), message("held")])
+            .unwrap();
+        assert_eq!(take(&events), ["alert network 3"]);
+        pipeline.resume().unwrap();
+        assert_eq!(take(&events), ["replayed 3,4"]);
+    }
+}
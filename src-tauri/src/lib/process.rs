@@ -0,0 +1,404 @@
+use crate::db::SessionDB;
+use crate::pipeline::MessagePipeline;
+use crate::template::now_micros;
+use msg_server::level::{parse_level, ERROR, INFO};
+use msg_server::MessageData;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// 发送 SIGTERM 后等待进程退出的时间，超时后强制结束
+const STOP_GRACE: Duration = Duration::from_secs(2);
+const STOP_POLL: Duration = Duration::from_millis(20);
+
+// 调试时从查看器启动的程序。子进程输出的每一行成为一条消息，角色为程序名，
+// 标签为 `stdout` / `stderr`。每次启动（包括重启）都新建一个会话，来源为命令行，
+// 以便区分不同的运行。
+
+/// 被捕获程序的启动方式
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProcessSpec {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// 追加到从查看器继承的环境变量中
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// 正则表达式的 `level` 分组（没有时取第一个分组）给出行的级别，例如
+    /// `\[(?P<level>\w+)\]`。不匹配的行以及未设置正则时的所有行，stdout 为 INFO，
+    /// stderr 为 ERROR。
+    #[serde(default)]
+    pub level_pattern: Option<String>,
+}
+
+impl ProcessSpec {
+    /// 捕获消息的角色
+    pub fn name(&self) -> String {
+        Path::new(&self.program)
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or(self.program.clone())
+    }
+
+    pub fn command_line(&self) -> String {
+        std::iter::once(&self.program)
+            .chain(&self.args)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessInfo {
+    pub id: u64,
+    pub spec: ProcessSpec,
+    pub pid: u32,
+    pub session_id: i64,
+    pub running: bool,
+    /// 进程结束后的退出码，运行中或被信号结束时为 `None`
+    pub exit_code: Option<i32>,
+}
+
+fn line_level(pattern: &Regex, line: &str) -> Option<i32> {
+    let caps = pattern.captures(line)?;
+    caps.name("level")
+        .or_else(|| caps.get(1))
+        .and_then(|m| parse_level(m.as_str()))
+}
+
+struct CapturedProcess {
+    id: u64,
+    spec: ProcessSpec,
+    session_id: i64,
+    child: Child,
+}
+
+impl CapturedProcess {
+    fn info(&mut self) -> ProcessInfo {
+        let status = self.child.try_wait().ok().flatten();
+        ProcessInfo {
+            id: self.id,
+            spec: self.spec.clone(),
+            pid: self.child.id(),
+            session_id: self.session_id,
+            running: status.is_none(),
+            exit_code: status.and_then(|s| s.code()),
+        }
+    }
+
+    // 先请求进程退出，超过 `STOP_GRACE` 后强制结束
+    fn stop(&mut self) -> Result<(), String> {
+        if self.child.try_wait().map_err(|e| e.to_string())?.is_some() {
+            return Ok(());
+        }
+        if terminate(&self.child) {
+            let deadline = Instant::now() + STOP_GRACE;
+            while Instant::now() < deadline {
+                if self.child.try_wait().map_err(|e| e.to_string())?.is_some() {
+                    return Ok(());
+                }
+                std::thread::sleep(STOP_POLL);
+            }
+        }
+        self.child
+            .kill()
+            .map_err(|e| format!("failed to stop {}: {}", self.spec.name(), e))?;
+        self.child.wait().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+// 发送 SIGTERM，返回是否发送成功
+#[cfg(unix)]
+fn terminate(child: &Child) -> bool {
+    // SAFETY: kill(2) 只接收整数参数；子进程尚未被回收，pid 不会被复用
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) == 0 }
+}
+
+// 没有可用的终止信号，直接结束进程
+#[cfg(not(unix))]
+fn terminate(_child: &Child) -> bool {
+    false
+}
+
+// 捕获的行写入的目标
+struct Capture {
+    role: String,
+    source: String,
+    pid: u32,
+    session_id: i64,
+    level_pattern: Option<Regex>,
+}
+
+fn capture<R>(
+    stream: R,
+    label: &'static str,
+    default_level: i32,
+    target: Arc<Capture>,
+    pipeline: Arc<MessagePipeline>,
+) where
+    R: Read + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        let mut buffer = Vec::new();
+        // 子进程及继承了管道的进程全部退出后结束
+        while matches!(reader.read_until(b'\n', &mut buffer), Ok(size) if size > 0) {
+            let line = String::from_utf8_lossy(&buffer);
+            let line = line.trim_end_matches(['\r', '\n']);
+            let data = MessageData {
+                role: target.role.clone(),
+                label: label.to_string(),
+                file: String::new(),
+                function: String::new(),
+                time: now_micros(),
+                process_id: target.pid as usize,
+                thread_id: 0,
+                line: 0,
+                level: target
+                    .level_pattern
+                    .as_ref()
+                    .and_then(|pattern| line_level(pattern, line))
+                    .unwrap_or(default_level),
+                messages: vec![line.to_string()],
+                fields: BTreeMap::new(),
+            };
+            let session = Some(target.session_id);
+            if let Err(e) = pipeline.ingest_into(&data, session, Some(&target.source)) {
+                eprintln!("failed to ingest output of {}: {}", target.role, e);
+            }
+            buffer.clear();
+        }
+    });
+}
+
+fn spawn(spec: &ProcessSpec, pipeline: &Arc<MessagePipeline>) -> Result<(i64, Child), String> {
+    if spec.program.trim().is_empty() {
+        return Err("program cannot be empty".to_string());
+    }
+    let level_pattern = match &spec.level_pattern {
+        Some(pattern) => {
+            Some(Regex::new(pattern).map_err(|e| format!("invalid level pattern: {}", e))?)
+        }
+        None => None,
+    };
+    let mut command = Command::new(&spec.program);
+    command
+        .args(&spec.args)
+        .envs(&spec.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(cwd) = &spec.cwd {
+        command.current_dir(cwd);
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("failed to launch {}: {}", spec.program, e))?;
+    let session_id = match pipeline
+        .db
+        .create_session(&spec.name(), Some(&spec.command_line()))
+    {
+        Ok(session_id) => session_id,
+        Err(e) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+    };
+    let target = Arc::new(Capture {
+        role: spec.name(),
+        source: spec.command_line(),
+        pid: child.id(),
+        session_id,
+        level_pattern,
+    });
+    if let Some(stdout) = child.stdout.take() {
+        capture(stdout, "stdout", INFO, target.clone(), pipeline.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        capture(stderr, "stderr", ERROR, target, pipeline.clone());
+    }
+    Ok((session_id, child))
+}
+
+/// 从应用启动的子进程，析构时停止
+#[derive(Default)]
+pub struct ProcessManager {
+    next_id: Mutex<u64>,
+    processes: Mutex<Vec<CapturedProcess>>,
+}
+
+impl ProcessManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn launch(
+        &self,
+        spec: ProcessSpec,
+        pipeline: &Arc<MessagePipeline>,
+    ) -> Result<ProcessInfo, String> {
+        let (session_id, child) = spawn(&spec, pipeline)?;
+        let id = {
+            let mut next_id = self.next_id.lock().map_err(|e| e.to_string())?;
+            *next_id += 1;
+            *next_id
+        };
+        let mut process = CapturedProcess {
+            id,
+            spec,
+            session_id,
+            child,
+        };
+        let info = process.info();
+        self.processes
+            .lock()
+            .map_err(|e| e.to_string())?
+            .push(process);
+        Ok(info)
+    }
+
+    pub fn list(&self) -> Result<Vec<ProcessInfo>, String> {
+        Ok(self
+            .processes
+            .lock()
+            .map_err(|e| e.to_string())?
+            .iter_mut()
+            .map(|p| p.info())
+            .collect())
+    }
+
+    pub fn stop(&self, id: u64) -> Result<ProcessInfo, String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        let process = find(&mut processes, id)?;
+        process.stop()?;
+        Ok(process.info())
+    }
+
+    /// 必要时停止进程，然后在新会话中重新启动，id 保持不变
+    pub fn restart(&self, id: u64, pipeline: &Arc<MessagePipeline>) -> Result<ProcessInfo, String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        let process = find(&mut processes, id)?;
+        process.stop()?;
+        let (session_id, child) = spawn(&process.spec, pipeline)?;
+        process.session_id = session_id;
+        process.child = child;
+        Ok(process.info())
+    }
+
+    /// 必要时停止进程并移除，已捕获的消息保留
+    pub fn remove(&self, id: u64) -> Result<(), String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        find(&mut processes, id)?.stop()?;
+        processes.retain(|p| p.id != id);
+        Ok(())
+    }
+
+    pub fn stop_all(&self) {
+        if let Ok(mut processes) = self.processes.lock() {
+            for process in processes.iter_mut() {
+                if let Err(e) = process.stop() {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+    }
+}
+
+fn find(processes: &mut [CapturedProcess], id: u64) -> Result<&mut CapturedProcess, String> {
+    processes
+        .iter_mut()
+        .find(|p| p.id == id)
+        .ok_or_else(|| format!("no process with id {}", id))
+}
+
+impl Drop for ProcessManager {
+    fn drop(&mut self) {
+        self.stop_all();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use crate::db::{FilterConfig, MessageDB, MessageField};
+    use crate::testing::TestDb;
+
+    #[test]
+    fn captures_both_streams_into_a_session() {
+        let db = TestDb::new();
+        let pipeline = db.pipeline();
+        let manager = ProcessManager::new();
+        let spec = ProcessSpec {
+            program: "/bin/sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                "echo \"$GREETING\"; echo '[warn] careful' >&2; echo oops >&2".to_string(),
+            ],
+            env: BTreeMap::from([("GREETING".to_string(), "hello".to_string())]),
+            cwd: None,
+            level_pattern: Some(r"^\[(\w+)\]".to_string()),
+        };
+        let info = manager.launch(spec, &pipeline).unwrap();
+        let restarted = manager.restart(info.id, &pipeline).unwrap();
+        assert_ne!(info.session_id, restarted.session_id);
+        std::thread::sleep(Duration::from_millis(500));
+        assert!(!manager.list().unwrap()[0].running);
+
+        let mut messages = db
+            .query_messages(&FilterConfig::default(), &MessageField::Id, &100, &0, false)
+            .unwrap();
+        messages.retain(|m| m.process_id == restarted.pid as usize);
+        messages.sort_by_key(|m| m.label.clone());
+        let summary: Vec<_> = messages
+            .iter()
+            .map(|m| {
+                (
+                    m.role.as_str(),
+                    m.label.as_str(),
+                    m.level,
+                    m.messages[0].as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("sh", "stderr", 3, "[warn] careful"),
+                ("sh", "stderr", 4, "oops"),
+                ("sh", "stdout", 2, "hello"),
+            ]
+        );
+    }
+
+    #[test]
+    fn stop_lets_the_process_exit_cleanly() {
+        let db = TestDb::new();
+        let pipeline = db.pipeline();
+        let manager = ProcessManager::new();
+        let spec = ProcessSpec {
+            program: "/bin/sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                "trap 'exit 3' TERM; while :; do sleep 0.05; done".to_string(),
+            ],
+            env: BTreeMap::new(),
+            cwd: None,
+            level_pattern: None,
+        };
+        let info = manager.launch(spec, &pipeline).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        let stopped = manager.stop(info.id).unwrap();
+        assert!(!stopped.running);
+        assert_eq!(stopped.exit_code, Some(3));
+    }
+}
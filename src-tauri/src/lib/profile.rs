@@ -0,0 +1,299 @@
+use crate::rules::{LabelRuleSet, LevelRuleSet, RoleRuleSet, RuleSet};
+use crate::settings::{Retention, Settings, Theme};
+use msg_server::zmq_support::SocketMode;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const PROFILE_FORMAT: &str = "xclogger-profile";
+pub const PROFILE_VERSION: u32 = 1;
+
+/// 可共享的设置子集。监听地址与机器相关，不包含在配置档案中。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub format: String,
+    pub version: u32,
+    pub created_at: String,
+    pub theme: Theme,
+    pub socket_mode: SocketMode,
+    #[serde(default)]
+    pub retention: Retention,
+    #[serde(default)]
+    pub level_rule_sets: Vec<LevelRuleSet>,
+    #[serde(default)]
+    pub role_rule_sets: Vec<RoleRuleSet>,
+    #[serde(default)]
+    pub label_rule_sets: Vec<LabelRuleSet>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileFormat {
+    Json,
+    Toml,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileStrategy {
+    /// 保留配置档案未提及的本地规则集，冲突时以配置档案为准
+    Merge,
+    /// 删除配置档案中没有的所有内容
+    Replace,
+}
+
+/// 与配置档案不同并被覆盖的本地值
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProfileConflict {
+    pub key: String,
+    pub local: serde_json::Value,
+    pub profile: serde_json::Value,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ProfileReport {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub conflicts: Vec<ProfileConflict>,
+    /// 保留本地值的键，例如服务运行时的套接字模式
+    pub skipped: Vec<String>,
+}
+
+impl ProfileReport {
+    /// 将 `key` 记录为跳过而非覆盖
+    pub fn skip(&mut self, key: &str) {
+        self.conflicts.retain(|c| c.key != key);
+        self.skipped.push(key.to_string());
+    }
+}
+
+impl ProfileFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(ProfileFormat::Json),
+            "toml" => Some(ProfileFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+impl Profile {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            format: PROFILE_FORMAT.to_string(),
+            version: PROFILE_VERSION,
+            created_at: chrono::Local::now().to_rfc3339(),
+            theme: settings.theme,
+            socket_mode: settings.socket_mode,
+            retention: settings.retention.clone(),
+            level_rule_sets: settings.level_rule_sets.clone(),
+            role_rule_sets: settings.role_rule_sets.clone(),
+            label_rule_sets: settings.label_rule_sets.clone(),
+        }
+    }
+
+    pub fn write(&self, path: &Path, format: ProfileFormat) -> Result<(), String> {
+        let text = match format {
+            ProfileFormat::Json => serde_json::to_string_pretty(self).map_err(|e| e.to_string())?,
+            ProfileFormat::Toml => toml::to_string_pretty(self).map_err(|e| e.to_string())?,
+        };
+        std::fs::write(path, text).map_err(|e| format!("failed to write profile: {}", e))
+    }
+
+    /// 读取配置档案，按扩展名选择格式，扩展名未知时依次尝试 JSON 和 TOML
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("failed to read profile: {}", e))?;
+        let profile: Profile = match ProfileFormat::from_path(path) {
+            Some(ProfileFormat::Json) => serde_json::from_str(&text).map_err(|e| e.to_string())?,
+            Some(ProfileFormat::Toml) => toml::from_str(&text).map_err(|e| e.to_string())?,
+            None => serde_json::from_str(&text)
+                .or_else(|_| toml::from_str(&text))
+                .map_err(|e| format!("unrecognized profile format: {}", e))?,
+        };
+        if profile.format != PROFILE_FORMAT || profile.version > PROFILE_VERSION {
+            return Err(format!(
+                "unsupported profile format: {} v{}",
+                profile.format, profile.version
+            ));
+        }
+        Ok(profile)
+    }
+
+    /// 将配置档案应用到 `settings` 上并报告改动
+    pub fn apply(
+        &self,
+        settings: &Settings,
+        strategy: ProfileStrategy,
+    ) -> (Settings, ProfileReport) {
+        let mut merged = settings.clone();
+        let mut report = ProfileReport::default();
+        scalar(&mut report, "theme", &settings.theme, &self.theme);
+        scalar(
+            &mut report,
+            "socket_mode",
+            &settings.socket_mode,
+            &self.socket_mode,
+        );
+        scalar(
+            &mut report,
+            "retention",
+            &settings.retention,
+            &self.retention,
+        );
+        merged.theme = self.theme;
+        merged.socket_mode = self.socket_mode;
+        merged.retention = self.retention.clone();
+        merged.level_rule_sets = merge_sets(
+            &mut report,
+            "level_rule_sets",
+            &settings.level_rule_sets,
+            &self.level_rule_sets,
+            strategy,
+        );
+        merged.role_rule_sets = merge_sets(
+            &mut report,
+            "role_rule_sets",
+            &settings.role_rule_sets,
+            &self.role_rule_sets,
+            strategy,
+        );
+        merged.label_rule_sets = merge_sets(
+            &mut report,
+            "label_rule_sets",
+            &settings.label_rule_sets,
+            &self.label_rule_sets,
+            strategy,
+        );
+        (merged, report)
+    }
+}
+
+fn to_value<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or(serde_json::Value::Null)
+}
+
+fn scalar<T: Serialize + PartialEq>(report: &mut ProfileReport, key: &str, local: &T, profile: &T) {
+    if local != profile {
+        report.conflicts.push(ProfileConflict {
+            key: key.to_string(),
+            local: to_value(local),
+            profile: to_value(profile),
+        });
+    }
+}
+
+// 规则集按名称匹配；保持本地顺序，新规则集追加在末尾
+fn merge_sets<T: Serialize + PartialEq + Clone>(
+    report: &mut ProfileReport,
+    key: &str,
+    local: &[RuleSet<T>],
+    profile: &[RuleSet<T>],
+    strategy: ProfileStrategy,
+) -> Vec<RuleSet<T>> {
+    let mut merged = Vec::new();
+    for set in local {
+        let path = format!("{}/{}", key, set.name);
+        match profile.iter().find(|p| p.name == set.name) {
+            Some(incoming) => {
+                if incoming != set {
+                    report.conflicts.push(ProfileConflict {
+                        key: path,
+                        local: to_value(set),
+                        profile: to_value(incoming),
+                    });
+                }
+                merged.push(incoming.clone());
+            }
+            None if strategy == ProfileStrategy::Merge => merged.push(set.clone()),
+            None => report.removed.push(path),
+        }
+    }
+    for set in profile {
+        if !local.iter().any(|l| l.name == set.name) {
+            report.added.push(format!("{}/{}", key, set.name));
+            merged.push(set.clone());
+        }
+    }
+    // 第一个启用的规则集为当前规则集，因此替换时保持配置档案中的顺序
+    if strategy == ProfileStrategy::Replace {
+        return profile.to_vec();
+    }
+    merged
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rules::{ChipStyle, ChipStyleKind, PatternRule, RuleAction};
+
+    fn role_set(name: &str, pattern: &str) -> RoleRuleSet {
+        RuleSet {
+            name: name.to_string(),
+            rules: vec![PatternRule {
+                pattern: pattern.to_string(),
+                mode: RuleAction::Equal,
+                style: ChipStyle {
+                    color: "red".to_string(),
+                    style: ChipStyleKind::Fill,
+                    text: None,
+                },
+            }],
+            disabled: false,
+        }
+    }
+
+    #[test]
+    fn merge_and_replace() {
+        let local = Settings {
+            role_rule_sets: vec![role_set("mine", "a"), role_set("team", "a")],
+            ..Default::default()
+        };
+        let mut profile = Profile::from_settings(&Settings::default());
+        profile.theme = Theme::Dark;
+        profile.role_rule_sets = vec![role_set("team", "b"), role_set("new", "c")];
+
+        let (merged, report) = profile.apply(&local, ProfileStrategy::Merge);
+        let names: Vec<_> = merged
+            .role_rule_sets
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(names, ["mine", "team", "new"]);
+        assert_eq!(merged.role_rule_sets[1], role_set("team", "b"));
+        assert_eq!(merged.theme, Theme::Dark);
+        assert_eq!(report.added, ["role_rule_sets/new"]);
+        let keys: Vec<_> = report.conflicts.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(keys, ["theme", "role_rule_sets/team"]);
+
+        let (replaced, report) = profile.apply(&local, ProfileStrategy::Replace);
+        assert_eq!(replaced.role_rule_sets, profile.role_rule_sets);
+        assert_eq!(report.removed, ["role_rule_sets/mine"]);
+    }
+
+    #[test]
+    fn skipped_keys_are_not_conflicts() {
+        let mut profile = Profile::from_settings(&Settings::default());
+        profile.socket_mode = SocketMode::Pull;
+        let (_, mut report) = profile.apply(&Settings::default(), ProfileStrategy::Merge);
+        assert_eq!(report.conflicts[0].key, "socket_mode");
+        report.skip("socket_mode");
+        assert!(report.conflicts.is_empty());
+        assert_eq!(report.skipped, ["socket_mode"]);
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let settings = Settings {
+            role_rule_sets: vec![role_set("team", "net")],
+            retention: Retention {
+                max_messages: Some(1000),
+                max_age_days: None,
+            },
+            ..Default::default()
+        };
+        let profile = Profile::from_settings(&settings);
+        let text = toml::to_string_pretty(&profile).unwrap();
+        let parsed: Profile = toml::from_str(&text).unwrap();
+        assert_eq!(parsed, profile);
+    }
+}
@@ -0,0 +1,310 @@
+use crate::db::{FilterConfig, MessageField, NumberRange, PatternMode, StringPattern};
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use msg_server::level::parse_level;
+
+// `FilterConfig` 的文本形式，供命令行工具使用：
+//
+//   level>=warn role=net label~conn file^src/ time>=-15m id=10..20 "connection reset"
+//
+// 字符串字段支持 `=`（等于）、`~`（包含）、`^`（开头为）和 `$`（结尾为）。
+// 数字字段支持 `=`、`>`、`>=`、`<`、`<=` 和 `=min..max`；同一数字字段的多个条件
+// 取交集。级别可以使用名称，时间可以是微秒数、RFC 3339、本地时间
+// `YYYY-MM-DD[ HH:MM:SS]` 或相对当前时间（`-30s`、`-15m`、`-2h`、`-1d`）。
+// 其余的词在消息内容中搜索。
+
+const OPERATORS: [&str; 8] = [">=", "<=", "=", ">", "<", "~", "^", "$"];
+
+/// 接受 `MessageField::column` 使用的列名和几个简写
+pub fn parse_field(name: &str) -> Option<MessageField> {
+    let field = match name.to_ascii_lowercase().as_str() {
+        "id" => MessageField::Id,
+        "role" => MessageField::Role,
+        "label" => MessageField::Label,
+        "file" => MessageField::File,
+        "function" | "func" => MessageField::Function,
+        "time" => MessageField::Time,
+        "process_id" | "pid" => MessageField::ProcessId,
+        "thread_id" | "tid" => MessageField::ThreadId,
+        "line" => MessageField::Line,
+        "level" => MessageField::Level,
+        _ => return None,
+    };
+    Some(field)
+}
+
+/// 将时间解析为自纪元起的微秒数
+pub fn parse_time(value: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid time: {}", value);
+    if let Ok(micros) = value.parse::<i64>() {
+        return Ok(micros);
+    }
+    if let Some(ago) = value.strip_prefix('-') {
+        // 单位可能是多字节字符，按字符边界分割
+        let (split, _) = ago.char_indices().last().ok_or_else(invalid)?;
+        let (amount, unit) = ago.split_at(split);
+        let amount = amount.parse::<i64>().map_err(|_| invalid())?;
+        let scale = match unit {
+            "s" => 1_000_000,
+            "m" => 60 * 1_000_000,
+            "h" => 3600 * 1_000_000,
+            "d" => 86400 * 1_000_000,
+            _ => return Err(invalid()),
+        };
+        return amount
+            .checked_mul(scale)
+            .and_then(|micros| Local::now().timestamp_micros().checked_sub(micros))
+            .ok_or_else(invalid);
+    }
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.timestamp_micros());
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default())
+        })
+        .map_err(|_| invalid())?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.timestamp_micros())
+        .ok_or_else(invalid)
+}
+
+// 按空白分割，双引号内的值保持完整
+fn tokenize(input: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut has_token = false;
+    for c in input.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                has_token = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if has_token {
+                    tokens.push(std::mem::take(&mut current));
+                    has_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_token = true;
+            }
+        }
+    }
+    if quoted {
+        return Err("unterminated quote in filter".to_string());
+    }
+    if has_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+fn split_condition(token: &str) -> Option<(&str, &'static str, &str)> {
+    let name_len = token
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(token.len());
+    let (name, rest) = token.split_at(name_len);
+    let op = OPERATORS.iter().find(|op| rest.starts_with(**op))?;
+    Some((name, op, &rest[op.len()..]))
+}
+
+fn narrow(range: &mut Option<NumberRange>, min: Option<i64>, max: Option<i64>) {
+    let current = range.get_or_insert(NumberRange {
+        min: None,
+        max: None,
+    });
+    if let Some(min) = min {
+        current.min = Some(current.min.map_or(min, |m| m.max(min)));
+    }
+    if let Some(max) = max {
+        current.max = Some(current.max.map_or(max, |m| m.min(max)));
+    }
+}
+
+fn number_condition(
+    range: &mut Option<NumberRange>,
+    op: &str,
+    value: &str,
+    parse: impl Fn(&str) -> Result<i64, String>,
+) -> Result<(), String> {
+    let out_of_range = || format!("{} is out of range", value);
+    match op {
+        "=" => match value.split_once("..") {
+            Some((min, max)) => {
+                let min = (!min.is_empty()).then(|| parse(min)).transpose()?;
+                let max = (!max.is_empty()).then(|| parse(max)).transpose()?;
+                narrow(range, min, max);
+            }
+            None => {
+                let value = parse(value)?;
+                narrow(range, Some(value), Some(value));
+            }
+        },
+        ">" => narrow(
+            range,
+            Some(parse(value)?.checked_add(1).ok_or_else(out_of_range)?),
+            None,
+        ),
+        ">=" => narrow(range, Some(parse(value)?), None),
+        "<" => narrow(
+            range,
+            None,
+            Some(parse(value)?.checked_sub(1).ok_or_else(out_of_range)?),
+        ),
+        "<=" => narrow(range, None, Some(parse(value)?)),
+        _ => return Err(format!("operator {} does not apply to numbers", op)),
+    }
+    Ok(())
+}
+
+fn string_condition(
+    pattern: &mut Option<StringPattern>,
+    name: &str,
+    op: &str,
+    value: &str,
+) -> Result<(), String> {
+    let mode = match op {
+        "=" => PatternMode::Equal,
+        "~" => PatternMode::Contain,
+        "^" => PatternMode::Start,
+        "$" => PatternMode::End,
+        _ => return Err(format!("operator {} does not apply to {}", op, name)),
+    };
+    if pattern.is_some() {
+        return Err(format!("{} can only be filtered once", name));
+    }
+    *pattern = Some(StringPattern {
+        mode,
+        value: value.to_string(),
+    });
+    Ok(())
+}
+
+fn parse_number(value: &str) -> Result<i64, String> {
+    value
+        .parse::<i64>()
+        .map_err(|_| format!("invalid number: {}", value))
+}
+
+fn parse_level_value(value: &str) -> Result<i64, String> {
+    parse_level(value)
+        .map(i64::from)
+        .ok_or_else(|| format!("invalid level: {}", value))
+}
+
+/// 解析本模块开头描述的过滤语法
+pub fn parse_filter(input: &str) -> Result<FilterConfig, String> {
+    let mut config = FilterConfig::default();
+    let mut words = Vec::new();
+    for token in tokenize(input)? {
+        let condition = split_condition(&token).and_then(|(name, op, value)| {
+            let is_message = matches!(name, "msg" | "message" | "messages");
+            (is_message || parse_field(name).is_some()).then_some((name, op, value))
+        });
+        let Some((name, op, value)) = condition else {
+            words.push(token);
+            continue;
+        };
+        match parse_field(name) {
+            Some(MessageField::Id) => number_condition(&mut config.id, op, value, parse_number)?,
+            Some(MessageField::Role) => string_condition(&mut config.role, name, op, value)?,
+            Some(MessageField::Label) => string_condition(&mut config.label, name, op, value)?,
+            Some(MessageField::File) => string_condition(&mut config.file, name, op, value)?,
+            Some(MessageField::Function) => {
+                string_condition(&mut config.function, name, op, value)?
+            }
+            Some(MessageField::Time) => number_condition(&mut config.time, op, value, parse_time)?,
+            Some(MessageField::ProcessId) => {
+                number_condition(&mut config.process_id, op, value, parse_number)?
+            }
+            Some(MessageField::ThreadId) => {
+                number_condition(&mut config.thread_id, op, value, parse_number)?
+            }
+            Some(MessageField::Line) => {
+                number_condition(&mut config.line, op, value, parse_number)?
+            }
+            Some(MessageField::Level) => {
+                number_condition(&mut config.level, op, value, parse_level_value)?
+            }
+            None => string_condition(&mut config.messages, name, op, value)?,
+        }
+    }
+    if !words.is_empty() {
+        if config.messages.is_some() {
+            return Err("msg can only be filtered once".to_string());
+        }
+        config.messages = Some(StringPattern {
+            mode: PatternMode::Contain,
+            value: words.join(" "),
+        });
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conditions_and_words() {
+        let config =
+            parse_filter(r#"level>=warn level<5 role=net file^src/ id=10..20 "conn reset" again"#)
+                .unwrap();
+        let level = config.level.unwrap();
+        assert_eq!((level.min, level.max), (Some(3), Some(4)));
+        let id = config.id.unwrap();
+        assert_eq!((id.min, id.max), (Some(10), Some(20)));
+        let role = config.role.unwrap();
+        assert!(matches!(role.mode, PatternMode::Equal));
+        assert_eq!(role.value, "net");
+        assert!(matches!(config.file.unwrap().mode, PatternMode::Start));
+        let messages = config.messages.unwrap();
+        assert!(matches!(messages.mode, PatternMode::Contain));
+        assert_eq!(messages.value, "conn reset again");
+    }
+
+    #[test]
+    fn unknown_names_are_words_and_bad_values_fail() {
+        let config = parse_filter("a=b").unwrap();
+        assert_eq!(config.messages.unwrap().value, "a=b");
+        assert!(parse_filter("role>net").is_err());
+        assert!(parse_filter("level=loud").is_err());
+        assert!(parse_filter("role=a role=b").is_err());
+        assert!(parse_filter("\"open").is_err());
+        assert!(parse_filter(&format!("id>{}", i64::MAX)).is_err());
+        assert!(parse_filter(&format!("id<{}", i64::MIN)).is_err());
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(
+            parse_time("1700000000000000").unwrap(),
+            1_700_000_000_000_000
+        );
+        assert_eq!(
+            parse_time("2023-11-14T22:13:20Z").unwrap(),
+            1_700_000_000_000_000
+        );
+        let time = parse_time("-15m").unwrap();
+        let ago = Local::now().timestamp_micros() - time;
+        assert!((900_000_000..901_000_000).contains(&ago));
+        assert!(parse_time("-15y").is_err());
+        assert!(parse_time("-").is_err());
+        assert!(parse_time("-5é").is_err());
+        assert!(parse_time("-15分").is_err());
+    }
+
+    #[test]
+    fn relative_time_overflow_is_an_error() {
+        assert!(parse_time(&format!("-{}d", i64::MAX)).is_err());
+        assert!(parse_time(&format!("-{}s", i64::MAX / 1_000_000 + 1)).is_err());
+        assert!(parse_time(&format!("-{}d", i64::MIN)).is_err());
+        assert!(parse_filter(&format!("time>=-{}h", i64::MAX / 1000)).is_err());
+    }
+}
@@ -0,0 +1,330 @@
+use crate::db::DBMessage;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// `src/api/rules.ts` 中规则集的 Rust 实现。序列化格式与
+// `dumpLevelRuleSetList` / `dumpRoleLabelRuleSetList` 一致，前端通过
+// `config_set` 写入的值可以直接加载。
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RuleAction {
+    Equal,
+    NotEqual,
+    Contains,
+    NotContains,
+    Regex,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChipStyleKind {
+    Outline,
+    Fill,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChipStyle {
+    pub color: String,
+    pub style: ChipStyleKind,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+/// 精确匹配级别，级别为 `None` 的规则是该规则集的兜底规则
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LevelRule {
+    pub level: Option<i32>,
+    #[serde(flatten)]
+    pub style: ChipStyle,
+}
+
+/// 匹配角色或标签，空模式是该规则集的兜底规则
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PatternRule {
+    pub pattern: String,
+    pub mode: RuleAction,
+    #[serde(flatten)]
+    pub style: ChipStyle,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RuleSet<T> {
+    pub name: String,
+    pub rules: Vec<T>,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+pub type LevelRuleSet = RuleSet<LevelRule>;
+pub type RoleRuleSet = RuleSet<PatternRule>;
+pub type LabelRuleSet = RuleSet<PatternRule>;
+
+impl PatternRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.mode == RuleAction::Regex {
+            regex::Regex::new(&self.pattern)
+                .map_err(|e| format!("invalid regex rule {:?}: {}", self.pattern, e))?;
+        }
+        Ok(())
+    }
+}
+
+impl RuleSet<PatternRule> {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("rule set name cannot be empty".to_string());
+        }
+        self.rules.iter().try_for_each(PatternRule::validate)
+    }
+}
+
+impl RuleSet<LevelRule> {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("rule set name cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 以 `(规则集下标, 规则在集中的下标)` 指向一条规则
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StyleId {
+    pub set: usize,
+    pub rule: usize,
+}
+
+/// 消息对应的样式，`None` 表示没有适用的规则，前端使用默认样式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MessageStyles {
+    pub level: Option<StyleId>,
+    pub role: Option<StyleId>,
+    pub label: Option<StyleId>,
+}
+
+impl RuleAction {
+    fn matches(&self, pattern: &str, regex: Option<&Regex>, value: &str) -> bool {
+        match self {
+            RuleAction::Equal => value == pattern,
+            RuleAction::NotEqual => value != pattern,
+            RuleAction::Contains => value.contains(pattern),
+            RuleAction::NotContains => !value.contains(pattern),
+            RuleAction::Regex => regex.is_some_and(|r| r.is_match(value)),
+            RuleAction::StartsWith => value.starts_with(pattern),
+            RuleAction::EndsWith => value.ends_with(pattern),
+        }
+    }
+}
+
+struct CompiledPatternSet {
+    index: usize,
+    rules: Vec<(PatternRule, Option<Regex>)>,
+}
+
+impl CompiledPatternSet {
+    // 与 `FormateMessage` 相同，只有第一个启用的规则集生效
+    fn active(sets: &[RuleSet<PatternRule>]) -> Result<Option<Self>, String> {
+        let Some((index, set)) = sets.iter().enumerate().find(|(_, s)| !s.disabled) else {
+            return Ok(None);
+        };
+        let rules = set
+            .rules
+            .iter()
+            .map(|rule| {
+                rule.validate()?;
+                let regex = match rule.mode {
+                    RuleAction::Regex => Regex::new(&rule.pattern).ok(),
+                    _ => None,
+                };
+                Ok((rule.clone(), regex))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Some(Self { index, rules }))
+    }
+
+    fn resolve(&self, value: &str) -> Option<StyleId> {
+        let matched = self
+            .rules
+            .iter()
+            .position(|(rule, regex)| rule.mode.matches(&rule.pattern, regex.as_ref(), value))
+            .or_else(|| {
+                self.rules
+                    .iter()
+                    .position(|(rule, _)| rule.pattern.is_empty())
+            });
+        matched.map(|rule| StyleId {
+            set: self.index,
+            rule,
+        })
+    }
+}
+
+/// 计算消息的级别、角色和标签样式。正则表达式在构建时编译一次。
+#[derive(Default)]
+pub struct RuleMatcher {
+    level: Option<(usize, Vec<LevelRule>)>,
+    role: Option<CompiledPatternSet>,
+    label: Option<CompiledPatternSet>,
+}
+
+impl RuleMatcher {
+    pub fn new(
+        level_sets: &[LevelRuleSet],
+        role_sets: &[RoleRuleSet],
+        label_sets: &[LabelRuleSet],
+    ) -> Result<Self, String> {
+        Ok(Self {
+            level: level_sets
+                .iter()
+                .enumerate()
+                .find(|(_, s)| !s.disabled)
+                .map(|(index, set)| (index, set.rules.clone())),
+            role: CompiledPatternSet::active(role_sets)?,
+            label: CompiledPatternSet::active(label_sets)?,
+        })
+    }
+
+    pub fn resolve_level(&self, level: i32) -> Option<StyleId> {
+        let (set, rules) = self.level.as_ref()?;
+        rules
+            .iter()
+            .position(|rule| rule.level == Some(level))
+            .or_else(|| rules.iter().position(|rule| rule.level.is_none()))
+            .map(|rule| StyleId { set: *set, rule })
+    }
+
+    pub fn resolve_role(&self, role: &str) -> Option<StyleId> {
+        self.role.as_ref()?.resolve(role)
+    }
+
+    pub fn resolve_label(&self, label: &str) -> Option<StyleId> {
+        self.label.as_ref()?.resolve(label)
+    }
+
+    pub fn resolve(&self, message: &DBMessage) -> MessageStyles {
+        MessageStyles {
+            level: self.resolve_level(message.level),
+            role: self.resolve_role(&message.role),
+            label: self.resolve_label(&message.label),
+        }
+    }
+
+    pub fn annotate(&self, message: &mut DBMessage) {
+        message.styles = Some(self.resolve(message));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn style() -> ChipStyle {
+        ChipStyle {
+            color: "red".to_string(),
+            style: ChipStyleKind::Fill,
+            text: None,
+        }
+    }
+
+    fn pattern(pattern: &str, mode: RuleAction) -> PatternRule {
+        PatternRule {
+            pattern: pattern.to_string(),
+            mode,
+            style: style(),
+        }
+    }
+
+    #[test]
+    fn parses_frontend_dump() {
+        let sets: Vec<RoleRuleSet> = serde_json::from_str(
+            r#"[{"name":"a","rules":[{"pattern":"net","mode":"startsWith","color":"blue","style":"outline","text":"N"}],"disabled":false}]"#,
+        )
+        .unwrap();
+        assert_eq!(sets[0].rules[0].mode, RuleAction::StartsWith);
+        assert_eq!(sets[0].rules[0].style.text.as_deref(), Some("N"));
+    }
+
+    #[test]
+    fn actions() {
+        let cases = [
+            (RuleAction::Equal, "net", "net", true),
+            (RuleAction::Equal, "net", "Net", false),
+            (RuleAction::NotEqual, "net", "db", true),
+            (RuleAction::Contains, "et", "network", true),
+            (RuleAction::NotContains, "et", "network", false),
+            (RuleAction::Regex, "^n.t$", "net", true),
+            (RuleAction::Regex, "^n.t$", "nets", false),
+            (RuleAction::StartsWith, "ne", "net", true),
+            (RuleAction::EndsWith, "ne", "net", false),
+        ];
+        for (action, pattern, value, expected) in cases {
+            let regex = Regex::new(pattern).ok();
+            assert_eq!(
+                action.matches(pattern, regex.as_ref(), value),
+                expected,
+                "{:?} {} {}",
+                action,
+                pattern,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn first_enabled_set_and_fallbacks() {
+        let role_sets = vec![
+            RuleSet {
+                name: "off".to_string(),
+                rules: vec![pattern("", RuleAction::Contains)],
+                disabled: true,
+            },
+            RuleSet {
+                name: "on".to_string(),
+                rules: vec![
+                    pattern("db", RuleAction::Equal),
+                    pattern("", RuleAction::Equal),
+                ],
+                disabled: false,
+            },
+        ];
+        let level_sets = vec![RuleSet {
+            name: "levels".to_string(),
+            rules: vec![
+                LevelRule {
+                    level: None,
+                    style: style(),
+                },
+                LevelRule {
+                    level: Some(4),
+                    style: style(),
+                },
+            ],
+            disabled: false,
+        }];
+        let matcher = RuleMatcher::new(&level_sets, &role_sets, &[]).unwrap();
+        assert_eq!(
+            matcher.resolve_role("db"),
+            Some(StyleId { set: 1, rule: 0 })
+        );
+        assert_eq!(
+            matcher.resolve_role("net"),
+            Some(StyleId { set: 1, rule: 1 })
+        );
+        assert_eq!(matcher.resolve_level(4), Some(StyleId { set: 0, rule: 1 }));
+        assert_eq!(matcher.resolve_level(2), Some(StyleId { set: 0, rule: 0 }));
+        assert_eq!(matcher.resolve_label("any"), None);
+    }
+
+    #[test]
+    fn invalid_regex_is_rejected() {
+        let sets = vec![RuleSet {
+            name: "bad".to_string(),
+            rules: vec![pattern("(", RuleAction::Regex)],
+            disabled: false,
+        }];
+        assert!(RuleMatcher::new(&[], &sets, &[]).is_err());
+    }
+}
@@ -0,0 +1,534 @@
+use crate::db::{Config, FilterConfig, MessageDB, MessageField, NumberRange};
+use crate::rules::{LabelRuleSet, LevelRuleSet, RoleRuleSet};
+use crate::tail::TailSource;
+use crate::template::now_micros;
+use msg_server::stream_support::StreamFormat;
+use msg_server::zmq_support::SocketMode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+pub const SETTINGS_VERSION: u32 = 1;
+pub const DEFAULT_ADDRESS: &str = "tcp://127.0.0.1:5555";
+/// `Retention::max_age_days` 的上限，保证截止时间不超出 `i64`
+pub const MAX_RETENTION_DAYS: u64 = 36_500;
+
+// 设置保存在 `app_config` 中，使用前端已有的键名，`config_get` / `config_set`
+// 与类型化接口可以同时使用。
+const VERSION_KEY: &str = "settings_version";
+const ADDRESS_KEY: &str = "address";
+const SOCKET_MODE_KEY: &str = "socket_mode";
+const RETENTION_KEY: &str = "retention";
+const THEME_KEY: &str = "theme";
+const HTTP_ADDRESS_KEY: &str = "http_address";
+const HTTP_TOKEN_KEY: &str = "http_token";
+const STREAM_LISTENERS_KEY: &str = "stream_listeners";
+const UDP_ADDRESS_KEY: &str = "udp_address";
+const SYSLOG_LISTENERS_KEY: &str = "syslog_listeners";
+const OTLP_ADDRESS_KEY: &str = "otlp_address";
+const TAIL_SOURCES_KEY: &str = "tail_sources";
+const LEVEL_RULE_SETS_KEY: &str = "level_rule_sets";
+const ROLE_RULE_SETS_KEY: &str = "role_rule_sets";
+const LABEL_RULE_SETS_KEY: &str = "label_rule_sets";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// 消息存储的保留限制，`None` 表示永久保留
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Retention {
+    pub max_messages: Option<u64>,
+    pub max_age_days: Option<u64>,
+}
+
+/// ZeroMQ 之外的普通 TCP 或 Unix 套接字监听
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamListener {
+    pub address: String,
+    #[serde(default)]
+    pub format: StreamFormat,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub address: String,
+    pub socket_mode: SocketMode,
+    pub retention: Retention,
+    pub theme: Theme,
+    /// HTTP 接口地址，例如 `127.0.0.1:7878`，`None` 表示关闭
+    pub http_address: Option<String>,
+    /// 每个 HTTP 接口请求必须携带的 Bearer 令牌，在非回环地址上提供接口时必须设置
+    pub http_token: Option<String>,
+    pub stream_listeners: Vec<StreamListener>,
+    /// UDP 数据报监听，例如 `udp://127.0.0.1:5556`，`None` 表示关闭
+    pub udp_address: Option<String>,
+    /// `udp://host:port` 或 `tcp://host:port` 上的 syslog 接收端
+    pub syslog_listeners: Vec<String>,
+    /// 同时支持 gRPC 和 HTTP 的 OpenTelemetry 日志接收端，例如 `127.0.0.1:4317`
+    pub otlp_address: Option<String>,
+    /// 服务运行时跟踪的日志文件
+    pub tail_sources: Vec<TailSource>,
+    pub level_rule_sets: Vec<LevelRuleSet>,
+    pub role_rule_sets: Vec<RoleRuleSet>,
+    pub label_rule_sets: Vec<LabelRuleSet>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            address: DEFAULT_ADDRESS.to_string(),
+            socket_mode: SocketMode::default(),
+            retention: Retention::default(),
+            theme: Theme::default(),
+            http_address: None,
+            http_token: None,
+            stream_listeners: Vec::new(),
+            udp_address: None,
+            syslog_listeners: Vec::new(),
+            otlp_address: None,
+            tail_sources: Vec::new(),
+            level_rule_sets: Vec::new(),
+            role_rule_sets: Vec::new(),
+            label_rule_sets: Vec::new(),
+        }
+    }
+}
+
+/// 桌面应用默认项目的数据库，与守护进程和命令行工具共用
+pub fn default_db_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("xclogger").join("xclogger.db"))
+}
+
+/// 桌面应用的项目登记表，见 [`ProjectDB`](crate::db::ProjectDB)
+pub fn default_registry_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("xclogger").join("projects.db"))
+}
+
+// 普通字符串不加引号存储，与前端写入的格式一致
+fn parse_plain<T: DeserializeOwned>(value: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(value.to_string())).ok()
+}
+
+fn plain<T: Serialize>(value: &T) -> Result<String, String> {
+    match serde_json::to_value(value).map_err(|e| e.to_string())? {
+        serde_json::Value::String(s) => Ok(s),
+        other => Ok(other.to_string()),
+    }
+}
+
+fn parse_json<T: DeserializeOwned>(value: &str) -> Option<T> {
+    serde_json::from_str(value).ok()
+}
+
+// 写入时不回退到默认值，无法解析的值直接报错
+fn strict_plain<T: DeserializeOwned>(key: &str, value: &str) -> Result<T, String> {
+    parse_plain(value).ok_or_else(|| format!("invalid value for {}: {}", key, value))
+}
+
+fn strict_json<T: DeserializeOwned>(key: &str, value: &str) -> Result<T, String> {
+    serde_json::from_str(value).map_err(|e| format!("invalid value for {}: {}", key, e))
+}
+
+// 空字符串表示未设置
+fn optional(value: &str) -> Option<String> {
+    Some(value.to_string()).filter(|v| !v.is_empty())
+}
+
+fn json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| e.to_string())
+}
+
+pub fn validate_address(address: &str) -> Result<(), String> {
+    let invalid = || format!("invalid server address: {}", address);
+    let (scheme, rest) = address.split_once("://").ok_or_else(invalid)?;
+    match scheme {
+        "tcp" => {
+            let (host, port) = rest.rsplit_once(':').ok_or_else(invalid)?;
+            if host.is_empty() || port.parse::<u16>().is_err() {
+                return Err(invalid());
+            }
+        }
+        "ipc" | "inproc" if !rest.is_empty() => {}
+        _ => return Err(invalid()),
+    }
+    Ok(())
+}
+
+pub fn validate_http_address(address: &str) -> Result<(), String> {
+    address
+        .parse::<SocketAddr>()
+        .map(|_| ())
+        .map_err(|_| format!("invalid HTTP address: {}", address))
+}
+
+/// HTTP 接口可以读取和删除所有消息，只有设置了令牌才允许对本机以外开放
+pub fn validate_http_access(address: &str, token: Option<&str>) -> Result<(), String> {
+    let parsed = address
+        .parse::<SocketAddr>()
+        .map_err(|_| format!("invalid HTTP address: {}", address))?;
+    if !parsed.ip().is_loopback() && token.is_none_or(str::is_empty) {
+        return Err(format!(
+            "HTTP address {} is not a loopback address, set http_token to serve the API on it",
+            address
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_stream_address(address: &str) -> Result<(), String> {
+    let invalid = || format!("invalid stream listener address: {}", address);
+    if address.starts_with("tcp://") {
+        return validate_address(address).map_err(|_| invalid());
+    }
+    match address.strip_prefix("unix://") {
+        Some(path) if cfg!(unix) && !path.is_empty() => Ok(()),
+        _ => Err(invalid()),
+    }
+}
+
+pub fn validate_udp_address(address: &str) -> Result<(), String> {
+    let invalid = || format!("invalid UDP address: {}", address);
+    let rest = address.strip_prefix("udp://").ok_or_else(invalid)?;
+    validate_address(&format!("tcp://{}", rest)).map_err(|_| invalid())
+}
+
+pub fn validate_syslog_address(address: &str) -> Result<(), String> {
+    let invalid = || format!("invalid syslog address: {}", address);
+    let rest = address
+        .strip_prefix("udp://")
+        .or_else(|| address.strip_prefix("tcp://"))
+        .ok_or_else(invalid)?;
+    validate_address(&format!("tcp://{}", rest)).map_err(|_| invalid())
+}
+
+impl Settings {
+    /// 读取存储的设置。缺失或无法解析的项使用默认值，手动改错的值不会导致应用无法使用
+    pub fn load<C: Config>(db: &C) -> Result<Self, String> {
+        let mut settings = Settings::default();
+        if let Some(version) = db.get_config(VERSION_KEY)? {
+            let version = version.parse::<u32>().unwrap_or(SETTINGS_VERSION);
+            if version > SETTINGS_VERSION {
+                return Err(format!(
+                    "settings version {} is newer than supported version {}",
+                    version, SETTINGS_VERSION
+                ));
+            }
+        }
+        if let Some(address) = db.get_config(ADDRESS_KEY)? {
+            if validate_address(&address).is_ok() {
+                settings.address = address;
+            }
+        }
+        if let Some(mode) = db
+            .get_config(SOCKET_MODE_KEY)?
+            .and_then(|v| parse_plain(&v))
+        {
+            settings.socket_mode = mode;
+        }
+        if let Some(theme) = db.get_config(THEME_KEY)?.and_then(|v| parse_plain(&v)) {
+            settings.theme = theme;
+        }
+        settings.http_token = db
+            .get_config(HTTP_TOKEN_KEY)?
+            .filter(|token| !token.is_empty());
+        settings.http_address = db.get_config(HTTP_ADDRESS_KEY)?.filter(|address| {
+            validate_http_access(address, settings.http_token.as_deref()).is_ok()
+        });
+        if let Some(listeners) = db
+            .get_config(STREAM_LISTENERS_KEY)?
+            .and_then(|v| parse_json(&v))
+        {
+            settings.stream_listeners = listeners;
+        }
+        settings.udp_address = db
+            .get_config(UDP_ADDRESS_KEY)?
+            .filter(|address| validate_udp_address(address).is_ok());
+        if let Some(listeners) = db
+            .get_config(SYSLOG_LISTENERS_KEY)?
+            .and_then(|v| parse_json(&v))
+        {
+            settings.syslog_listeners = listeners;
+        }
+        settings.otlp_address = db
+            .get_config(OTLP_ADDRESS_KEY)?
+            .filter(|address| validate_http_address(address).is_ok());
+        if let Some(sources) = db
+            .get_config(TAIL_SOURCES_KEY)?
+            .and_then(|v| parse_json(&v))
+        {
+            settings.tail_sources = sources;
+        }
+        if let Some(retention) = db.get_config(RETENTION_KEY)?.and_then(|v| parse_json(&v)) {
+            settings.retention = retention;
+        }
+        if let Some(sets) = db
+            .get_config(LEVEL_RULE_SETS_KEY)?
+            .and_then(|v| parse_json(&v))
+        {
+            settings.level_rule_sets = sets;
+        }
+        if let Some(sets) = db
+            .get_config(ROLE_RULE_SETS_KEY)?
+            .and_then(|v| parse_json(&v))
+        {
+            settings.role_rule_sets = sets;
+        }
+        if let Some(sets) = db
+            .get_config(LABEL_RULE_SETS_KEY)?
+            .and_then(|v| parse_json(&v))
+        {
+            settings.label_rule_sets = sets;
+        }
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version > SETTINGS_VERSION {
+            return Err(format!(
+                "settings version {} is newer than supported version {}",
+                self.version, SETTINGS_VERSION
+            ));
+        }
+        validate_address(&self.address)?;
+        if let Some(address) = &self.http_address {
+            validate_http_access(address, self.http_token.as_deref())?;
+        }
+        for listener in &self.stream_listeners {
+            validate_stream_address(&listener.address)?;
+        }
+        if let Some(address) = &self.udp_address {
+            validate_udp_address(address)?;
+        }
+        for address in &self.syslog_listeners {
+            validate_syslog_address(address)?;
+        }
+        if let Some(address) = &self.otlp_address {
+            validate_http_address(address)?;
+        }
+        self.tail_sources.iter().try_for_each(|s| s.validate())?;
+        if self.retention.max_messages == Some(0) || self.retention.max_age_days == Some(0) {
+            return Err("retention limits must be greater than zero".to_string());
+        }
+        if self
+            .retention
+            .max_age_days
+            .is_some_and(|days| days > MAX_RETENTION_DAYS)
+        {
+            return Err(format!(
+                "retention age must not exceed {} days",
+                MAX_RETENTION_DAYS
+            ));
+        }
+        self.level_rule_sets.iter().try_for_each(|s| s.validate())?;
+        self.role_rule_sets.iter().try_for_each(|s| s.validate())?;
+        self.label_rule_sets.iter().try_for_each(|s| s.validate())?;
+        Ok(())
+    }
+
+    /// 按 `config_set` 的键名和原始值修改对应字段，返回该键是否属于设置。
+    /// 调用者随后通过 [`save`](Self::save) 校验并写入，设置项不会绕过校验。
+    pub fn set(&mut self, key: &str, value: &str) -> Result<bool, String> {
+        match key {
+            VERSION_KEY => return Err(format!("{} cannot be written directly", key)),
+            ADDRESS_KEY => self.address = value.to_string(),
+            SOCKET_MODE_KEY => self.socket_mode = strict_plain(key, value)?,
+            THEME_KEY => self.theme = strict_plain(key, value)?,
+            HTTP_ADDRESS_KEY => self.http_address = optional(value),
+            HTTP_TOKEN_KEY => self.http_token = optional(value),
+            STREAM_LISTENERS_KEY => self.stream_listeners = strict_json(key, value)?,
+            UDP_ADDRESS_KEY => self.udp_address = optional(value),
+            SYSLOG_LISTENERS_KEY => self.syslog_listeners = strict_json(key, value)?,
+            OTLP_ADDRESS_KEY => self.otlp_address = optional(value),
+            TAIL_SOURCES_KEY => self.tail_sources = strict_json(key, value)?,
+            RETENTION_KEY => self.retention = strict_json(key, value)?,
+            LEVEL_RULE_SETS_KEY => self.level_rule_sets = strict_json(key, value)?,
+            ROLE_RULE_SETS_KEY => self.role_rule_sets = strict_json(key, value)?,
+            LABEL_RULE_SETS_KEY => self.label_rule_sets = strict_json(key, value)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn save<C: Config>(&self, db: &C) -> Result<(), String> {
+        self.validate()?;
+        // 空值表示关闭对应的接口
+        db.set_configs(&[
+            (VERSION_KEY, SETTINGS_VERSION.to_string()),
+            (ADDRESS_KEY, self.address.clone()),
+            (SOCKET_MODE_KEY, plain(&self.socket_mode)?),
+            (THEME_KEY, plain(&self.theme)?),
+            (
+                HTTP_ADDRESS_KEY,
+                self.http_address.clone().unwrap_or_default(),
+            ),
+            (HTTP_TOKEN_KEY, self.http_token.clone().unwrap_or_default()),
+            (STREAM_LISTENERS_KEY, json(&self.stream_listeners)?),
+            (
+                UDP_ADDRESS_KEY,
+                self.udp_address.clone().unwrap_or_default(),
+            ),
+            (SYSLOG_LISTENERS_KEY, json(&self.syslog_listeners)?),
+            (
+                OTLP_ADDRESS_KEY,
+                self.otlp_address.clone().unwrap_or_default(),
+            ),
+            (TAIL_SOURCES_KEY, json(&self.tail_sources)?),
+            (RETENTION_KEY, json(&self.retention)?),
+            (LEVEL_RULE_SETS_KEY, json(&self.level_rule_sets)?),
+            (ROLE_RULE_SETS_KEY, json(&self.role_rule_sets)?),
+            (LABEL_RULE_SETS_KEY, json(&self.label_rule_sets)?),
+        ])
+    }
+
+    /// 只保存监听地址，项目数据库是监听地址唯一的来源
+    pub fn save_address<C: Config>(db: &C, address: &str) -> Result<(), String> {
+        validate_address(address)?;
+        db.set_config(ADDRESS_KEY, address)
+    }
+}
+
+/// 删除超出保留限制的消息，返回删除的条数
+pub fn apply_retention<D: MessageDB>(db: &D, retention: &Retention) -> Result<usize, String> {
+    let mut removed = 0;
+    if let Some(days) = retention.max_age_days {
+        let age = days
+            .checked_mul(24 * 3600 * 1_000_000)
+            .and_then(|age| i64::try_from(age).ok())
+            .ok_or_else(|| format!("retention age of {} days is out of range", days))?;
+        let cutoff = (now_micros() as i64).saturating_sub(age);
+        removed += db.delete_messages(&FilterConfig {
+            time: Some(NumberRange {
+                min: None,
+                max: Some(cutoff - 1),
+            }),
+            ..Default::default()
+        })?;
+    }
+    if let Some(max) = retention.max_messages {
+        // 保留最新的 `max` 条，更早的全部删除
+        let oldest_dropped = db.query_messages(
+            &FilterConfig::default(),
+            &MessageField::Id,
+            &1,
+            &(max.min(i32::MAX as u64) as i32),
+            true,
+        )?;
+        if let Some(message) = oldest_dropped.first() {
+            removed += db.delete_messages(&FilterConfig {
+                id: Some(NumberRange {
+                    min: None,
+                    max: Some(message.id as i64),
+                }),
+                ..Default::default()
+            })?;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{message, TestDb};
+    use msg_server::MessageData;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let db = TestDb::new();
+        let settings = Settings {
+            address: "tcp://0.0.0.0:6000".to_string(),
+            theme: Theme::Dark,
+            http_address: Some("0.0.0.0:7878".to_string()),
+            http_token: Some("secret".to_string()),
+            udp_address: Some("udp://127.0.0.1:5556".to_string()),
+            syslog_listeners: vec!["tcp://127.0.0.1:514".to_string()],
+            retention: Retention {
+                max_messages: Some(100),
+                max_age_days: Some(7),
+            },
+            ..Default::default()
+        };
+        settings.save(&*db).unwrap();
+        assert_eq!(Settings::load(&*db).unwrap(), settings);
+        // 前端按原始键名读取
+        assert_eq!(db.get_config(THEME_KEY).unwrap().as_deref(), Some("dark"));
+    }
+
+    #[test]
+    fn invalid_settings_are_not_written() {
+        let db = TestDb::new();
+        let mut settings = Settings {
+            http_address: Some("0.0.0.0:7878".to_string()),
+            ..Default::default()
+        };
+        assert!(settings.save(&*db).is_err());
+        settings.http_token = Some("secret".to_string());
+        settings.save(&*db).unwrap();
+
+        // 非回环地址上清空令牌的写入被拒绝，已保存的值不变
+        let mut changed = Settings::load(&*db).unwrap();
+        assert!(changed.set(HTTP_TOKEN_KEY, "").unwrap());
+        assert!(changed.save(&*db).is_err());
+        let mut changed = Settings::load(&*db).unwrap();
+        assert!(changed.set(ADDRESS_KEY, "nonsense").unwrap());
+        assert!(changed.save(&*db).is_err());
+        assert_eq!(Settings::load(&*db).unwrap(), settings);
+
+        assert!(changed.set(THEME_KEY, "purple").is_err());
+        assert!(changed.set(RETENTION_KEY, "{").is_err());
+        assert!(changed.set(VERSION_KEY, "2").is_err());
+        assert!(!changed.set("column_widths", "[1,2]").unwrap());
+    }
+
+    #[test]
+    fn load_falls_back_on_bad_values() {
+        let db = TestDb::new();
+        db.set_config(ADDRESS_KEY, "nonsense").unwrap();
+        db.set_config(THEME_KEY, "purple").unwrap();
+        db.set_config(RETENTION_KEY, "{").unwrap();
+        db.set_config(UDP_ADDRESS_KEY, "127.0.0.1:5556").unwrap();
+        // 没有令牌的非回环 HTTP 地址不会被启用
+        db.set_config(HTTP_ADDRESS_KEY, "0.0.0.0:7878").unwrap();
+        assert_eq!(Settings::load(&*db).unwrap(), Settings::default());
+
+        db.set_config(VERSION_KEY, &(SETTINGS_VERSION + 1).to_string())
+            .unwrap();
+        assert!(Settings::load(&*db).is_err());
+    }
+
+    #[test]
+    fn retention_applies_age_and_count() {
+        let now = now_micros();
+        let recent = |text| MessageData {
+            time: now,
+            ..message(text)
+        };
+        // message() 的时间戳远早于保留期限
+        let db = TestDb::with([
+            message("old 1"),
+            message("old 2"),
+            recent("new 1"),
+            recent("new 2"),
+            recent("new 3"),
+        ]);
+        let retention = Retention {
+            max_messages: Some(2),
+            max_age_days: Some(1),
+        };
+        assert_eq!(apply_retention(&*db, &retention).unwrap(), 3);
+        let left = db
+            .query_messages(&FilterConfig::default(), &MessageField::Id, &10, &0, false)
+            .unwrap();
+        let ids: Vec<_> = left.iter().map(|m| m.id).collect();
+        assert_eq!(ids, [4, 5]);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::db::{DBMessage, FilterConfig};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+// 有待发送的消息时，刷新线程检查节流窗口的间隔
+const FLUSH_INTERVAL: Duration = Duration::from_millis(10);
+// 每个订阅最多缓存的消息数，超出时丢弃最早的消息
+const MAX_BUFFERED: usize = 10_000;
+
+/// 向订阅者发送一批消息，接收端已关闭时返回 false
+pub type Sink = Box<dyn Fn(Vec<DBMessage>) -> bool + Send + Sync>;
+
+struct Subscription {
+    config: FilterConfig,
+    throttle: Duration,
+    sink: Sink,
+    buffer: VecDeque<DBMessage>,
+    last_flush: Instant,
+}
+
+impl Subscription {
+    fn flush(&mut self) -> bool {
+        self.last_flush = Instant::now();
+        if self.buffer.is_empty() {
+            return true;
+        }
+        (self.sink)(std::mem::take(&mut self.buffer).into())
+    }
+}
+
+/// 服务端过滤的实时日志：每个订阅只接收匹配其 `FilterConfig` 的消息，
+/// 在每个节流窗口内最多合并发送一次
+pub struct SubscriptionManager {
+    next_id: AtomicU64,
+    subscriptions: Arc<Mutex<HashMap<u64, Subscription>>>,
+    flusher: Thread,
+}
+
+impl Default for SubscriptionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubscriptionManager {
+    pub fn new() -> Self {
+        let subscriptions = Arc::new(Mutex::new(HashMap::new()));
+        let weak: Weak<Mutex<HashMap<u64, Subscription>>> = Arc::downgrade(&subscriptions);
+        let flusher = thread::spawn(move || {
+            while let Some(subscriptions) = weak.upgrade() {
+                let pending = {
+                    let mut subscriptions = subscriptions.lock().unwrap();
+                    subscriptions.retain(|_, sub| {
+                        sub.buffer.is_empty()
+                            || sub.last_flush.elapsed() < sub.throttle
+                            || sub.flush()
+                    });
+                    subscriptions.values().any(|sub| !sub.buffer.is_empty())
+                };
+                drop(subscriptions);
+                // 没有待发送的消息时休眠，直到 dispatch 缓存了新消息
+                if pending {
+                    thread::sleep(FLUSH_INTERVAL);
+                } else {
+                    thread::park();
+                }
+            }
+        })
+        .thread()
+        .clone();
+        Self {
+            next_id: AtomicU64::new(1),
+            subscriptions,
+            flusher,
+        }
+    }
+
+    pub fn subscribe<F>(&self, config: FilterConfig, throttle_ms: u64, sink: F) -> u64
+    where
+        F: 'static + Fn(Vec<DBMessage>) -> bool + Send + Sync,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.subscriptions.lock().unwrap().insert(
+            id,
+            Subscription {
+                config,
+                throttle: Duration::from_millis(throttle_ms),
+                sink: Box::new(sink),
+                buffer: VecDeque::new(),
+                last_flush: Instant::now(),
+            },
+        );
+        id
+    }
+
+    pub fn unsubscribe(&self, id: u64) -> bool {
+        self.subscriptions.lock().unwrap().remove(&id).is_some()
+    }
+
+    pub fn count(&self) -> usize {
+        self.subscriptions.lock().unwrap().len()
+    }
+
+    pub fn dispatch(&self, message: &DBMessage) {
+        let mut buffered = false;
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.retain(|_, sub| {
+            if !sub.config.matches(message) {
+                return true;
+            }
+            sub.buffer.push_back(message.clone());
+            if sub.buffer.len() > MAX_BUFFERED {
+                sub.buffer.pop_front();
+            }
+            // 节流窗口已过时立即发送，其余由刷新线程负责
+            if sub.throttle > sub.last_flush.elapsed() {
+                buffered = true;
+                return true;
+            }
+            sub.flush()
+        });
+        drop(subscriptions);
+        if buffered {
+            self.flusher.unpark();
+        }
+    }
+}
+
+impl Drop for SubscriptionManager {
+    fn drop(&mut self) {
+        // 先释放订阅表，被唤醒的刷新线程随后退出
+        self.subscriptions = Arc::new(Mutex::new(HashMap::new()));
+        self.flusher.unpark();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{MessageDB, MessageField};
+    use crate::testing::TestDb;
+    use msg_server::MessageData;
+
+    fn message(i: usize) -> MessageData {
+        MessageData {
+            role: ["Worker", "worker-1", "scheduler"][i % 3].to_string(),
+            label: format!("Stage-{}", i % 4),
+            file: ["src/Main.cc", "src/net.cc"][i % 2].to_string(),
+            function: "run".to_string(),
+            time: 1000 + i,
+            process_id: 100 + i % 2,
+            thread_id: i % 5,
+            line: (i * 7 % 50) as i32,
+            level: (i % 6) as i32,
+            messages: vec![
+                format!("Frame {}", i),
+                "Done \"ok\"".to_string(),
+                ["50% c:\\tmp", "a_b", "axb 50x"][i % 3].to_string(),
+            ],
+            fields: Default::default(),
+        }
+    }
+
+    // 订阅在内存中过滤，结果必须与数据库查询一致
+    #[test]
+    fn matches_agrees_with_sql() {
+        let db = TestDb::with((0..60).map(message));
+        let all = db
+            .query_messages(
+                &FilterConfig::default(),
+                &MessageField::Id,
+                &1000,
+                &0,
+                false,
+            )
+            .unwrap();
+        let filters = [
+            r#"{"role":{"mode":"Equal","value":"worker"}}"#,
+            r#"{"role":{"mode":"Contain","value":"WORKER"}}"#,
+            r#"{"label":{"mode":"Start","value":"stage-1"}}"#,
+            r#"{"file":{"mode":"End","value":"MAIN.CC"}}"#,
+            r#"{"function":{"mode":"Equal","value":"Run"}}"#,
+            r#"{"messages":{"mode":"Contain","value":"frame 1"}}"#,
+            r#"{"messages":{"mode":"Contain","value":"\"ok\""}}"#,
+            // LIKE 通配符和转义字符按字面匹配
+            r#"{"messages":{"mode":"Contain","value":"50%"}}"#,
+            r#"{"messages":{"mode":"Contain","value":"a_b"}}"#,
+            r#"{"messages":{"mode":"Contain","value":"c:\\"}}"#,
+            r#"{"label":{"mode":"Start","value":"stage_"}}"#,
+            r#"{"file":{"mode":"End","value":"%.cc"}}"#,
+            r#"{"level":{"min":2,"max":4},"thread_id":{"min":null,"max":1}}"#,
+            r#"{"id":{"min":10,"max":20},"process_id":{"min":101,"max":null}}"#,
+            r#"{"time":{"min":1010,"max":1040},"line":{"min":20,"max":null}}"#,
+        ];
+        for filter in filters {
+            let config: FilterConfig = serde_json::from_str(filter).unwrap();
+            let expected: Vec<_> = db
+                .query_messages(&config, &MessageField::Id, &1000, &0, false)
+                .unwrap()
+                .into_iter()
+                .map(|m| m.id)
+                .collect();
+            let actual: Vec<_> = all
+                .iter()
+                .filter(|m| config.matches(m))
+                .map(|m| m.id)
+                .collect();
+            assert_eq!(actual, expected, "{}", filter);
+        }
+    }
+
+    #[test]
+    fn throttled_batches() {
+        let manager = SubscriptionManager::new();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let config: FilterConfig =
+            serde_json::from_str(r#"{"level":{"min":3,"max":null}}"#).unwrap();
+        manager.subscribe(config, 50, move |batch| sender.send(batch.len()).is_ok());
+        for i in 0..12 {
+            let data = message(i);
+            manager.dispatch(&DBMessage {
+                id: i + 1,
+                role: data.role,
+                label: data.label,
+                file: data.file,
+                function: data.function,
+                time: data.time,
+                process_id: data.process_id,
+                thread_id: data.thread_id,
+                line: data.line,
+                level: data.level,
+                messages: data.messages,
+                fields: data.fields,
+                styles: None,
+            });
+        }
+        // 节流窗口内的消息由刷新线程合并为一批发送
+        let batch = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(batch, 6);
+        drop(receiver);
+        assert_eq!(manager.count(), 1);
+    }
+}
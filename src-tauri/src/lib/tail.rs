@@ -0,0 +1,555 @@
+use crate::db::Config;
+use crate::template::{LineParser, LineTemplate};
+use msg_server::frame::MAX_FRAME_SIZE;
+use msg_server::MessageData;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// 像 `tail -F` 一样跟踪日志文件，通过 `LineParser` 接收其中的行，续行规则与
+// 文本导入相同：模板不匹配的行追加到上一条消息。
+//
+// 读取偏移量连同所属文件的标识保存在 `app_config` 的 `tail_offset:<path>` 中。
+// 路径上的文件标识变化说明文件被轮转，旧句柄读完后从头读取新文件。文件长度
+// 小于偏移量说明被截断，同样从头读取。
+//
+// 超过 `MAX_FRAME_SIZE` 仍没有换行的行在此处截断并直接解析，没有换行的文件
+// 不会让缓冲区无限增长。
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// 持续有新行时写入偏移量的间隔
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+const OFFSET_KEY_PREFIX: &str = "tail_offset:";
+const READ_CHUNK: usize = 64 * 1024;
+
+type Handler = Arc<RwLock<Box<dyn Fn(MessageData) + Send + Sync>>>;
+
+/// 像网络来源一样被跟踪的日志文件
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TailSource {
+    pub path: String,
+    pub template: LineTemplate,
+    /// 首次遇到的文件从头读取，而不是只读取添加之后追加的行
+    #[serde(default)]
+    pub from_start: bool,
+}
+
+impl TailSource {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.path.trim().is_empty() {
+            return Err("tailed file path cannot be empty".to_string());
+        }
+        LineParser::new(self.template.clone())
+            .map(|_| ())
+            .map_err(|e| format!("invalid template for {}: {}", self.path, e))
+    }
+
+    /// 与其他监听地址一起显示
+    pub fn address(&self) -> String {
+        format!("file://{}", self.path)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+struct TailOffset {
+    file_id: u64,
+    offset: u64,
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+// 没有 inode 时用创建时间区分重新创建的文件
+#[cfg(not(unix))]
+fn file_id(metadata: &Metadata) -> u64 {
+    metadata
+        .created()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+struct OpenFile {
+    file: File,
+    id: u64,
+    // 已作为完整行处理的字节数
+    offset: u64,
+    // 最后一个换行之后已读取的字节
+    partial: Vec<u8>,
+}
+
+struct Tail {
+    path: PathBuf,
+    parser: LineParser,
+    file: Option<OpenFile>,
+    // 下一次在该路径打开文件时的起始位置
+    resume: Option<TailOffset>,
+    from_start: bool,
+    pending: Option<MessageData>,
+    // 待发送消息第一行的偏移量
+    pending_offset: u64,
+}
+
+impl Tail {
+    fn new(source: &TailSource, resume: Option<TailOffset>) -> Result<Self, String> {
+        Ok(Self {
+            path: PathBuf::from(&source.path),
+            parser: LineParser::new(source.template.clone())?,
+            file: None,
+            resume,
+            from_start: source.from_start,
+            pending: None,
+            pending_offset: 0,
+        })
+    }
+
+    /// 要保存的偏移量，重启后重新读取待发送的消息
+    fn offset(&self) -> Option<TailOffset> {
+        self.file.as_ref().map(|file| TailOffset {
+            file_id: file.id,
+            offset: match self.pending {
+                Some(_) => self.pending_offset,
+                None => file.offset,
+            },
+        })
+    }
+
+    fn open(&mut self) -> std::io::Result<()> {
+        let mut file = File::open(&self.path)?;
+        let metadata = file.metadata()?;
+        let id = file_id(&metadata);
+        let offset = match self.resume.take() {
+            Some(resume) if resume.file_id == id && resume.offset <= metadata.len() => {
+                resume.offset
+            }
+            Some(_) => 0,
+            None if self.from_start => 0,
+            None => metadata.len(),
+        };
+        file.seek(SeekFrom::Start(offset))?;
+        self.file = Some(OpenFile {
+            file,
+            id,
+            offset,
+            partial: Vec::new(),
+        });
+        Ok(())
+    }
+
+    fn line(&mut self, line: &[u8], offset: u64, emit: &dyn Fn(MessageData)) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        match self.parser.parse(line) {
+            Some(message) => {
+                if let Some(previous) = self.pending.replace(message) {
+                    emit(previous);
+                }
+                self.pending_offset = offset;
+            }
+            None => {
+                if let Some(previous) = self.pending.as_mut() {
+                    previous.messages.push(line.to_string());
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self, emit: &dyn Fn(MessageData)) {
+        if let Some(previous) = self.pending.take() {
+            emit(previous);
+        }
+    }
+
+    // 读取新追加的内容，返回是否读到了数据
+    fn read(&mut self, closed: &dyn Fn() -> bool, emit: &dyn Fn(MessageData)) -> bool {
+        let mut read_any = false;
+        let mut chunk = vec![0u8; READ_CHUNK];
+        while !closed() {
+            let Some(file) = self.file.as_mut() else {
+                break;
+            };
+            let size = match file.file.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(size) => size,
+                Err(e) => {
+                    eprintln!("failed to read {}: {}", self.path.display(), e);
+                    break;
+                }
+            };
+            read_any = true;
+            // 之前的部分没有换行，只在新读取的内容中查找，长行不会被反复扫描
+            let newline = chunk[..size].iter().rposition(|&b| b == b'\n');
+            let end = newline.map(|i| file.partial.len() + i);
+            file.partial.extend_from_slice(&chunk[..size]);
+            let Some(end) = end else {
+                if file.partial.len() >= MAX_FRAME_SIZE {
+                    let line = std::mem::take(&mut file.partial);
+                    let offset = file.offset;
+                    file.offset += line.len() as u64;
+                    self.line(&line, offset, emit);
+                }
+                continue;
+            };
+            let lines: Vec<u8> = file.partial.drain(..=end).collect();
+            let mut offset = file.offset;
+            file.offset += lines.len() as u64;
+            for line in lines[..end].split(|&b| b == b'\n') {
+                self.line(line, offset, emit);
+                offset += line.len() as u64 + 1;
+            }
+        }
+        read_any
+    }
+
+    // 读完不会再增长的文件的剩余内容，例如轮转之后
+    fn finish(&mut self, emit: &dyn Fn(MessageData)) {
+        if let Some(file) = self.file.take() {
+            if !file.partial.is_empty() {
+                self.line(&file.partial, file.offset, emit);
+            }
+        }
+        self.flush(emit);
+    }
+
+    fn poll(&mut self, closed: &dyn Fn() -> bool, emit: &dyn Fn(MessageData)) {
+        if self.file.is_none() && self.open().is_err() {
+            // 文件尚未创建，或处于轮转与重新创建之间
+            return;
+        }
+        let read_any = self.read(closed, emit);
+        let Some((id, end)) = self
+            .file
+            .as_ref()
+            .map(|file| (file.id, file.offset + file.partial.len() as u64))
+        else {
+            return;
+        };
+        match std::fs::metadata(&self.path) {
+            Ok(metadata) if file_id(&metadata) != id => {
+                // 已轮转，旧句柄刚刚读完
+                self.finish(emit);
+                self.resume = Some(TailOffset {
+                    file_id: file_id(&metadata),
+                    offset: 0,
+                });
+            }
+            Ok(metadata) if metadata.len() < end => {
+                // 原地截断，例如 copytruncate
+                self.flush(emit);
+                let rewound = self.file.as_mut().map(|file| {
+                    file.offset = 0;
+                    file.partial.clear();
+                    file.file.seek(SeekFrom::Start(0))
+                });
+                if let Some(Err(e)) = rewound {
+                    eprintln!("failed to rewind {}: {}", self.path.display(), e);
+                    self.file = None;
+                }
+            }
+            // 文件被移走但尚未重新创建时也保留旧句柄
+            _ => {
+                if !read_any {
+                    // 空闲时最后一条消息不会再有续行
+                    self.flush(emit);
+                }
+            }
+        }
+    }
+}
+
+fn offset_key(path: &str) -> String {
+    format!("{}{}", OFFSET_KEY_PREFIX, path)
+}
+
+fn load_offset(db: &Arc<Mutex<Option<Connection>>>, path: &str) -> Option<TailOffset> {
+    db.get_config(&offset_key(path))
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+}
+
+fn save_offset(db: &Arc<Mutex<Option<Connection>>>, path: &str, offset: TailOffset) {
+    let result = serde_json::to_string(&offset)
+        .map_err(|e| e.to_string())
+        .and_then(|value| db.set_config(&offset_key(path), &value));
+    if let Err(e) = result {
+        eprintln!("failed to save offset of {}: {}", path, e);
+    }
+}
+
+/// 与网络监听生命周期相同的文件来源，不同之处在于 [`close`](Self::close)
+/// 会等待最后的偏移量保存完成
+pub struct FileTailer {
+    source: TailSource,
+    db: Arc<Mutex<Option<Connection>>>,
+    handler_: Handler,
+    closed_: Arc<RwLock<bool>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl FileTailer {
+    pub fn new<F>(source: TailSource, db: Arc<Mutex<Option<Connection>>>, handler: F) -> Self
+    where
+        F: 'static + Fn(MessageData) + Send + Sync,
+    {
+        Self {
+            source,
+            db,
+            handler_: Arc::new(RwLock::new(Box::new(handler))),
+            closed_: Arc::new(RwLock::new(true)),
+            thread: Mutex::new(None),
+        }
+    }
+
+    /// 立即检查模板，文件本身可以稍后出现
+    pub fn run(&self) -> Result<(), String> {
+        if !self.is_closed() {
+            return Ok(());
+        }
+        self.source.validate()?;
+        let mut tail = Tail::new(&self.source, load_offset(&self.db, &self.source.path))?;
+        let path = self.source.path.clone();
+        let db = self.db.clone();
+        let handler = self.handler_.clone();
+        let closed = self.closed_.clone();
+        *closed.write().unwrap() = false;
+        let thread = std::thread::spawn(move || {
+            let is_closed = || *closed.read().unwrap();
+            let emit = |data: MessageData| (handler.read().unwrap())(data);
+            let mut saved = tail.offset();
+            let mut saved_at = Instant::now();
+            while !is_closed() {
+                tail.poll(&is_closed, &emit);
+                if saved_at.elapsed() >= SAVE_INTERVAL && tail.offset() != saved {
+                    saved = tail.offset();
+                    saved_at = Instant::now();
+                    if let Some(offset) = saved {
+                        save_offset(&db, &path, offset);
+                    }
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+            tail.flush(&emit);
+            // 已发送待发送的消息，偏移量不再停留在它之前
+            if let Some(offset) = tail.offset() {
+                save_offset(&db, &path, offset);
+            }
+        });
+        *self.thread.lock().unwrap() = Some(thread);
+        Ok(())
+    }
+
+    pub fn close(&self) {
+        *self.closed_.write().unwrap() = true;
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.closed_.read().unwrap()
+    }
+
+    pub fn set_handler<F>(&self, handler: F)
+    where
+        F: 'static + Fn(MessageData) + Send + Sync,
+    {
+        *self.handler_.write().unwrap() = Box::new(handler);
+    }
+
+    pub fn address(&self) -> String {
+        self.source.address()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{TempDir, TestDb};
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::path::Path;
+
+    fn append(path: &PathBuf, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn source(path: &Path, from_start: bool) -> TailSource {
+        TailSource {
+            path: path.to_string_lossy().to_string(),
+            template: LineTemplate {
+                pattern: String::new(),
+                format: Some("[{level}] {message}".to_string()),
+                time_format: None,
+                role: Some("app".to_string()),
+                label: None,
+                level: None,
+            },
+            from_start,
+        }
+    }
+
+    // 依次轮询直到没有新内容，返回每条消息按 `|` 连接的行
+    fn read_all(tail: &mut Tail) -> Vec<String> {
+        let received = RefCell::new(Vec::new());
+        let emit = |data: MessageData| received.borrow_mut().push(data.messages.join("|"));
+        tail.poll(&|| false, &emit);
+        tail.poll(&|| false, &emit);
+        received.into_inner()
+    }
+
+    fn file_id_of(path: &Path) -> u64 {
+        file_id(&std::fs::metadata(path).unwrap())
+    }
+
+    #[test]
+    fn follows_rotation_and_truncation() {
+        let dir = TempDir::new();
+        let path = dir.join("app.log");
+        append(&path, "[INFO] old\n");
+        let mut tail = Tail::new(&source(&path, false), None).unwrap();
+        let received = RefCell::new(Vec::new());
+        let emit = |data: MessageData| received.borrow_mut().push(data.messages.join("|"));
+        let mut poll = || tail.poll(&|| false, &emit);
+
+        poll();
+        append(&path, "[WARN] first\n  continued\n[ERROR] sec");
+        poll();
+        append(&path, "ond\n");
+        poll();
+        poll();
+        assert_eq!(*received.borrow(), ["first|  continued", "second"]);
+
+        append(&path, "[INFO] last before rotation\n");
+        std::fs::rename(&path, dir.join("app.log.1")).unwrap();
+        append(&path, "[INFO] rotated\n");
+        poll();
+        poll();
+        poll();
+        std::fs::write(&path, "").unwrap();
+        poll();
+        append(&path, "[INFO] after truncation\n");
+        poll();
+        poll();
+        assert_eq!(
+            received.borrow()[2..],
+            ["last before rotation", "rotated", "after truncation"]
+        );
+        assert_eq!(tail.offset().unwrap().offset, 24);
+    }
+
+    #[test]
+    fn stale_offsets_are_ignored() {
+        let dir = TempDir::new();
+        let path = dir.join("app.log");
+        append(&path, "[INFO] one\n[INFO] two\n");
+        let id = file_id_of(&path);
+        let resume = |file_id, offset| Some(TailOffset { file_id, offset });
+
+        let mut tail = Tail::new(&source(&path, false), resume(id, 11)).unwrap();
+        assert_eq!(read_all(&mut tail), ["two"]);
+        // 文件标识不同说明已经换了文件，从头读取
+        let mut tail = Tail::new(&source(&path, false), resume(id + 1, 11)).unwrap();
+        assert_eq!(read_all(&mut tail), ["one", "two"]);
+        // 偏移量超出文件长度说明文件被截断过
+        let mut tail = Tail::new(&source(&path, false), resume(id, 100)).unwrap();
+        assert_eq!(read_all(&mut tail), ["one", "two"]);
+    }
+
+    #[test]
+    fn pending_message_is_read_again_after_restart() {
+        let dir = TempDir::new();
+        let path = dir.join("app.log");
+        append(&path, "[INFO] done\n[WARN] multi\n  line\n");
+        let mut tail = Tail::new(&source(&path, true), None).unwrap();
+        let received = RefCell::new(Vec::new());
+        let emit = |data: MessageData| received.borrow_mut().push(data.messages.join("|"));
+        // 读到数据的这一轮不发送最后一条消息，它可能还有续行
+        tail.poll(&|| false, &emit);
+        assert_eq!(*received.borrow(), ["done"]);
+        let saved = tail.offset();
+        assert_eq!(saved.unwrap().offset, 12);
+
+        // 未正常关闭就重启时，从待发送消息的第一行重新读取
+        drop(tail);
+        append(&path, "  more\n");
+        let mut tail = Tail::new(&source(&path, true), saved).unwrap();
+        assert_eq!(read_all(&mut tail), ["multi|  line|  more"]);
+    }
+
+    #[test]
+    fn overlong_lines_are_cut() {
+        let dir = TempDir::new();
+        let path = dir.join("app.log");
+        let long = format!("{}tail", "x".repeat(MAX_FRAME_SIZE + READ_CHUNK));
+        let text = format!("[INFO] start\n{}\n[INFO] next\n", long);
+        append(&path, &text);
+        let mut tail = Tail::new(&source(&path, true), None).unwrap();
+        let received = RefCell::new(Vec::new());
+        let emit = |data: MessageData| received.borrow_mut().push(data.messages);
+        tail.poll(&|| false, &emit);
+        tail.poll(&|| false, &emit);
+        let received = received.into_inner();
+        assert_eq!(received.len(), 2);
+        let (first, cut) = received[0].split_first().unwrap();
+        assert_eq!(first, "start");
+        // 超长的行被切成多段续行，每段都不超过上限加一次读取的长度
+        assert!(cut.len() >= 2);
+        assert!(cut
+            .iter()
+            .all(|line| line.len() <= MAX_FRAME_SIZE + READ_CHUNK));
+        assert_eq!(cut.concat(), long);
+        assert_eq!(received[1], ["next"]);
+        assert_eq!(tail.offset().unwrap().offset, text.len() as u64);
+    }
+
+    #[test]
+    fn tailer_resumes_from_the_saved_offset() {
+        let dir = TempDir::new();
+        let path = dir.join("app.log");
+        let db = TestDb::new();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let start = || {
+            let sink = received.clone();
+            let tailer = FileTailer::new(source(&path, true), db.clone(), move |data| {
+                sink.lock().unwrap().push(data.messages.join("|"))
+            });
+            tailer.run().unwrap();
+            tailer
+        };
+        let wait_for = |count: usize| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while received.lock().unwrap().len() < count && Instant::now() < deadline {
+                std::thread::sleep(POLL_INTERVAL / 4);
+            }
+            std::mem::take(&mut *received.lock().unwrap())
+        };
+
+        append(&path, "[INFO] one\n[INFO] two\n");
+        let tailer = start();
+        assert_eq!(wait_for(2), ["one", "two"]);
+        tailer.close();
+        let saved = load_offset(&db, &path.to_string_lossy()).unwrap();
+        assert_eq!((saved.file_id, saved.offset), (file_id_of(&path), 22));
+
+        // 关闭期间追加的行在重启后读取，已读的行不会重复
+        append(&path, "[INFO] three\n");
+        let tailer = start();
+        assert_eq!(wait_for(1), ["three"]);
+        tailer.close();
+    }
+}
@@ -0,0 +1,208 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use msg_server::level::parse_level;
+use msg_server::MessageData;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// 描述如何将一行纯文本日志解析为 `MessageData`
+///
+/// `pattern` 为正则表达式，其命名分组填充消息的对应字段：`time`、`level`、
+/// `role`、`label`、`file`、`line`、`function`、`process_id`、`thread_id` 和
+/// `message`，缺少的分组使用下面的默认值
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LineTemplate {
+    #[serde(default)]
+    pub pattern: String,
+    /// `pattern` 的简写形式，例如 `{time} [{level}] {message}`
+    /// 设置后代替 `pattern` 使用，见 [`format_pattern`]
+    #[serde(default)]
+    pub format: Option<String>,
+    /// `time` 分组的 chrono 时间格式，或 `"rfc3339"`
+    /// 未设置时按自纪元起的微秒数读取
+    #[serde(default)]
+    pub time_format: Option<String>,
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub level: Option<i32>,
+}
+
+pub struct LineParser {
+    regex: Regex,
+    template: LineTemplate,
+}
+
+pub fn now_micros() -> usize {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_micros() as usize)
+        .unwrap_or(0)
+}
+
+fn parse_time(value: &str, format: Option<&str>) -> Option<usize> {
+    let value = value.trim();
+    match format {
+        None => value.parse::<usize>().ok(),
+        Some("rfc3339") => DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|t| t.timestamp_micros() as usize),
+        Some(format) => DateTime::parse_from_str(value, format)
+            .map(|t| t.timestamp_micros())
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(value, format).map(|t| {
+                    Local
+                        .from_local_datetime(&t)
+                        .earliest()
+                        .map(|t| t.timestamp_micros())
+                        .unwrap_or(t.and_utc().timestamp_micros())
+                })
+            })
+            .ok()
+            .map(|t| t as usize),
+    }
+}
+
+const GROUPS: [&str; 10] = [
+    "time",
+    "level",
+    "role",
+    "label",
+    "file",
+    "line",
+    "function",
+    "process_id",
+    "thread_id",
+    "message",
+];
+
+/// 将模板格式转换为首尾锚定的正则表达式。`{name}` 以非贪婪方式捕获上面的某个分组，
+/// 最后一个占位符匹配行的剩余部分，`{name:regex}` 为分组指定自己的正则。
+/// 其余文本按字面匹配，连续的空白可以匹配任意空白
+pub fn format_pattern(format: &str) -> Result<String, String> {
+    let mut pattern = String::from("^");
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        push_literal(&mut pattern, &rest[..start]);
+        // 自定义分组中可以嵌套花括号，例如 `{time:\d{4}-\d{2}}`
+        let mut depth = 0;
+        let end = rest[start..]
+            .find(|c| {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })
+            .map(|end| start + end)
+            .ok_or_else(|| format!("unclosed placeholder in template: {}", format))?;
+        let placeholder = &rest[start + 1..end];
+        let (name, group) = match placeholder.split_once(':') {
+            Some((name, group)) => (name, Some(group)),
+            None => (placeholder, None),
+        };
+        if !GROUPS.contains(&name) {
+            return Err(format!("unknown template field: {}", name));
+        }
+        rest = &rest[end + 1..];
+        let group = group.unwrap_or(if rest.trim().is_empty() { ".*" } else { ".*?" });
+        pattern.push_str(&format!("(?P<{}>{})", name, group));
+    }
+    push_literal(&mut pattern, rest);
+    pattern.push('$');
+    Ok(pattern)
+}
+
+fn push_literal(pattern: &mut String, literal: &str) {
+    let mut words = literal.split(char::is_whitespace).peekable();
+    while let Some(word) = words.next() {
+        pattern.push_str(&regex::escape(word));
+        if words.peek().is_some() && !pattern.ends_with(r"\s+") {
+            pattern.push_str(r"\s+");
+        }
+    }
+}
+
+impl LineParser {
+    pub fn new(template: LineTemplate) -> Result<Self, String> {
+        let pattern = match &template.format {
+            Some(format) => format_pattern(format)?,
+            None => template.pattern.clone(),
+        };
+        let regex = Regex::new(&pattern).map_err(|e| e.to_string())?;
+        Ok(Self { regex, template })
+    }
+
+    /// 解析一行日志，不匹配时返回 `None`
+    pub fn parse(&self, line: &str) -> Option<MessageData> {
+        let caps = self.regex.captures(line)?;
+        let get = |name: &str| caps.name(name).map(|m| m.as_str());
+        let message = get("message")
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| line.to_string());
+        Some(MessageData {
+            role: get("role")
+                .map(|s| s.to_string())
+                .or(self.template.role.clone())
+                .unwrap_or_default(),
+            label: get("label")
+                .map(|s| s.to_string())
+                .or(self.template.label.clone())
+                .unwrap_or_default(),
+            file: get("file").unwrap_or_default().to_string(),
+            function: get("function").unwrap_or_default().to_string(),
+            time: get("time")
+                .and_then(|t| parse_time(t, self.template.time_format.as_deref()))
+                .unwrap_or_else(now_micros),
+            process_id: get("process_id")
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(0),
+            thread_id: get("thread_id")
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(0),
+            line: get("line").and_then(|s| s.trim().parse().ok()).unwrap_or(0),
+            level: get("level")
+                .and_then(parse_level)
+                .or(self.template.level)
+                .unwrap_or(msg_server::level::INFO),
+            messages: vec![message],
+            fields: Default::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_templates() {
+        assert_eq!(
+            format_pattern("{time} [{level}]  {message}").unwrap(),
+            r"^(?P<time>.*?)\s+\[(?P<level>.*?)\]\s+(?P<message>.*)$"
+        );
+        assert!(format_pattern("{when} {message}").is_err());
+        assert!(format_pattern("{message").is_err());
+        let parser = LineParser::new(LineTemplate {
+            pattern: String::new(),
+            format: Some(
+                r"{time:\d{4}-\d{2}-\d{2} [\d:]+} {level} {file}:{line} {message}".to_string(),
+            ),
+            time_format: Some("%Y-%m-%d %H:%M:%S".to_string()),
+            role: None,
+            label: None,
+            level: None,
+        })
+        .unwrap();
+        let data = parser
+            .parse("2024-05-01 12:00:00 error main.rs:42 disk full")
+            .unwrap();
+        assert_eq!(
+            (data.level, data.file.as_str(), data.line),
+            (4, "main.rs", 42)
+        );
+        assert_eq!(data.messages, ["disk full"]);
+    }
+}
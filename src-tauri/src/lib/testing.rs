@@ -0,0 +1,91 @@
+// 单元测试共用的临时目录、数据库与消息构造
+use crate::db::{MessageDB, DB};
+use crate::pipeline::MessagePipeline;
+use msg_server::MessageData;
+use rusqlite::Connection;
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+// 每次调用都得到一个新目录，进程号区分并行运行的测试程序，析构时删除
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "xclogger-test-{}-{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// 临时目录中的消息数据库，解引用为 Arc<Mutex<Option<Connection>>>
+pub struct TestDb {
+    db: Arc<Mutex<Option<Connection>>>,
+    pub dir: TempDir,
+}
+
+impl TestDb {
+    pub fn new() -> Self {
+        let dir = TempDir::new();
+        let db = Arc::new(Mutex::new(Option::<Connection>::None));
+        db.connect(&dir.join("messages.db")).unwrap();
+        Self { db, dir }
+    }
+
+    // 按顺序插入，id 从 1 开始
+    pub fn with(messages: impl IntoIterator<Item = MessageData>) -> Self {
+        let db = Self::new();
+        for message in messages {
+            db.insert_message(&message).unwrap();
+        }
+        db
+    }
+
+    pub fn pipeline(&self) -> Arc<MessagePipeline> {
+        Arc::new(MessagePipeline::new(self.db.clone()))
+    }
+}
+
+impl Deref for TestDb {
+    type Target = Arc<Mutex<Option<Connection>>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.db
+    }
+}
+
+// 元数据固定的单行消息，个别字段用结构体更新语法覆盖：
+// `MessageData { level: 4, ..message("text") }`
+pub fn message(text: &str) -> MessageData {
+    MessageData {
+        role: "worker".to_string(),
+        label: "test".to_string(),
+        file: "main.cc".to_string(),
+        function: "run".to_string(),
+        time: 1000,
+        process_id: 1,
+        thread_id: 2,
+        line: 3,
+        level: 2,
+        messages: vec![text.to_string()],
+        fields: Default::default(),
+    }
+}
@@ -51,6 +51,16 @@ export interface FilterConfig {
     line?: NumberRange;
     messages?: StringPattern;
 }
+export interface DistinctQuery {
+    config?: FilterConfig;
+    prefix?: string;
+    limit?: number;
+    by_frequency?: boolean;
+}
+export interface FieldCount {
+    value: string | number | null;
+    count: number;
+}
 
 export interface IClient {
     set(key: string, value: string): void;
@@ -62,6 +72,6 @@ export interface IClient {
     stop_server(): void;
     filter_messages(config: FilterConfig, oeder: MessageField, limit: number, offset: number, desc: boolean): Promise<Array<Message>>;
     filter_messages_count(config: FilterConfig): Promise<number>;
-    get_distinct(field: MessageField): Promise<Array<string>>;
+    get_distinct(field: MessageField, query?: DistinctQuery): Promise<Array<FieldCount>>;
     delete_messages(config: FilterConfig): Promise<number>;
 }
\ No newline at end of file
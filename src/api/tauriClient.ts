@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
-import { FilterConfig, IClient, Message, MessageField } from "./client";
+import { DistinctQuery, FieldCount, FilterConfig, IClient, Message, MessageField } from "./client";
 import { listen } from "@tauri-apps/api/event";
 
 export interface TauriParam {
@@ -76,11 +76,10 @@ export class TauriClient implements IClient {
             throw error; // 重新抛出错误以便调用者处理
         }
     }
-    async get_distinct(field: MessageField): Promise<Array<string>> {
+    async get_distinct(field: MessageField, query?: DistinctQuery): Promise<Array<FieldCount>> {
         try {
-            const res = await invoke<string>(TauriCommands.GetDistinct, { field });
-            console.log("distinct result:", res);
-            return JSON.parse(res) as Array<string>;
+            const res = await invoke<string>(TauriCommands.GetDistinct, { field, query });
+            return JSON.parse(res) as Array<FieldCount>;
         } catch (error) {
             console.error("Error invoking 'get_distinct':", error);
             throw error; // 重新抛出错误以便调用者处理
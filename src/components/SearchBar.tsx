@@ -23,9 +23,12 @@ import {
   ArrowUpward as ArrowUpwardIcon,
   FilterList as FilterListIcon
 } from '@mui/icons-material';
-import { FilterConfig, MessageField, PatternMode } from '../api/client';
+import { DistinctQuery, FilterConfig, MessageField, PatternMode } from '../api/client';
 import client from '../api/tauriClient';
 
+// Most frequent values first, enough for the dropdowns
+const DISTINCT_QUERY: DistinctQuery = { by_frequency: true, limit: 500 };
+
 interface SearchBarProps {
   onSearch: (config: FilterConfig, orderBy: MessageField, desc: boolean) => void;
   onReset: () => void;
@@ -96,7 +99,7 @@ const SearchBar: React.FC<SearchBarProps> = ({ onSearch, onReset }) => {
     // Load label options
     setLoadingLabel(true);
     try {
-      const labels = await client.get_distinct(MessageField.Label);
+      const labels = (await client.get_distinct(MessageField.Label, DISTINCT_QUERY)).map(c => String(c.value ?? ''));
       setLabelOptions(labels.filter(label => label && label.trim() !== ''));
     } catch (error) {
       console.error('Failed to load label options:', error);
@@ -107,7 +110,7 @@ const SearchBar: React.FC<SearchBarProps> = ({ onSearch, onReset }) => {
     // Load role options
     setLoadingRole(true);
     try {
-      const roles = await client.get_distinct(MessageField.Role);
+      const roles = (await client.get_distinct(MessageField.Role, DISTINCT_QUERY)).map(c => String(c.value ?? ''));
       setRoleOptions(roles.filter(role => role && role.trim() !== ''));
     } catch (error) {
       console.error('Failed to load role options:', error);
@@ -118,7 +121,7 @@ const SearchBar: React.FC<SearchBarProps> = ({ onSearch, onReset }) => {
     // Load file options
     setLoadingFile(true);
     try {
-      const files = await client.get_distinct(MessageField.file);
+      const files = (await client.get_distinct(MessageField.file, DISTINCT_QUERY)).map(c => String(c.value ?? ''));
       setFileOptions(files.filter(file => file && file.trim() !== ''));
     } catch (error) {
       console.error('Failed to load file options:', error);
@@ -129,7 +132,7 @@ const SearchBar: React.FC<SearchBarProps> = ({ onSearch, onReset }) => {
     // Load function options
     setLoadingFunction(true);
     try {
-      const functions = await client.get_distinct(MessageField.function);
+      const functions = (await client.get_distinct(MessageField.function, DISTINCT_QUERY)).map(c => String(c.value ?? ''));
       setFunctionOptions(functions.filter(func => func && func.trim() !== ''));
     } catch (error) {
       console.error('Failed to load function options:', error);